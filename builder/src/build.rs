@@ -0,0 +1,514 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+use liblis::format::PackageWriter;
+use liblis::platform::BinaryPlatform;
+
+use crate::project::Project;
+
+/// Events reported by a running build
+pub enum BuildEvent {
+    /// A line for the build log
+    Log(String),
+    /// Progress over all bytes copied and archived
+    Progress {
+        current_file: String,
+        bytes_done: u64,
+        bytes_total: u64,
+    },
+    /// The build ended, with the package path on success
+    Finished(Result<PathBuf, String>),
+}
+
+/// Build the package on a worker thread. Setting `cancel` stops it and removes partial output.
+pub fn spawn_build(project: Project, cancel: Arc<AtomicBool>) -> mpsc::Receiver<BuildEvent> {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let result = build_package(&project, &cancel, &sender);
+        let _ = sender.send(BuildEvent::Finished(result));
+    });
+
+    receiver
+}
+
+/// Path of the package produced by a project
+pub fn output_path(project: &Project) -> PathBuf {
+    project.metadata.output_directory.join(&project.package_name)
+}
+
+/// Keeps track of the bytes processed and reports them
+struct Progress<'a> {
+    sender: &'a mpsc::Sender<BuildEvent>,
+    bytes_done: u64,
+    bytes_total: u64,
+}
+
+impl Progress<'_> {
+    fn log(&self, msg: &str) {
+        let _ = self.sender.send(BuildEvent::Log(format!("-> {}", msg)));
+    }
+
+    fn advance(&mut self, current_file: &str, bytes: u64) {
+        self.bytes_done += bytes;
+        let _ = self.sender.send(BuildEvent::Progress {
+            current_file: current_file.to_string(),
+            bytes_done: self.bytes_done,
+            bytes_total: self.bytes_total,
+        });
+    }
+}
+
+/// Copy `src` to `dest` in chunks, reporting progress and honouring cancellation
+fn copy_file(src: &Path, dest: &Path, label: &str, cancel: &AtomicBool, progress: &mut Progress) -> Result<(), String> {
+    let mut reader = File::open(src).map_err(|e| format!("Could not open {}: {}", src.display(), e))?;
+    let mut writer = File::create(dest).map_err(|e| format!("Could not create {}: {}", dest.display(), e))?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Build cancelled by user".to_string());
+        }
+        let read = reader.read(&mut buffer).map_err(|e| format!("Could not read {}: {}", src.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).map_err(|e| format!("Could not write {}: {}", dest.display(), e))?;
+        progress.advance(label, read as u64);
+    }
+    Ok(())
+}
+
+/// Generate comprehensive metadata.toml contents with all project information
+fn package_metadata(project: &Project, platform: Option<&BinaryPlatform>) -> liblis::Metadata {
+    let metadata = &project.metadata;
+
+    // Convert project files to metadata format
+    let metadata_files: Vec<liblis::metadata::FileEntry> = project.files.iter().map(|f| {
+        liblis::metadata::FileEntry {
+            source: f.destination.clone(), // In the .lis, it's in application/ folder
+            destination: f.destination.clone(),
+            permissions: f.permissions.clone(),
+        }
+    }).collect();
+
+    // Convert installer screens to metadata format
+    let metadata_screens: Vec<liblis::metadata::InstallerScreen> = project.installer_screens.iter().map(|s| {
+        liblis::metadata::InstallerScreen {
+            id: s.id.clone(),
+            enabled: s.enabled,
+            order: s.order,
+            custom_content: s.custom_content.clone(),
+        }
+    }).collect();
+
+    liblis::Metadata {
+        package: liblis::metadata::PackageInfo {
+            name: metadata.name.clone(),
+            version: metadata.version.clone(),
+            app_id: metadata.app_id.clone(),
+            description: metadata.description.clone(),
+            author: metadata.author.clone(),
+            application_name: metadata.application_name.clone(),
+            package_name: project.package_name.clone(),
+            compression_level: project.compression_level,
+            architecture: platform.map(|p| p.architecture.clone()),
+            libc: platform.and_then(|p| p.libc.clone()),
+        },
+        installation: liblis::metadata::InstallationInfo {
+            prefix_system: "/usr/local".to_string(),
+            prefix_user: "~/.local".to_string(),
+        },
+        desktop: liblis::metadata::DesktopInfo {
+            name: metadata.application_name.clone(),
+            exec: desktop_exec(project).unwrap_or_else(|| metadata.name.to_lowercase().replace(" ", "-")),
+            icon: String::new(),
+            categories: vec!["Utility".to_string()],
+        },
+        dependencies: liblis::metadata::DependenciesInfo {
+            bundled: vec![],
+            requires: project.relations.requires.clone(),
+            conflicts: project.relations.conflicts.clone(),
+            provides: project.relations.provides.clone(),
+        },
+        requirements: liblis::metadata::RequirementsInfo {
+            min_gtk_version: non_empty(&project.requirements.min_gtk_version),
+            min_libadwaita_version: non_empty(&project.requirements.min_libadwaita_version),
+            architectures: project.requirements.architectures.clone(),
+            libraries: project.requirements.libraries.clone(),
+        },
+        hooks: liblis::metadata::HooksInfo {
+            pre_install: hook_script(&project.hooks.pre_install, "pre_install"),
+            post_install: hook_script(&project.hooks.post_install, "post_install"),
+            pre_uninstall: hook_script(&project.hooks.pre_uninstall, "pre_uninstall"),
+            post_uninstall: hook_script(&project.hooks.post_uninstall, "post_uninstall"),
+            ..Default::default()
+        },
+        files: metadata_files,
+        installer_screens: metadata_screens,
+        delta: None,
+    }
+}
+
+/// Value of a key of the project's desktop file (e.g., "Icon")
+pub fn desktop_value(project: &Project, key: &str) -> Option<String> {
+    let desktop_file = project.metadata.desktop_file.as_ref()?;
+    let content = fs::read_to_string(desktop_file).ok()?;
+    content.lines()
+        .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim().to_string())
+}
+
+/// Program started by the project's desktop file: the first word of its
+/// `Exec` line without the directory (e.g., "example-rust")
+pub fn desktop_exec(project: &Project) -> Option<String> {
+    let exec = desktop_value(project, "Exec")?;
+    let exec = exec.split_whitespace().next()?;
+    Path::new(exec).file_name().map(|name| name.to_string_lossy().to_string())
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Path of a hook script inside install/, if the project has one
+fn hook_script(source: &Option<PathBuf>, name: &str) -> Option<String> {
+    source.as_ref().map(|_| format!("hooks/{}", name))
+}
+
+/// Detect the architecture and libc of the project's binaries.
+/// Files built for different platforms can't be shipped in one package.
+fn detect_platform(project: &Project, progress: &Progress) -> Result<Option<BinaryPlatform>, String> {
+    let mut architecture: Option<(String, &str)> = None;
+    let mut libc: Option<(String, &str)> = None;
+
+    for file_entry in project.files.iter().filter(|f| f.source.exists()) {
+        let Some(platform) = liblis::platform::detect(&file_entry.source).map_err(|e| format!("{:#}", e))? else {
+            continue;
+        };
+        progress.log(&format!("Detected platform of {}: {}", file_entry.destination, platform));
+
+        match &architecture {
+            Some((first_arch, first_file)) if *first_arch != platform.architecture => {
+                return Err(format!(
+                    "Files built for different architectures: {} is {} but {} is {}",
+                    first_file, first_arch, file_entry.destination, platform.architecture
+                ));
+            }
+            Some(_) => {}
+            None => architecture = Some((platform.architecture, &file_entry.destination)),
+        }
+
+        if let Some(file_libc) = platform.libc {
+            match &libc {
+                Some((first_libc, first_file)) if *first_libc != file_libc => {
+                    return Err(format!(
+                        "Files linked against different libc: {} uses {} but {} uses {}",
+                        first_file, first_libc, file_entry.destination, file_libc
+                    ));
+                }
+                Some(_) => {}
+                None => libc = Some((file_libc, &file_entry.destination)),
+            }
+        }
+    }
+
+    Ok(architecture.map(|(architecture, _)| BinaryPlatform {
+        architecture,
+        libc: libc.map(|(libc, _)| libc),
+    }))
+}
+
+/// Add every file under `dir` to the archive below `name`
+fn append_dir<W: Write>(
+    writer: &mut PackageWriter<W>,
+    name: &str,
+    dir: &Path,
+    cancel: &AtomicBool,
+    progress: &mut Progress,
+) -> Result<(), String> {
+    writer.append_dir_all(name, dir, |archive_name, size| {
+        if cancel.load(Ordering::Relaxed) {
+            anyhow::bail!("Build cancelled by user");
+        }
+        progress.advance(&archive_name.to_string_lossy(), size);
+        Ok(())
+    })
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Build the .lis package of a project
+fn build_package(project: &Project, cancel: &AtomicBool, sender: &mpsc::Sender<BuildEvent>) -> Result<PathBuf, String> {
+    // Directories and patterns are expanded now, so new files are picked up
+    let project = &project.expanded()?;
+    let output_dir = &project.metadata.output_directory;
+    let output_path = output_path(project);
+
+    // Every byte is copied to the temp directory and then archived
+    let bytes_total: u64 = project.files.iter()
+        .map(|f| &f.source)
+        .chain(project.hooks.defined().into_iter().map(|(_, path)| path))
+        .filter_map(|source| fs::metadata(source).ok())
+        .map(|m| m.len() * 2)
+        .sum();
+    let mut progress = Progress { sender, bytes_done: 0, bytes_total };
+
+    progress.log("Starting build process...");
+    progress.log(&format!("{} files to package", project.files.len()));
+
+    let platform = detect_platform(project, &progress)?;
+    match &platform {
+        Some(platform) => progress.log(&format!("Package platform: {}", platform)),
+        None => progress.log("No binaries found, the package is architecture-independent"),
+    }
+
+    // Ensure output directory exists
+    if !output_dir.exists() {
+        progress.log(&format!("Creating output directory: {:?}", output_dir));
+        fs::create_dir_all(output_dir)
+            .map_err(|e| format!("Could not create output directory: {}", e))?;
+    }
+
+    // Create unique temporary directory
+    let temp_dir = std::env::temp_dir().join(format!("obision-build-{}", std::process::id()));
+    progress.log(&format!("Creating temporary build directory: {:?}", temp_dir));
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Could not create temp directory: {}", e))?;
+
+    // The package is written next to the output and renamed once complete,
+    // so a failed or cancelled build never leaves a truncated file behind
+    let partial_path = output_path.with_extension("lis.part");
+
+    let result = write_package(project, platform.as_ref(), &temp_dir, &partial_path, cancel, &mut progress)
+        .and_then(|()| {
+            fs::rename(&partial_path, &output_path)
+                .map_err(|e| format!("Could not create package file: {}", e))
+        });
+
+    progress.log("Cleaning up temporary directory...");
+    let _ = fs::remove_dir_all(&temp_dir);
+
+    match result {
+        Ok(()) => {
+            progress.log("Build Successful!");
+            Ok(output_path)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial_path);
+            progress.log(&format!("ERROR: {}", e));
+            Err(e)
+        }
+    }
+}
+
+fn write_package(
+    project: &Project,
+    platform: Option<&BinaryPlatform>,
+    temp_dir: &Path,
+    package_path: &Path,
+    cancel: &AtomicBool,
+    progress: &mut Progress,
+) -> Result<(), String> {
+    // The app ID names files in the package, so it is checked first
+    liblis::install::validate_app_id(&project.metadata.app_id)
+        .map_err(|e| e.to_string())?;
+
+    // Create install/ and application/ directories
+    let install_dir = temp_dir.join("install");
+    let application_dir = temp_dir.join("application");
+    fs::create_dir(&install_dir)
+        .map_err(|e| format!("Could not create install directory: {}", e))?;
+    fs::create_dir(&application_dir)
+        .map_err(|e| format!("Could not create application directory: {}", e))?;
+
+    // === POPULATE INSTALL FOLDER ===
+    progress.log("Populating install/ folder...");
+
+    // Copy .desktop file to install/
+    if let Some(ref desktop_file) = project.metadata.desktop_file {
+        if desktop_file.exists() {
+            // Named after the app ID, like the installed desktop file
+            let filename = format!("{}.desktop", project.metadata.app_id);
+            let dest = install_dir.join(&filename);
+            progress.log(&format!("Copying: {} -> install/{}", desktop_file.display(), filename));
+            if let Err(e) = fs::copy(desktop_file, &dest) {
+                progress.log(&format!("ERROR copying desktop file: {}", e));
+            }
+        } else {
+            progress.log("WARNING: Desktop file not found");
+        }
+    } else {
+        progress.log("WARNING: No desktop file specified");
+    }
+
+    // Hook scripts go to install/hooks/; the installer can't run a missing one
+    let hooks = project.hooks.defined();
+    if !hooks.is_empty() {
+        let hooks_dir = install_dir.join("hooks");
+        fs::create_dir(&hooks_dir)
+            .map_err(|e| format!("Could not create hooks directory: {}", e))?;
+        for (name, source) in hooks {
+            if !source.exists() {
+                return Err(format!("The {} hook script was not found: {}", name, source.display()));
+            }
+            progress.log(&format!("Copying: {} -> install/hooks/{}", source.display(), name));
+            copy_file(source, &hooks_dir.join(name), &format!("hooks/{}", name), cancel, progress)?;
+        }
+    }
+
+    // === POPULATE APPLICATION FOLDER ===
+    progress.log("Populating application/ folder...");
+
+    for file_entry in &project.files {
+        if file_entry.source.exists() {
+            let dest = application_dir.join(&file_entry.destination);
+            progress.log(&format!("Copying: {} -> application/{}", file_entry.source.display(), file_entry.destination));
+
+            // Create parent directories if needed
+            if let Some(parent) = dest.parent() {
+                if let Err(e) = fs::create_dir_all(parent) {
+                    progress.log(&format!("ERROR creating directory for {}: {}", file_entry.destination, e));
+                    continue;
+                }
+            }
+
+            copy_file(&file_entry.source, &dest, &file_entry.destination, cancel, progress)?;
+        } else {
+            progress.log(&format!("WARNING: File not found: {}", file_entry.source.display()));
+        }
+    }
+
+    // === CREATE .LIS ARCHIVE FROM TEMP DIRECTORY ===
+    progress.log(&format!("Creating package file: {:?}", package_path));
+    let f = File::create(package_path)
+        .map_err(|e| format!("Could not create package file: {}", e))?;
+
+    // Entries are sorted and normalized so the same project always gives the same bytes
+    let mut writer = PackageWriter::new(f);
+
+    progress.log("Adding install/ directory to archive...");
+    append_dir(&mut writer, "install", &install_dir, cancel, progress)?;
+
+    progress.log("Adding application/ directory to archive...");
+    append_dir(&mut writer, "application", &application_dir, cancel, progress)?;
+
+    progress.log("Generating metadata.toml...");
+    let metadata = package_metadata(project, platform);
+    liblis::Version::parse(&metadata.package.version)
+        .map_err(|e| e.to_string())?;
+    liblis::relations::PackageRelations::from_metadata(&metadata)
+        .map_err(|e| format!("Invalid package relations: {:#}", e))?;
+    writer.append_metadata(&metadata)
+        .map_err(|e| format!("Error adding metadata: {:#}", e))?;
+
+    progress.log("Finalizing archive...");
+    writer.finish()
+        .map_err(|e| format!("Error finalizing archive: {:#}", e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::FileTimes;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    use liblis::hash::sha256_file;
+
+    use super::*;
+    use crate::project::ProjectFile;
+
+    /// Sources of the project, in the order they are created
+    const SOURCES: [(&str, &str, bool); 5] = [
+        ("hello.desktop", "[Desktop Entry]\nName=Hello\nExec=hello\n", false),
+        ("post-install.sh", "#!/bin/sh\necho installed\n", true),
+        ("target/hello", "#!/bin/sh\necho hello\n", true),
+        ("data/a.txt", "a\n", false),
+        ("data/icons/b.svg", "<svg/>\n", false),
+    ];
+
+    /// Write the sources in `dir`. `variant` changes the order they are
+    /// created in, their modification times and their permissions.
+    fn write_sources(dir: &Path, variant: u64) {
+        let mut sources = SOURCES.to_vec();
+        if variant % 2 == 1 {
+            sources.reverse();
+        }
+        for (path, content, executable) in sources {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+
+            let mode = match (executable, variant % 2) {
+                (true, 0) => 0o755,
+                (true, _) => 0o700,
+                (false, 0) => 0o644,
+                (false, _) => 0o600,
+            };
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(3600 * variant);
+            File::options().write(true).open(&path).unwrap()
+                .set_times(FileTimes::new().set_modified(modified))
+                .unwrap();
+        }
+    }
+
+    fn project(dir: &Path) -> Project {
+        let mut project = Project::new();
+        project.metadata.name = "Hello".to_string();
+        project.metadata.app_id = "com.example.Hello".to_string();
+        project.metadata.application_name = "Hello".to_string();
+        project.metadata.output_directory = dir.join("dist");
+        project.metadata.desktop_file = Some(dir.join("hello.desktop"));
+        project.files = vec![
+            ProjectFile {
+                source: dir.join("target/hello"),
+                destination: "bin/hello".to_string(),
+                permissions: Some("755".to_string()),
+                include: Vec::new(),
+                exclude: Vec::new(),
+            },
+            ProjectFile {
+                source: dir.join("data"),
+                destination: "share/hello".to_string(),
+                permissions: None,
+                include: Vec::new(),
+                exclude: Vec::new(),
+            },
+        ];
+        project.hooks.post_install = Some(dir.join("post-install.sh"));
+        project.package_name = "hello.lis".to_string();
+        project
+    }
+
+    /// Build the project like the builder window does and hash the package
+    fn build(dir: &Path, variant: u64) -> String {
+        write_sources(dir, variant);
+
+        let (sender, receiver) = mpsc::channel();
+        let result = build_package(&project(dir), &AtomicBool::new(false), &sender);
+        drop(sender);
+        let package = result.unwrap_or_else(|e| {
+            let log: Vec<String> = receiver.iter()
+                .filter_map(|event| match event {
+                    BuildEvent::Log(line) => Some(line),
+                    _ => None,
+                })
+                .collect();
+            panic!("{}\n{}", e, log.join("\n"))
+        });
+
+        sha256_file(&package).unwrap()
+    }
+
+    #[test]
+    fn same_project_builds_identical_packages() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        assert_eq!(build(first.path(), 0), build(second.path(), 1));
+    }
+}
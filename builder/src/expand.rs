@@ -0,0 +1,222 @@
+use std::path::{Component, Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+use crate::project::{Project, ProjectFile};
+
+/// Whether a path component has glob syntax
+fn has_glob_syntax(component: &str) -> bool {
+    component.contains(['*', '?', '[', '{'])
+}
+
+/// `*` and `?` don't match '/', `**` matches any number of directories
+fn glob(pattern: &str) -> Result<globset::Glob, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(glob(pattern)?);
+    }
+    builder.build().map_err(|e| format!("Invalid patterns: {}", e))
+}
+
+impl ProjectFile {
+    /// Whether the source is a directory or a glob pattern rather than a single file
+    pub fn is_pattern(&self) -> bool {
+        self.source.is_dir() || has_glob_syntax(&self.source.to_string_lossy())
+    }
+
+    /// Split a pattern source into the directory to walk and the pattern of
+    /// the files below it; a directory matches every file
+    fn base_and_pattern(&self) -> Result<(PathBuf, Option<GlobMatcher>), String> {
+        let mut base = PathBuf::new();
+        let mut components = self.source.components();
+        for component in components.by_ref() {
+            if let Component::Normal(name) = component {
+                if has_glob_syntax(&name.to_string_lossy()) {
+                    let rest: PathBuf = std::iter::once(component).chain(components).collect();
+                    let pattern = rest.to_string_lossy().to_string();
+                    return Ok((base, Some(glob(&pattern)?.compile_matcher())));
+                }
+            }
+            base.push(component);
+        }
+        Ok((base, None))
+    }
+
+    /// The files of the entry: itself for a single file, or every file of the
+    /// directory or pattern that passes the include and exclude rules, sorted
+    pub fn expand(&self) -> Result<Vec<ProjectFile>, String> {
+        if !self.is_pattern() {
+            return Ok(vec![self.clone()]);
+        }
+
+        let (base, pattern) = self.base_and_pattern()?;
+        if !base.is_dir() {
+            return Err(format!("Directory not found: {}", base.display()));
+        }
+        let include = glob_set(&self.include)?;
+        let exclude = glob_set(&self.exclude)?;
+
+        let mut files = Vec::new();
+        for entry in WalkDir::new(&base).sort_by_file_name() {
+            let entry = entry.map_err(|e| format!("Could not read {}: {}", base.display(), e))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(&base).unwrap_or(entry.path());
+            let matches = pattern.as_ref().is_none_or(|p| p.is_match(relative))
+                && (self.include.is_empty() || include.is_match(relative))
+                && !exclude.is_match(relative);
+            if !matches {
+                continue;
+            }
+
+            let destination = Path::new(self.destination.trim_end_matches('/')).join(relative);
+            files.push(ProjectFile {
+                source: entry.path().to_path_buf(),
+                destination: destination.to_string_lossy().to_string(),
+                permissions: self.permissions.clone(),
+                include: Vec::new(),
+                exclude: Vec::new(),
+            });
+        }
+        Ok(files)
+    }
+}
+
+impl Project {
+    /// Copy of the project with directories and patterns replaced by the files they match
+    pub fn expanded(&self) -> Result<Project, String> {
+        let mut files = Vec::new();
+        for file in &self.files {
+            files.extend(file.expand()?);
+        }
+        let mut project = self.clone();
+        project.files = files;
+        Ok(project)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(source: PathBuf, destination: &str, include: &[&str], exclude: &[&str]) -> ProjectFile {
+        ProjectFile {
+            source,
+            destination: destination.to_string(),
+            permissions: Some("644".to_string()),
+            include: include.iter().map(|p| p.to_string()).collect(),
+            exclude: exclude.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// Data files of an application
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "data/app.desktop",
+            "data/hicolor/icons/app.png",
+            "data/hicolor/icons/app.svg",
+            "data/hicolor/icons/legacy/old.png",
+            "data/symbolic/icons/app-symbolic.png",
+            "data/ui/window.ui",
+            "data/ui/dialogs/about.ui",
+            "data/ui/window.ui~",
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    /// Sources relative to `dir` and destinations of the expanded files
+    fn expanded(dir: &Path, entry: &ProjectFile) -> Vec<(String, String)> {
+        entry.expand().unwrap().into_iter()
+            .map(|f| {
+                assert_eq!(f.permissions, entry.permissions);
+                (f.source.strip_prefix(dir).unwrap().to_string_lossy().to_string(), f.destination)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_file_is_kept_as_is() {
+        let dir = tree();
+        let file = entry(dir.path().join("data/app.desktop"), "share/applications/app.desktop", &[], &[]);
+        assert!(!file.is_pattern());
+        assert_eq!(file.expand().unwrap(), [file]);
+    }
+
+    #[test]
+    fn directory_gives_every_file_sorted() {
+        let dir = tree();
+        let ui = entry(dir.path().join("data/ui"), "share/app/ui", &[], &[]);
+        assert!(ui.is_pattern());
+        assert_eq!(expanded(dir.path(), &ui), [
+            ("data/ui/dialogs/about.ui".to_string(), "share/app/ui/dialogs/about.ui".to_string()),
+            ("data/ui/window.ui".to_string(), "share/app/ui/window.ui".to_string()),
+            ("data/ui/window.ui~".to_string(), "share/app/ui/window.ui~".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn trailing_slash_of_the_destination_is_ignored() {
+        let dir = tree();
+        let ui = entry(dir.path().join("data/ui/dialogs"), "share/app/ui/", &[], &[]);
+        assert_eq!(expanded(dir.path(), &ui), [
+            ("data/ui/dialogs/about.ui".to_string(), "share/app/ui/about.ui".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn pattern_in_a_middle_component_splits_the_base() {
+        let dir = tree();
+        let icons = entry(dir.path().join("data/*/icons/*.png"), "share/icons", &[], &[]);
+        assert_eq!(icons.base_and_pattern().unwrap().0, dir.path().join("data"));
+
+        // `*` doesn't match '/', so icons in subdirectories are left out
+        assert_eq!(expanded(dir.path(), &icons), [
+            ("data/hicolor/icons/app.png".to_string(), "share/icons/hicolor/icons/app.png".to_string()),
+            ("data/symbolic/icons/app-symbolic.png".to_string(), "share/icons/symbolic/icons/app-symbolic.png".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_directories() {
+        let dir = tree();
+        let icons = entry(dir.path().join("data/**/*.png"), "share/icons", &[], &[]);
+        let sources: Vec<String> = expanded(dir.path(), &icons).into_iter().map(|(source, _)| source).collect();
+        assert_eq!(sources, [
+            "data/hicolor/icons/app.png",
+            "data/hicolor/icons/legacy/old.png",
+            "data/symbolic/icons/app-symbolic.png",
+        ]);
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let dir = tree();
+        let ui = entry(dir.path().join("data"), "share/app", &["ui/**", "*.desktop"], &["**/*~", "ui/dialogs/**"]);
+        let sources: Vec<String> = expanded(dir.path(), &ui).into_iter().map(|(source, _)| source).collect();
+        assert_eq!(sources, ["data/app.desktop", "data/ui/window.ui"]);
+    }
+
+    #[test]
+    fn missing_base_and_bad_patterns_are_errors() {
+        let dir = tree();
+        let missing = entry(dir.path().join("assets/*.png"), "share/app", &[], &[]);
+        assert!(missing.expand().unwrap_err().starts_with("Directory not found"));
+
+        let invalid = entry(dir.path().join("data"), "share/app", &["[ui"], &[]);
+        assert!(invalid.expand().unwrap_err().starts_with("Invalid pattern '[ui'"));
+    }
+}
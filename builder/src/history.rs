@@ -0,0 +1,323 @@
+use std::mem;
+use std::path::PathBuf;
+
+use crate::project::{BuildProfile, Project, ProjectFile, ProjectVariable};
+
+/// Text fields of the project edited with an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Name,
+    ApplicationName,
+    AppId,
+    Version,
+    Author,
+    Description,
+    PackageName,
+    MinGtkVersion,
+    MinLibadwaitaVersion,
+}
+
+impl TextField {
+    fn get_mut(self, project: &mut Project) -> &mut String {
+        match self {
+            TextField::Name => &mut project.metadata.name,
+            TextField::ApplicationName => &mut project.metadata.application_name,
+            TextField::AppId => &mut project.metadata.app_id,
+            TextField::Version => &mut project.metadata.version,
+            TextField::Author => &mut project.metadata.author,
+            TextField::Description => &mut project.metadata.description,
+            TextField::PackageName => &mut project.package_name,
+            TextField::MinGtkVersion => &mut project.requirements.min_gtk_version,
+            TextField::MinLibadwaitaVersion => &mut project.requirements.min_libadwaita_version,
+        }
+    }
+}
+
+/// List fields of the project edited as comma separated text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListField {
+    Architectures,
+    Libraries,
+    Requires,
+    Conflicts,
+    Provides,
+}
+
+impl ListField {
+    fn get_mut(self, project: &mut Project) -> &mut Vec<String> {
+        match self {
+            ListField::Architectures => &mut project.requirements.architectures,
+            ListField::Libraries => &mut project.requirements.libraries,
+            ListField::Requires => &mut project.relations.requires,
+            ListField::Conflicts => &mut project.relations.conflicts,
+            ListField::Provides => &mut project.relations.provides,
+        }
+    }
+}
+
+/// An edit of the project. Applying a command returns the command that
+/// undoes it: the `Set` commands give back the value they replaced, and
+/// inserting and removing undo each other.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    SetText(TextField, String),
+    SetList(ListField, Vec<String>),
+    SetVariables(Vec<ProjectVariable>),
+    SetOutputDirectory(PathBuf),
+    /// Script of a hook, by name (pre_install, post_install, ...)
+    SetHook(String, Option<PathBuf>),
+    /// Whether the installer screen with the ID is shown
+    SetScreenEnabled(String, bool),
+    InsertFile(usize, ProjectFile),
+    RemoveFile(usize),
+    SetFile(usize, ProjectFile),
+    InsertProfile(usize, BuildProfile),
+    RemoveProfile(usize),
+    SetProfile(usize, BuildProfile),
+}
+
+impl Command {
+    /// Apply the command and return its inverse; None when it refers to a
+    /// file, profile, hook or screen the project doesn't have
+    fn apply(self, project: &mut Project) -> Option<Command> {
+        let inverse = match self {
+            Command::SetText(field, mut text) => {
+                mem::swap(field.get_mut(project), &mut text);
+                Command::SetText(field, text)
+            }
+            Command::SetList(field, mut list) => {
+                mem::swap(field.get_mut(project), &mut list);
+                Command::SetList(field, list)
+            }
+            Command::SetVariables(mut variables) => {
+                mem::swap(&mut project.variables, &mut variables);
+                Command::SetVariables(variables)
+            }
+            Command::SetOutputDirectory(mut path) => {
+                mem::swap(&mut project.metadata.output_directory, &mut path);
+                Command::SetOutputDirectory(path)
+            }
+            Command::SetHook(name, mut script) => {
+                mem::swap(project.hooks.get_mut(&name)?, &mut script);
+                Command::SetHook(name, script)
+            }
+            Command::SetScreenEnabled(id, mut enabled) => {
+                let screen = project.installer_screens.iter_mut().find(|s| s.id == id)?;
+                mem::swap(&mut screen.enabled, &mut enabled);
+                Command::SetScreenEnabled(id, enabled)
+            }
+            Command::InsertFile(index, file) => {
+                if index > project.files.len() {
+                    return None;
+                }
+                project.files.insert(index, file);
+                Command::RemoveFile(index)
+            }
+            Command::RemoveFile(index) => {
+                if index >= project.files.len() {
+                    return None;
+                }
+                Command::InsertFile(index, project.files.remove(index))
+            }
+            Command::SetFile(index, mut file) => {
+                mem::swap(project.files.get_mut(index)?, &mut file);
+                Command::SetFile(index, file)
+            }
+            Command::InsertProfile(index, profile) => {
+                if index > project.profiles.len() {
+                    return None;
+                }
+                project.profiles.insert(index, profile);
+                Command::RemoveProfile(index)
+            }
+            Command::RemoveProfile(index) => {
+                if index >= project.profiles.len() {
+                    return None;
+                }
+                Command::InsertProfile(index, project.profiles.remove(index))
+            }
+            Command::SetProfile(index, mut profile) => {
+                mem::swap(project.profiles.get_mut(index)?, &mut profile);
+                Command::SetProfile(index, profile)
+            }
+        };
+        Some(inverse)
+    }
+
+    /// Whether the command goes on with the edit `previous` undoes, like
+    /// typing in the same entry, so that one undo reverts both
+    fn continues(&self, previous: &Command) -> bool {
+        match (self, previous) {
+            (Command::SetText(field, _), Command::SetText(previous, _)) => field == previous,
+            (Command::SetList(field, _), Command::SetList(previous, _)) => field == previous,
+            (Command::SetVariables(_), Command::SetVariables(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Edits of a project that can be undone and redone
+#[derive(Debug)]
+pub struct History {
+    /// Commands that undo the edits, the last edit's at the end
+    undo: Vec<Command>,
+    /// Commands that redo the undone edits, the last undone at the end
+    redo: Vec<Command>,
+    /// Length of `undo` when the project was last saved; None when no
+    /// undo or redo gets back to it
+    saved: Option<usize>,
+}
+
+impl History {
+    /// History of a project as it was opened or saved
+    pub fn new() -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), saved: Some(0) }
+    }
+
+    /// Apply an edit to the project. Returns false when it changed nothing.
+    pub fn execute(&mut self, project: &mut Project, command: Command) -> bool {
+        let continues = self.redo.is_empty()
+            && !self.is_saved()
+            && self.undo.last().is_some_and(|last| command.continues(last));
+        let Some(inverse) = command.clone().apply(project) else {
+            return false;
+        };
+        if inverse == command {
+            return false;
+        }
+
+        // An undone save can't be redone anymore
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+        self.redo.clear();
+        // Otherwise the command undoing the first part of the edit undoes it all
+        if !continues {
+            self.undo.push(inverse);
+        }
+        true
+    }
+
+    /// Undo the last edit. Returns false when there is none.
+    pub fn undo(&mut self, project: &mut Project) -> bool {
+        let Some(redo) = self.undo.pop().and_then(|command| command.apply(project)) else {
+            return false;
+        };
+        self.redo.push(redo);
+        true
+    }
+
+    /// Redo the last undone edit. Returns false when there is none.
+    pub fn redo(&mut self, project: &mut Project) -> bool {
+        let Some(undo) = self.redo.pop().and_then(|command| command.apply(project)) else {
+            return false;
+        };
+        self.undo.push(undo);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+    }
+
+    /// The project differs from its file by something else than the edits,
+    /// e.g., it was upgraded when opened
+    pub fn mark_unsaved(&mut self) {
+        self.saved = None;
+    }
+
+    /// Whether the project is as it was last saved
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo.len())
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str) -> ProjectFile {
+        ProjectFile {
+            source: PathBuf::from("/src").join(name),
+            destination: format!("bin/{}", name),
+            permissions: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn edits_are_undone_and_redone() {
+        let mut project = Project::new();
+        project.files = vec![file("a"), file("b")];
+        let original = project.clone();
+        let mut history = History::new();
+
+        assert!(history.execute(&mut project, Command::RemoveFile(0)));
+        assert!(history.execute(&mut project, Command::SetScreenEnabled("license".to_string(), false)));
+        assert!(history.execute(&mut project, Command::SetHook("post_install".to_string(), Some(PathBuf::from("/src/post.sh")))));
+        let edited = project.clone();
+        assert_eq!(project.files, [file("b")]);
+
+        while history.undo(&mut project) {}
+        assert_eq!(project, original);
+        assert!(history.is_saved());
+
+        while history.redo(&mut project) {}
+        assert_eq!(project, edited);
+        assert!(!history.is_saved());
+    }
+
+    #[test]
+    fn typing_is_undone_at_once() {
+        let mut project = Project::new();
+        let mut history = History::new();
+        for name in ["H", "He", "Hel", "Hello"] {
+            history.execute(&mut project, Command::SetText(TextField::Name, name.to_string()));
+        }
+        history.execute(&mut project, Command::SetText(TextField::Version, "2.0.0".to_string()));
+        // Setting the value the field already has is no edit
+        assert!(!history.execute(&mut project, Command::SetText(TextField::Version, "2.0.0".to_string())));
+
+        assert!(history.undo(&mut project));
+        assert_eq!(project.metadata.version, "1.0.0");
+        assert!(history.undo(&mut project));
+        assert_eq!(project.metadata.name, "MyProject");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn saving_ends_the_edit_and_tracks_modification() {
+        let mut project = Project::new();
+        let mut history = History::new();
+        history.execute(&mut project, Command::SetText(TextField::Name, "Hel".to_string()));
+        history.mark_saved();
+        history.execute(&mut project, Command::SetText(TextField::Name, "Hello".to_string()));
+        assert!(!history.is_saved());
+
+        // Undoing goes back to the saved name, not to the one before typing
+        history.undo(&mut project);
+        assert_eq!(project.metadata.name, "Hel");
+        assert!(history.is_saved());
+
+        // Once another edit replaces the undone ones, the saved state is gone
+        history.undo(&mut project);
+        history.execute(&mut project, Command::SetText(TextField::Author, "Me".to_string()));
+        history.undo(&mut project);
+        assert!(!history.is_saved());
+    }
+}
@@ -3,18 +3,42 @@ use gtk4::{glib, gio, Application, FileDialog, AlertDialog};
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::path::PathBuf;
-use std::fs::File;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use libadwaita as adw;
 use adw::prelude::PreferencesGroupExt;
 use adw::prelude::ActionRowExt;
+use adw::prelude::ExpanderRowExt;
+use adw::prelude::EntryRowExt;
+use adw::prelude::ComboRowExt;
+use adw::prelude::AdwDialogExt;
 
 mod project;
-use project::{Project, ProjectFile};
+mod build;
+mod expand;
+mod history;
+mod profiles;
+mod session;
+mod templates;
+mod validation;
+use history::{Command, History, ListField, TextField};
+use project::{BuildProfile, Project, ProjectFile, ProjectVariable};
+use session::Session;
+use validation::Severity;
 
 struct AppState {
     project: Project,
     current_path: Option<PathBuf>,
     is_modified: bool,
+    /// Edits of the project since it was opened, to undo and redo
+    history: History,
+    /// Build profile chosen on the Build page; None builds the project as configured
+    profile: Option<String>,
+    /// Whether there are changes not yet written to the recovery file
+    autosave_pending: bool,
+    /// Whether the recovery file holds changes of this run, rather than the
+    /// ones of a crash still to be offered
+    owns_recovery: bool,
 }
 
 impl AppState {
@@ -23,37 +47,147 @@ impl AppState {
             project: Project::new(),
             current_path: None,
             is_modified: false,
+            history: History::new(),
+            profile: None,
+            autosave_pending: false,
+            owns_recovery: false,
         }
     }
 }
 
 const APP_ID: &str = "com.obision.appinstall.Builder";
 
-/// Parse a .desktop file and extract Name and Comment
-fn parse_desktop_file(path: &PathBuf) -> Result<(String, String), String> {
-    let content = std::fs::read_to_string(path)
-        .map_err(|e| format!("Failed to read .desktop file: {}", e))?;
-    
-    let mut name = String::new();
-    let mut comment = String::new();
-    
-    for line in content.lines() {
-        let line = line.trim();
-        if line.starts_with("Name=") {
-            name = line.strip_prefix("Name=").unwrap_or("").to_string();
-        } else if line.starts_with("Comment=") {
-            comment = line.strip_prefix("Comment=").unwrap_or("").to_string();
+/// Seconds between writes of unsaved changes to the recovery file
+const AUTOSAVE_INTERVAL: u32 = 30;
+
+/// Change the session and save it; the builder works without one
+fn update_session(session: &RefCell<Session>, change: impl FnOnce(&mut Session)) {
+    let mut session = session.borrow_mut();
+    change(&mut session);
+    if let Err(e) = session.save() {
+        eprintln!("Could not save the session: {}", e);
+    }
+}
+
+/// Split a comma separated list, dropping empty items
+fn split_list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Variables from a comma separated list of name=value items
+fn parse_variables(text: &str) -> Vec<ProjectVariable> {
+    split_list(text).into_iter()
+        .map(|item| {
+            let (name, value) = item.split_once('=').unwrap_or((&item, ""));
+            ProjectVariable { name: name.trim().to_string(), value: value.trim().to_string() }
+        })
+        .collect()
+}
+
+/// Variables as a comma separated list of name=value items
+fn format_variables(variables: &[ProjectVariable]) -> String {
+    variables.iter()
+        .map(|v| format!("{}={}", v.name, v.value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Local date and time of a Unix timestamp
+fn format_time(time: u64) -> String {
+    glib::DateTime::from_unix_local(time as i64)
+        .and_then(|time| time.format("%x %H:%M"))
+        .map(|text| text.to_string())
+        .unwrap_or_default()
+}
+
+/// Whether a version entry is empty (still being filled in) or a valid version
+fn is_valid_version_entry(text: &str) -> bool {
+    let text = text.trim();
+    text.is_empty() || liblis::Version::parse(text).is_ok()
+}
+
+/// Whether an app ID entry is empty (still being filled in) or a reverse-DNS name
+fn is_valid_app_id_entry(text: &str) -> bool {
+    let text = text.trim();
+    text.is_empty() || liblis::install::validate_app_id(text).is_ok()
+}
+
+/// Validate and build a project, with a build profile or without one,
+/// without the user interface, printing the issues and the build log
+fn build_from_command_line(project_path: &str, profile: Option<&str>) -> glib::ExitCode {
+    let project = match Project::load_from_file(&PathBuf::from(project_path)) {
+        Ok(project) => project,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return glib::ExitCode::FAILURE;
         }
+    };
+
+    let issues = project.validate(profile);
+    for issue in &issues {
+        eprintln!("{}", issue);
     }
-    
-    if name.is_empty() {
-        return Err("No Name field found in .desktop file".to_string());
+    if validation::has_errors(&issues) {
+        eprintln!("The project has errors, not building it");
+        return glib::ExitCode::FAILURE;
     }
-    
-    Ok((name, comment))
+
+    let project = match project.resolved(profile) {
+        Ok(project) => project,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+    if let Some(profile) = profile {
+        println!("-> Building profile '{}'", profile);
+    }
+
+    // The result shows next to the project in the builder's recent projects
+    let record_build = |succeeded: bool| {
+        if let Ok(path) = std::path::absolute(project_path) {
+            update_session(&RefCell::new(Session::load()), |session| session.built(&path, succeeded));
+        }
+    };
+
+    let receiver = build::spawn_build(project, Arc::new(AtomicBool::new(false)));
+    for event in receiver {
+        match event {
+            build::BuildEvent::Log(msg) => println!("{}", msg),
+            build::BuildEvent::Progress { .. } => {}
+            build::BuildEvent::Finished(Ok(path)) => {
+                println!("Package created at {}", path.display());
+                record_build(true);
+                return glib::ExitCode::SUCCESS;
+            }
+            build::BuildEvent::Finished(Err(e)) => {
+                eprintln!("ERROR: {}", e);
+                record_build(false);
+                return glib::ExitCode::FAILURE;
+            }
+        }
+    }
+    glib::ExitCode::FAILURE
 }
 
 fn main() -> glib::ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [flag, project_path] if flag == "--build" => return build_from_command_line(project_path, None),
+        [flag, project_path, option, profile] if flag == "--build" && option == "--profile" => {
+            return build_from_command_line(project_path, Some(profile));
+        }
+        [flag, ..] if flag == "--build" => {
+            eprintln!("Usage: obision-appinstall-builder --build <project.lisproj> [--profile <name>]");
+            return glib::ExitCode::FAILURE;
+        }
+        _ => {}
+    }
+
     adw::init().expect("Failed to initialize libadwaita");
 
     let app = Application::builder()
@@ -70,11 +204,17 @@ fn main() -> glib::ExitCode {
 }
 
 fn setup_actions(app: &Application) {
-    let actions = ["new-project", "open-project", "save-project", "save-as-project", "about"];
+    let actions = ["new-project", "open-project", "save-project", "save-as-project", "undo", "redo", "about"];
     for action_name in actions {
         let action = gio::SimpleAction::new(action_name, None);
         app.add_action(&action);
     }
+    app.set_accels_for_action("app.undo", &["<Control>z"]);
+    app.set_accels_for_action("app.redo", &["<Control><Shift>z"]);
+
+    // Opens the project file given as parameter, e.g., a recent project
+    let open_project_file = gio::SimpleAction::new("open-project-file", Some(glib::VariantTy::STRING));
+    app.add_action(&open_project_file);
     
     // About handler can be global
     if let Some(action) = app.lookup_action("about") {
@@ -95,16 +235,17 @@ fn build_ui(app: &Application) {
     window.set_application(Some(app));
 
     let app_state = Rc::new(RefCell::new(AppState::new()));
-    setup_window_interaction(&window_builder, app_state);
+    let session = Rc::new(RefCell::new(Session::load()));
+    setup_window_interaction(&window_builder, app_state, session.clone());
 
 
     let root_stack: gtk4::Stack = window_builder.object("root_stack").expect("Could not get root_stack");
-    show_welcome_screen(&window, &root_stack);
+    show_welcome_screen(&window, &root_stack, &session);
     
     window.present();
 }
 
-fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppState>>) {
+fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppState>>, session: Rc<RefCell<Session>>) {
     let window = builder.object::<adw::ApplicationWindow>("window").expect("Could not get window");
     let root_stack: gtk4::Stack = builder.object("root_stack").expect("Could not get root_stack");
     let content_header: adw::HeaderBar = builder.object("content_header").expect("Could not get content_header");
@@ -113,23 +254,53 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     
     let project_name_entry: adw::EntryRow = builder.object("project_name_entry").expect("Could not get project_name_entry");
     let application_name_entry: adw::EntryRow = builder.object("application_name_entry").expect("Could not get application_name_entry");
+    let app_id_entry: adw::EntryRow = builder.object("app_id_entry").expect("Could not get app_id_entry");
     let project_version_entry: adw::EntryRow = builder.object("project_version_entry").expect("Could not get project_version_entry");
     let project_author_entry: adw::EntryRow = builder.object("project_author_entry").expect("Could not get project_author_entry");
     let project_description_entry: adw::EntryRow = builder.object("project_description_entry").expect("Could not get project_description_entry");
     let package_name_entry: adw::EntryRow = builder.object("package_name_entry").expect("Could not get package_name_entry");
+    let min_gtk_entry: adw::EntryRow = builder.object("min_gtk_entry").expect("Could not get min_gtk_entry");
+    let min_libadwaita_entry: adw::EntryRow = builder.object("min_libadwaita_entry").expect("Could not get min_libadwaita_entry");
+    let architectures_entry: adw::EntryRow = builder.object("architectures_entry").expect("Could not get architectures_entry");
+    let libraries_entry: adw::EntryRow = builder.object("libraries_entry").expect("Could not get libraries_entry");
+    let requires_entry: adw::EntryRow = builder.object("requires_entry").expect("Could not get requires_entry");
+    let conflicts_entry: adw::EntryRow = builder.object("conflicts_entry").expect("Could not get conflicts_entry");
+    let provides_entry: adw::EntryRow = builder.object("provides_entry").expect("Could not get provides_entry");
+    let variables_entry: adw::EntryRow = builder.object("variables_entry").expect("Could not get variables_entry");
+    let profiles_group: adw::PreferencesGroup = builder.object("profiles_group").expect("Could not get profiles_group");
+    let add_profile_row: adw::ActionRow = builder.object("add_profile_row").expect("Could not get add_profile_row");
     
     let files_group: adw::PreferencesGroup = builder.object("files_list_group").expect("Could not get files_list_group");
     let add_file_row: adw::ActionRow = builder.object("add_file_row").expect("Could not get add_file_row");
+    let add_folder_row: adw::ActionRow = builder.object("add_folder_row").expect("Could not get add_folder_row");
     
     let output_dir_row: adw::ActionRow = builder.object("output_dir_row").expect("Could not get output_dir_row");
     let output_dir_button: gtk4::Button = builder.object("output_dir_button").expect("output_dir_button");
     let build_package_button: gtk4::Button = builder.object("build_package_button").expect("build_package_button");
     let build_log_view: gtk4::TextView = builder.object("build_log_view").expect("build_log_view");
+    let build_progress_box: gtk4::Box = builder.object("build_progress_box").expect("build_progress_box");
+    let build_progress_bar: gtk4::ProgressBar = builder.object("build_progress_bar").expect("build_progress_bar");
+    let build_progress_label: gtk4::Label = builder.object("build_progress_label").expect("build_progress_label");
+    let build_cancel_button: gtk4::Button = builder.object("build_cancel_button").expect("build_cancel_button");
+    let build_profile_row: adw::ComboRow = builder.object("build_profile_row").expect("build_profile_row");
+    let issues_status: adw::StatusPage = builder.object("issues_status").expect("issues_status");
+    let issues_group: adw::PreferencesGroup = builder.object("issues_group").expect("issues_group");
+    
+    // Hook script rows: (hook name, row, choose button, clear button, subtitle when unset)
+    let hook_rows: Vec<(&'static str, adw::ActionRow, gtk4::Button, gtk4::Button, String)> =
+        ["pre_install", "post_install", "pre_uninstall", "post_uninstall"].into_iter().map(|name| {
+            let row: adw::ActionRow = builder.object(format!("hook_{}_row", name)).expect("hook row");
+            let choose: gtk4::Button = builder.object(format!("hook_{}_button", name)).expect("hook button");
+            let clear: gtk4::Button = builder.object(format!("hook_{}_clear", name)).expect("hook clear button");
+            let subtitle = row.subtitle().map(|s| s.to_string()).unwrap_or_default();
+            (name, row, choose, clear, subtitle)
+        }).collect();
     
     // Installer screen switches
     let screen_welcome: adw::SwitchRow = builder.object("screen_welcome").expect("Could not get screen_welcome");
     let screen_license: adw::SwitchRow = builder.object("screen_license").expect("Could not get screen_license");
     let screen_install_location: adw::SwitchRow = builder.object("screen_install_location").expect("Could not get screen_install_location");
+    let screen_directory: adw::SwitchRow = builder.object("screen_directory").expect("Could not get screen_directory");
     let screen_finish: adw::SwitchRow = builder.object("screen_finish").expect("Could not get screen_finish");
 
     // == Helpers ==
@@ -154,35 +325,45 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         }
     };
 
-    // Update Save Action State
+    // Update Save, Undo and Redo Action State
     let update_save_action = {
         let window = window.clone();
         let app_state = app_state.clone();
         move || {
             if let Some(app) = window.application() {
-                if let Some(action) = app.lookup_action("save-project") {
-                    if let Some(simple) = action.downcast_ref::<gio::SimpleAction>() {
-                        let state = app_state.borrow();
-                        simple.set_enabled(state.is_modified);
+                let state = app_state.borrow();
+                let enabled = [
+                    ("save-project", state.is_modified),
+                    ("undo", state.history.can_undo()),
+                    ("redo", state.history.can_redo()),
+                ];
+                for (name, enabled) in enabled {
+                    if let Some(action) = app.lookup_action(name) {
+                        if let Some(simple) = action.downcast_ref::<gio::SimpleAction>() {
+                            simple.set_enabled(enabled);
+                        }
                     }
                 }
             }
         }
     };
 
-    // Mark Modified
-    let mark_modified = {
+    // Edit the project through its history, so the edit can be undone
+    let edit = {
         let app_state = app_state.clone();
         let update_title = update_title.clone();
         let update_save_action = update_save_action.clone();
-        move || {
+        move |command: Command| {
             let mut state = app_state.borrow_mut();
-            if !state.is_modified {
-                state.is_modified = true;
-                drop(state);
-                update_title();
-                update_save_action();
+            let AppState { project, history, .. } = &mut *state;
+            if !history.execute(project, command) {
+                return;
             }
+            state.autosave_pending = true;
+            state.is_modified = !state.history.is_saved();
+            drop(state);
+            update_title();
+            update_save_action();
         }
     };
 
@@ -208,7 +389,7 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         let files_group = files_group.clone();
 
         let app_state = app_state.clone();
-        let mark_modified = mark_modified.clone();
+        let edit = edit.clone();
         let window = window.clone();
         let refresher_weak = Rc::downgrade(&refresher);
         let active_file_rows = active_file_rows.clone();
@@ -224,11 +405,28 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                  app_state.borrow().project.files.clone()
             };
             
+            // Refresh again once patterns change
+            let refresh = {
+                let refresher_weak = refresher_weak.clone();
+                move || {
+                    if let Some(refresher) = refresher_weak.upgrade() {
+                        if let Some(func) = refresher.borrow().as_ref() {
+                            func();
+                        }
+                    }
+                }
+            };
+
             for (idx, file) in files.iter().enumerate() {
-                let row = adw::ActionRow::builder()
-                    .title(file.source.to_string_lossy().as_ref())
-                    .subtitle(&file.destination)
-                    .build();
+                let row: gtk4::Widget = if file.is_pattern() {
+                    pattern_row(file, idx, &app_state, &edit, &refresh).upcast()
+                } else {
+                    adw::ActionRow::builder()
+                        .title(file.source.to_string_lossy().as_ref())
+                        .subtitle(&file.destination)
+                        .build()
+                        .upcast()
+                };
                 
                  let delete_btn = gtk4::Button::builder()
                     .icon_name("user-trash-symbolic")
@@ -236,8 +434,7 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                     .css_classes(["flat"])
                     .build();
                     
-                 let app_state = app_state.clone();
-                 let mark_modified = mark_modified.clone();
+                 let edit = edit.clone();
                  let window = window.clone();
                  let refresher_weak = refresher_weak.clone();
 
@@ -251,15 +448,13 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                         .cancel_button(0)
                         .build();
 
-                    let app_state = app_state.clone();
-                    let mark_modified = mark_modified.clone();
+                    let edit = edit.clone();
                     let refresher_weak = refresher_weak.clone();
                     
                      alert.choose(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
                         if let Ok(res) = result {
                             if res == 1 { 
-                                app_state.borrow_mut().project.files.remove(idx);
-                                mark_modified();
+                                edit(Command::RemoveFile(idx));
                                 if let Some(refresher) = refresher_weak.upgrade() {
                                     if let Some(func) = refresher.borrow().as_ref() {
                                         func();
@@ -270,33 +465,199 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                      });
                  });
                  
-                 row.add_suffix(&delete_btn);
+                 match row.downcast_ref::<adw::ExpanderRow>() {
+                     Some(expander) => expander.add_suffix(&delete_btn),
+                     None => row.downcast_ref::<adw::ActionRow>().expect("file row").add_suffix(&delete_btn),
+                 }
                  files_group.add(&row);
-                 tracked.push(row.upcast());
+                 tracked.push(row);
             }
         }
     };
     
     *refresher.borrow_mut() = Some(Rc::new(perform_refresh));
 
+    // Build profiles, edited on the Configuration page and chosen on the Build page
+    let profiles_refresher: Refresher = Rc::new(RefCell::new(None));
+    let refresh_profiles = {
+        let profiles_refresher = profiles_refresher.clone();
+        move || {
+            if let Some(func) = profiles_refresher.borrow().as_ref() {
+                func();
+            }
+        }
+    };
+
+    let active_profile_rows: Rc<RefCell<Vec<adw::ExpanderRow>>> = Rc::new(RefCell::new(Vec::new()));
+    let perform_profiles_refresh = {
+        let profiles_group = profiles_group.clone();
+        let build_profile_row = build_profile_row.clone();
+        let app_state = app_state.clone();
+        let edit = edit.clone();
+        let window = window.clone();
+        let refresher_weak = Rc::downgrade(&profiles_refresher);
+
+        move || {
+            let mut tracked = active_profile_rows.borrow_mut();
+            for row in tracked.drain(..) {
+                profiles_group.remove(&row);
+            }
+
+            let (profiles, selected) = {
+                let state = app_state.borrow();
+                (state.project.profiles.clone(), state.profile.clone())
+            };
+
+            let refresh = {
+                let refresher_weak = refresher_weak.clone();
+                move || {
+                    if let Some(refresher) = refresher_weak.upgrade() {
+                        if let Some(func) = refresher.borrow().as_ref() {
+                            func();
+                        }
+                    }
+                }
+            };
+
+            for (idx, profile) in profiles.iter().enumerate() {
+                let row = profile_row(profile, idx, &app_state, &edit, &refresh);
+
+                let delete_btn = gtk4::Button::builder()
+                    .icon_name("user-trash-symbolic")
+                    .valign(gtk4::Align::Center)
+                    .css_classes(["flat"])
+                    .build();
+
+                let edit = edit.clone();
+                let window = window.clone();
+                let refresh = refresh.clone();
+                delete_btn.connect_clicked(move |_| {
+                    let alert = AlertDialog::builder()
+                        .modal(true)
+                        .message("Remove Profile")
+                        .detail("Are you sure you want to remove this build profile?")
+                        .buttons(["Cancel", "Remove"])
+                        .default_button(0)
+                        .cancel_button(0)
+                        .build();
+
+                    let edit = edit.clone();
+                    let refresh = refresh.clone();
+                    alert.choose(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
+                        if let Ok(1) = result {
+                            edit(Command::RemoveProfile(idx));
+                            refresh();
+                        }
+                    });
+                });
+
+                row.add_suffix(&delete_btn);
+                profiles_group.add(&row);
+                tracked.push(row);
+            }
+
+            // "None" first, then the profiles; a removed profile isn't chosen anymore
+            let names: Vec<&str> = std::iter::once("None")
+                .chain(profiles.iter().map(|p| p.name.as_str()))
+                .collect();
+            let index = selected
+                .and_then(|name| profiles.iter().position(|p| p.name == name))
+                .map_or(0, |idx| idx + 1);
+            build_profile_row.set_model(Some(&gtk4::StringList::new(&names)));
+            build_profile_row.set_selected(index as u32);
+        }
+    };
+
+    *profiles_refresher.borrow_mut() = Some(Rc::new(perform_profiles_refresh));
+
+    // The profile to build is a choice of the session, not saved with the project
+    build_profile_row.connect_selected_notify({
+        let app_state = app_state.clone();
+        move |row| {
+            let mut state = app_state.borrow_mut();
+            let profile = (row.selected() as usize).checked_sub(1)
+                .and_then(|idx| state.project.profiles.get(idx))
+                .map(|p| p.name.clone());
+            state.profile = profile;
+        }
+    });
+
+    // Validate the project and list the issues on the Issues page
+    let issue_rows: Rc<RefCell<Vec<adw::ActionRow>>> = Rc::new(RefCell::new(Vec::new()));
+    let refresh_issues = {
+        let app_state = app_state.clone();
+        let issues_status = issues_status.clone();
+        let issues_group = issues_group.clone();
+        move || {
+            let issues = {
+                let state = app_state.borrow();
+                state.project.validate(state.profile.as_deref())
+            };
+
+            let mut rows = issue_rows.borrow_mut();
+            for row in rows.drain(..) {
+                issues_group.remove(&row);
+            }
+            for issue in &issues {
+                let (icon, class, title) = match issue.severity {
+                    Severity::Error => ("dialog-error-symbolic", "error", "Error"),
+                    Severity::Warning => ("dialog-warning-symbolic", "warning", "Warning"),
+                };
+                let image = gtk4::Image::from_icon_name(icon);
+                image.add_css_class(class);
+                let row = adw::ActionRow::builder()
+                    .title(title)
+                    .subtitle(&issue.message)
+                    .subtitle_selectable(true)
+                    .build();
+                row.add_prefix(&image);
+                issues_group.add(&row);
+                rows.push(row);
+            }
+
+            issues_status.set_visible(issues.is_empty());
+            issues_group.set_visible(!issues.is_empty());
+            issues
+        }
+    };
+
     // Update UI from State
     let update_ui = {
         let app_state = app_state.clone();
         let call_refresh = call_refresh.clone();
         let name_entry = project_name_entry.clone();
         let app_name_entry = application_name_entry.clone(); // New
+        let app_id_entry = app_id_entry.clone();
         let ver_entry = project_version_entry.clone();
         let auth_entry = project_author_entry.clone();
         let desc_entry = project_description_entry.clone(); // New
         let pkg_entry = package_name_entry.clone();
         let output_row = output_dir_row.clone();
+        let hook_rows = hook_rows.clone();
+        let min_gtk_entry = min_gtk_entry.clone();
+        let min_libadwaita_entry = min_libadwaita_entry.clone();
+        let architectures_entry = architectures_entry.clone();
+        let libraries_entry = libraries_entry.clone();
+        let requires_entry = requires_entry.clone();
+        let conflicts_entry = conflicts_entry.clone();
+        let provides_entry = provides_entry.clone();
+        let variables_entry = variables_entry.clone();
+        let screen_switches = [
+            ("welcome", screen_welcome.clone()),
+            ("license", screen_license.clone()),
+            ("install_location", screen_install_location.clone()),
+            ("directory", screen_directory.clone()),
+            ("finish", screen_finish.clone()),
+        ];
+        let refresh_profiles = refresh_profiles.clone();
         
         move || {
-            let (name, app_name, ver, auth, desc, pkg, out_dir) = {
+            let (name, app_name, app_id, ver, auth, desc, pkg, out_dir) = {
                 let state = app_state.borrow();
                 (
                     state.project.metadata.name.clone(),
                     state.project.metadata.application_name.clone(),
+                    state.project.metadata.app_id.clone(),
                     state.project.metadata.version.clone(),
                     state.project.metadata.author.clone(),
                     state.project.metadata.description.clone(),
@@ -307,39 +668,118 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
             
             name_entry.set_text(&name);
             app_name_entry.set_text(&app_name);
+            app_id_entry.set_text(&app_id);
             ver_entry.set_text(&ver);
             auth_entry.set_text(&auth);
             desc_entry.set_text(&desc);
             pkg_entry.set_text(&pkg);
             output_row.set_subtitle(&out_dir.to_string_lossy());
             
+            let requirements = app_state.borrow().project.requirements.clone();
+            min_gtk_entry.set_text(&requirements.min_gtk_version);
+            min_libadwaita_entry.set_text(&requirements.min_libadwaita_version);
+            architectures_entry.set_text(&requirements.architectures.join(", "));
+            libraries_entry.set_text(&requirements.libraries.join(", "));
+            
+            let relations = app_state.borrow().project.relations.clone();
+            requires_entry.set_text(&relations.requires.join(", "));
+            conflicts_entry.set_text(&relations.conflicts.join(", "));
+            provides_entry.set_text(&relations.provides.join(", "));
+            
+            variables_entry.set_text(&format_variables(&app_state.borrow().project.variables));
+            
+            for (name, row, _, clear, subtitle) in &hook_rows {
+                let script = app_state.borrow_mut().project.hooks.get_mut(name).and_then(|p| p.clone());
+                match &script {
+                    Some(path) => row.set_subtitle(&path.to_string_lossy()),
+                    None => row.set_subtitle(subtitle),
+                }
+                clear.set_sensitive(script.is_some());
+            }
+            
+            let screens = app_state.borrow().project.installer_screens.clone();
+            for screen in screens {
+                if let Some((_, switch)) = screen_switches.iter().find(|(id, _)| *id == screen.id) {
+                    switch.set_active(screen.enabled);
+                }
+            }
+            
             call_refresh();
+            refresh_profiles();
         }
     };
     
+    // Connect "Add Profile"
+    add_profile_row.connect_activated({
+        let app_state = app_state.clone();
+        let edit = edit.clone();
+        let refresh_profiles = refresh_profiles.clone();
+        move |_| {
+            let state = app_state.borrow();
+            let name = (1..).map(|n| format!("profile-{}", n))
+                .find(|name| state.project.profile(name).is_none())
+                .unwrap_or_default();
+            let idx = state.project.profiles.len();
+            drop(state);
+            edit(Command::InsertProfile(idx, BuildProfile { name, ..BuildProfile::default() }));
+            refresh_profiles();
+        }
+    });
+
     // Connect "Add File"
     add_file_row.connect_activated({
         let window = window.clone();
         let app_state = app_state.clone();
-        let mark_modified = mark_modified.clone();
+        let edit = edit.clone();
         let call_refresh = call_refresh.clone();
         move |_| {
             let file_dialog = FileDialog::builder().title("Add File").modal(true).build();
             let app_state = app_state.clone();
-            let mark_modified = mark_modified.clone();
+            let edit = edit.clone();
             let call_refresh = call_refresh.clone();
             
             file_dialog.open(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
                 if let Ok(file) = result {
                     if let Some(path) = file.path() {
-                        let mut state = app_state.borrow_mut();
-                        state.project.files.push(ProjectFile {
+                        let idx = app_state.borrow().project.files.len();
+                        edit(Command::InsertFile(idx, ProjectFile {
                             source: path.clone(),
                             destination: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
                             permissions: None,
-                        });
-                        drop(state);
-                        mark_modified();
+                            include: Vec::new(),
+                            exclude: Vec::new(),
+                        }));
+                        call_refresh();
+                    }
+                }
+            });
+        }
+    });
+
+    // Connect "Add Folder"
+    add_folder_row.connect_activated({
+        let window = window.clone();
+        let app_state = app_state.clone();
+        let edit = edit.clone();
+        let call_refresh = call_refresh.clone();
+        move |_| {
+            let file_dialog = FileDialog::builder().title("Add Folder").modal(true).build();
+            let app_state = app_state.clone();
+            let edit = edit.clone();
+            let call_refresh = call_refresh.clone();
+            
+            file_dialog.select_folder(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
+                if let Ok(folder) = result {
+                    if let Some(path) = folder.path() {
+                        let idx = app_state.borrow().project.files.len();
+                        // The folder's files go to a directory of the same name
+                        edit(Command::InsertFile(idx, ProjectFile {
+                            source: path.clone(),
+                            destination: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                            permissions: None,
+                            include: Vec::new(),
+                            exclude: Vec::new(),
+                        }));
                         call_refresh();
                     }
                 }
@@ -348,33 +788,54 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     });
 
     // Connect Entry changes
+    #[derive(Clone, Copy)]
+    enum EntryField {
+        Text(TextField),
+        List(ListField),
+        Variables,
+    }
     let entries = [
-        (&project_name_entry, 0), 
-        (&project_version_entry, 1), 
-        (&project_author_entry, 2), 
-        (&package_name_entry, 3),
-        (&application_name_entry, 4), // New
-        (&project_description_entry, 5) // New
+        (&project_name_entry, EntryField::Text(TextField::Name)),
+        (&project_version_entry, EntryField::Text(TextField::Version)),
+        (&project_author_entry, EntryField::Text(TextField::Author)),
+        (&package_name_entry, EntryField::Text(TextField::PackageName)),
+        (&application_name_entry, EntryField::Text(TextField::ApplicationName)),
+        (&project_description_entry, EntryField::Text(TextField::Description)),
+        (&min_gtk_entry, EntryField::Text(TextField::MinGtkVersion)),
+        (&min_libadwaita_entry, EntryField::Text(TextField::MinLibadwaitaVersion)),
+        (&architectures_entry, EntryField::List(ListField::Architectures)),
+        (&libraries_entry, EntryField::List(ListField::Libraries)),
+        (&requires_entry, EntryField::List(ListField::Requires)),
+        (&conflicts_entry, EntryField::List(ListField::Conflicts)),
+        (&provides_entry, EntryField::List(ListField::Provides)),
+        (&app_id_entry, EntryField::Text(TextField::AppId)),
+        (&variables_entry, EntryField::Variables),
     ];
     
-    for (entry, id) in entries {
-        let app_state = app_state.clone();
-        let mark_modified = mark_modified.clone();
+    for (entry, field) in entries {
+        let edit = edit.clone();
         entry.connect_notify_local(Some("text"), move |entry, _| {
             let text = entry.text().to_string();
-            let mut state = app_state.borrow_mut();
-            let changed = match id {
-                0 => if state.project.metadata.name != text { state.project.metadata.name = text; true } else { false },
-                1 => if state.project.metadata.version != text { state.project.metadata.version = text; true } else { false },
-                2 => if state.project.metadata.author != text { state.project.metadata.author = text; true } else { false },
-                3 => if state.project.package_name != text { state.project.package_name = text; true } else { false },
-                4 => if state.project.metadata.application_name != text { state.project.metadata.application_name = text; true } else { false },
-                5 => if state.project.metadata.description != text { state.project.metadata.description = text; true } else { false },
-                _ => false,
+            // Setting the value a field already has, e.g., when the project is
+            // loaded, is no edit
+            edit(match field {
+                EntryField::Text(TextField::AppId) => Command::SetText(TextField::AppId, text.trim().to_string()),
+                EntryField::Text(field) => Command::SetText(field, text),
+                EntryField::List(field) => Command::SetList(field, split_list(&text)),
+                EntryField::Variables => Command::SetVariables(parse_variables(&text)),
+            });
+            
+            // Flag a version the installer couldn't compare with other versions,
+            // and an app ID that can't name the desktop file and manifest
+            let valid = match field {
+                EntryField::Text(TextField::Version) => is_valid_version_entry(&entry.text()),
+                EntryField::Text(TextField::AppId) => is_valid_app_id_entry(&entry.text()),
+                _ => true,
             };
-            drop(state);
-            if changed {
-                mark_modified();
+            if valid {
+                entry.remove_css_class("error");
+            } else {
+                entry.add_css_class("error");
             }
         });
     }
@@ -382,8 +843,7 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     // Connect Output Dir Button
     output_dir_button.connect_clicked({
         let window = window.clone();
-        let app_state = app_state.clone();
-        let mark_modified = mark_modified.clone();
+        let edit = edit.clone();
         let update_ui = update_ui.clone();
         move |_| {
             let file_dialog = FileDialog::builder()
@@ -391,17 +851,13 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                 .modal(true)
                 .build();
                 
-            let app_state = app_state.clone();
-            let mark_modified = mark_modified.clone();
+            let edit = edit.clone();
             let update_ui = update_ui.clone();
             
             file_dialog.select_folder(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
                  if let Ok(file) = result {
                      if let Some(path) = file.path() {
-                         let mut state = app_state.borrow_mut();
-                         state.project.metadata.output_directory = path;
-                         drop(state);
-                         mark_modified();
+                         edit(Command::SetOutputDirectory(path));
                          update_ui();
                      }
                  }
@@ -409,6 +865,44 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         }
     });
 
+    // Connect Hook Script Buttons
+    for (name, _, choose, clear, _) in &hook_rows {
+        choose.connect_clicked({
+            let window = window.clone();
+            let edit = edit.clone();
+            let update_ui = update_ui.clone();
+            let name = *name;
+            move |_| {
+                let file_dialog = FileDialog::builder()
+                    .title("Select Hook Script")
+                    .modal(true)
+                    .build();
+                    
+                let edit = edit.clone();
+                let update_ui = update_ui.clone();
+                
+                file_dialog.open(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
+                     if let Ok(file) = result {
+                         if let Some(path) = file.path() {
+                             edit(Command::SetHook(name.to_string(), Some(path)));
+                             update_ui();
+                         }
+                     }
+                });
+            }
+        });
+        
+        clear.connect_clicked({
+            let edit = edit.clone();
+            let update_ui = update_ui.clone();
+            let name = *name;
+            move |_| {
+                edit(Command::SetHook(name.to_string(), None));
+                update_ui();
+            }
+        });
+    }
+
     // Connect Build Package Button
 
 
@@ -417,6 +911,7 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     // SAVE Logic
     let perform_save = {
         let app_state = app_state.clone();
+        let session = session.clone();
         let update_title = update_title.clone();
         let update_save_action = update_save_action.clone();
         move |path: PathBuf| {
@@ -425,9 +920,15 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                  eprintln!("Save error: {}", e);
             } else {
                  println!("Saved to {:?}", path);
-                 state.current_path = Some(path);
+                 state.current_path = Some(path.clone());
                  state.is_modified = false;
+                 state.history.mark_saved();
+                 state.autosave_pending = false;
                  drop(state);
+                 update_session(&session, |session| {
+                     session.opened(&path);
+                     session.discard_recovery();
+                 });
                  update_title();
                  update_save_action();
             }
@@ -475,6 +976,7 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     
     let check_unsaved = {
         let app_state = app_state.clone();
+        let session = session.clone();
         let window = window.clone();
         let request_save = request_save.clone();
         let update_save_action = update_save_action.clone();
@@ -494,6 +996,7 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                 let request_save = request_save.clone();
                 let on_proceed_clone = on_proceed.clone();
                 let app_state = app_state.clone();
+                let session = session.clone();
                 let update_save_action = update_save_action.clone();
 
                 alert.choose(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
@@ -502,7 +1005,9 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                              // Mark as clean to proceed
                              let mut state = app_state.borrow_mut();
                              state.is_modified = false;
+                             state.autosave_pending = false;
                              drop(state);
+                             update_session(&session, Session::discard_recovery);
                              update_save_action();
                              on_proceed();
                         } else if res == 2 { // Save
@@ -518,6 +1023,17 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         }
     };
     
+    // Cancel a running build
+    let build_cancel: Rc<RefCell<Option<Arc<AtomicBool>>>> = Rc::new(RefCell::new(None));
+    build_cancel_button.connect_clicked({
+        let build_cancel = build_cancel.clone();
+        move |_| {
+            if let Some(cancel) = build_cancel.borrow().as_ref() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+
     // Connect Build Package Button
     // Connect Build Package Button
     build_package_button.connect_clicked({
@@ -525,49 +1041,45 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         let window = window.clone();
         let check_unsaved = check_unsaved.clone();
         let build_log_view = build_log_view.clone();
+        let build_progress_box = build_progress_box.clone();
+        let build_progress_bar = build_progress_bar.clone();
+        let build_progress_label = build_progress_label.clone();
+        let build_cancel = build_cancel.clone();
+        let refresh_issues = refresh_issues.clone();
+        let sidebar_list = sidebar_list.clone();
+        let session = session.clone();
         
-        move |_| {
+        move |build_package_button| {
             let app_state = app_state.clone();
+            let session = session.clone();
             let window = window.clone();
             let build_log_view = build_log_view.clone();
+            let build_package_button = build_package_button.clone();
+            let build_progress_box = build_progress_box.clone();
+            let build_progress_bar = build_progress_bar.clone();
+            let build_progress_label = build_progress_label.clone();
+            let build_cancel = build_cancel.clone();
+            let refresh_issues = refresh_issues.clone();
+            let sidebar_list = sidebar_list.clone();
             
             // Build Logic Closure
             let run_build = move || {
-                // Validate required fields
-                let state = app_state.borrow();
-                let validation_errors = {
-                    let mut errors = Vec::new();
-                    
-                    if state.project.metadata.name.trim().is_empty() {
-                        errors.push("Project Name is required");
-                    }
-                    if state.project.metadata.application_name.trim().is_empty() {
-                        errors.push("Application Name is required");
-                    }
-                    if state.project.metadata.version.trim().is_empty() {
-                        errors.push("Version is required");
-                    }
-                    if state.project.metadata.author.trim().is_empty() {
-                        errors.push("Author is required");
-                    }
-                    if state.project.metadata.description.trim().is_empty() {
-                        errors.push("Description is required");
-                    }
-                    if state.project.package_name.trim().is_empty() {
-                        errors.push("Package Name is required");
-                    }
-                    
-                    errors
-                };
-                drop(state);
-                
-                if !validation_errors.is_empty() {
-                    let error_msg = validation_errors.join("\n• ");
+                // Errors stop the build and are listed on the Issues page
+                let issues = refresh_issues();
+                if validation::has_errors(&issues) {
+                    let errors: Vec<&str> = issues.iter()
+                        .filter(|i| i.severity == Severity::Error)
+                        .map(|i| i.message.as_str())
+                        .collect();
                     let alert = AlertDialog::builder()
-                        .message("Missing Required Fields")
-                        .detail(&format!("Please fill in all required fields:\n• {}", error_msg))
+                        .message("Missing or Invalid Fields")
+                        .detail(&format!("Please fix the following issues:\n• {}", errors.join("\n• ")))
                         .build();
                     alert.show(Some(&window));
+                    if let Some(issues_row) = sidebar_list.row_at_index(3) {
+                        issues_row.activate();
+                        sidebar_list.select_row(Some(&issues_row));
+                    }
                     return;
                 }
                 
@@ -578,237 +1090,97 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                 let log = move |msg: &str| {
                     let mut end = log_buffer_local.end_iter();
                     log_buffer_local.insert(&mut end, &format!("{}\n", msg));
-                    // Pump event loop to show progress
-                    while glib::MainContext::default().iteration(false) {}
                 };
 
-                let state = app_state.borrow();
-                let output_dir = state.project.metadata.output_directory.clone();
-                let package_name = state.project.package_name.clone();
-                let files = state.project.files.clone();
-                let metadata = state.project.metadata.clone();
-                let project = state.project.clone();
-                drop(state); 
+                // Warnings don't stop the build but go to the top of the log
+                for issue in &issues {
+                    log(&issue.to_string());
+                }
 
-                let output_path = output_dir.join(&package_name);
-                let check_path = output_path.clone(); 
+                // The profile exists, or validation would have reported it
+                let (project, profile, project_path) = {
+                    let state = app_state.borrow();
+                    (state.project.resolved(state.profile.as_deref()), state.profile.clone(), state.current_path.clone())
+                };
+                let Ok(project) = project else {
+                    return;
+                };
+                if let Some(profile) = &profile {
+                    log(&format!("-> Building profile '{}'", profile));
+                }
+                let check_path = build::output_path(&project);
 
                 let window_for_build = window.clone();
-                
-                let output_dir_for_build = output_dir.clone();
-                let output_path_for_build = output_path.clone();
                 let log_buffer = buffer.clone();
-                
-                // Actual Build Work
+                let build_package_button = build_package_button.clone();
+                let build_progress_box = build_progress_box.clone();
+                let build_progress_bar = build_progress_bar.clone();
+                let build_progress_label = build_progress_label.clone();
+                let build_cancel = build_cancel.clone();
+                let session = session.clone();
+
+                // Actual Build Work, on a worker thread
                 let perform_build = Rc::new(move || {
-                    let log = |msg: &str| {
-                        let mut end = log_buffer.end_iter();
-                        log_buffer.insert(&mut end, &format!("-> {}\n", msg));
-                        while glib::MainContext::default().iteration(false) {}
-                    };
-
-                    log("Starting build process...");
-
-                    // Ensure output directory exists
-                    if !output_dir_for_build.exists() {
-                         log(&format!("Creating output directory: {:?}", output_dir_for_build));
-                         if let Err(e) = std::fs::create_dir_all(&output_dir_for_build) {
-                             log(&format!("ERROR: Could not create output directory: {}", e));
-                             let alert = AlertDialog::builder().message("Build Failed").detail(&format!("Could not create output directory: {}", e)).build();
-                             alert.show(Some(&window_for_build));
-                             return;
-                         }
-                    }
-                    
-                    // Create unique temporary directory
-                    let temp_dir = std::env::temp_dir().join(format!("obision-build-{}", std::process::id()));
-                    log(&format!("Creating temporary build directory: {:?}", temp_dir));
-                    
-                    if let Err(e) = std::fs::create_dir_all(&temp_dir) {
-                        log(&format!("ERROR: Could not create temp directory: {}", e));
-                        let alert = AlertDialog::builder().message("Build Failed").detail(&format!("Could not create temp directory: {}", e)).build();
-                        alert.show(Some(&window_for_build));
-                        return;
-                    }
-                    
-                    // Create install/ and application/ directories
-                    let install_dir = temp_dir.join("install");
-                    let application_dir = temp_dir.join("application");
-                    
-                    if let Err(e) = std::fs::create_dir(&install_dir) {
-                        log(&format!("ERROR: Could not create install directory: {}", e));
-                        let _ = std::fs::remove_dir_all(&temp_dir);
-                        let alert = AlertDialog::builder().message("Build Failed").detail(&format!("Could not create install directory: {}", e)).build();
-                        alert.show(Some(&window_for_build));
-                        return;
-                    }
-                    
-                    if let Err(e) = std::fs::create_dir(&application_dir) {
-                        log(&format!("ERROR: Could not create application directory: {}", e));
-                        let _ = std::fs::remove_dir_all(&temp_dir);
-                        let alert = AlertDialog::builder().message("Build Failed").detail(&format!("Could not create application directory: {}", e)).build();
-                        alert.show(Some(&window_for_build));
-                        return;
-                    }
-                    
-                    // === POPULATE INSTALL FOLDER ===
-                    log("Populating install/ folder...");
-                    
-                    // Copy .desktop file to install/
-                    if let Some(ref desktop_file) = metadata.desktop_file {
-                        if desktop_file.exists() {
-                            if let Some(filename) = desktop_file.file_name() {
-                                let dest = install_dir.join(filename);
-                                log(&format!("Copying: {} -> install/{}", desktop_file.display(), filename.to_string_lossy()));
-                                if let Err(e) = std::fs::copy(desktop_file, &dest) {
-                                    log(&format!("ERROR copying desktop file: {}", e));
+                    let cancel = Arc::new(AtomicBool::new(false));
+                    *build_cancel.borrow_mut() = Some(cancel.clone());
+                    let receiver = build::spawn_build(project.clone(), cancel);
+
+                    build_package_button.set_sensitive(false);
+                    build_progress_bar.set_fraction(0.0);
+                    build_progress_label.set_text("");
+                    build_progress_box.set_visible(true);
+
+                    let window = window_for_build.clone();
+                    let log_buffer = log_buffer.clone();
+                    let build_package_button = build_package_button.clone();
+                    let build_progress_box = build_progress_box.clone();
+                    let build_progress_bar = build_progress_bar.clone();
+                    let build_progress_label = build_progress_label.clone();
+                    let build_cancel = build_cancel.clone();
+                    let session = session.clone();
+                    let project_path = project_path.clone();
+
+                    glib::timeout_add_local(std::time::Duration::from_millis(50), move || {
+                        loop {
+                            match receiver.try_recv() {
+                                Ok(build::BuildEvent::Log(msg)) => {
+                                    let mut end = log_buffer.end_iter();
+                                    log_buffer.insert(&mut end, &format!("{}\n", msg));
                                 }
-                            }
-                        } else {
-                            log("WARNING: Desktop file not found");
-                        }
-                    } else {
-                        log("WARNING: No desktop file specified");
-                    }
-                    
-                    // === POPULATE APPLICATION FOLDER ===
-                    log("Populating application/ folder...");
-                    
-                    for file_entry in &files {
-                        if file_entry.source.exists() {
-                            let dest = application_dir.join(&file_entry.destination);
-                            log(&format!("Copying: {} -> application/{}", file_entry.source.display(), file_entry.destination));
-                            
-                            // Create parent directories if needed
-                            if let Some(parent) = dest.parent() {
-                                if let Err(e) = std::fs::create_dir_all(parent) {
-                                    log(&format!("ERROR creating directory for {}: {}", file_entry.destination, e));
-                                    continue;
+                                Ok(build::BuildEvent::Progress { current_file, bytes_done, bytes_total }) => {
+                                    if bytes_total > 0 {
+                                        build_progress_bar.set_fraction((bytes_done as f64 / bytes_total as f64).min(1.0));
+                                    }
+                                    build_progress_label.set_text(&current_file);
                                 }
-                            }
-                            
-                            if let Err(e) = std::fs::copy(&file_entry.source, &dest) {
-                                log(&format!("ERROR copying file: {}", e));
-                            }
-                        } else {
-                            log(&format!("WARNING: File not found: {}", file_entry.source.display()));
-                        }
-                    }
-                    
-                    // === CREATE .LIS ARCHIVE FROM TEMP DIRECTORY ===
-                    log(&format!("Creating package file: {:?}", output_path_for_build));
-                    let f = match File::create(&output_path_for_build) {
-                        Ok(f) => f,
-                        Err(e) => {
-                            log(&format!("ERROR: Could not create package file: {}", e));
-                            let _ = std::fs::remove_dir_all(&temp_dir);
-                            let alert = AlertDialog::builder().message("Build Failed").detail(&format!("Could not create package file: {}", e)).build();
-                            alert.show(Some(&window_for_build));
-                            return;
-                        }
-                    };
-                    
-                    let enc = flate2::write::GzEncoder::new(f, flate2::Compression::default());
-                    let mut tar = tar::Builder::new(enc);
-                    
-                    log("Adding install/ directory to archive...");
-                    if let Err(e) = tar.append_dir_all("install", &install_dir) {
-                        log(&format!("ERROR adding install directory: {}", e));
-                    }
-                    
-                    log("Adding application/ directory to archive...");
-                    if let Err(e) = tar.append_dir_all("application", &application_dir) {
-                        log(&format!("ERROR adding application directory: {}", e));
-                    }
-                    
-                    // Generate comprehensive metadata.toml with all project information
-                     log("Generating metadata.toml...");
-                     
-                     // Convert project files to metadata format
-                     let metadata_files: Vec<liblis::metadata::FileEntry> = files.iter().map(|f| {
-                         liblis::metadata::FileEntry {
-                             source: f.destination.clone(), // In the .lis, it's in application/ folder
-                             destination: f.destination.clone(),
-                             permissions: f.permissions.clone(),
-                         }
-                     }).collect();
-                     
-                     // Convert installer screens to metadata format
-                     let metadata_screens: Vec<liblis::metadata::InstallerScreen> = project.installer_screens.iter().map(|s| {
-                         liblis::metadata::InstallerScreen {
-                             id: s.id.clone(),
-                             enabled: s.enabled,
-                             order: s.order,
-                             custom_content: s.custom_content.clone(),
-                         }
-                     }).collect();
-                     
-                     // Create complete metadata structure
-                     let full_metadata = liblis::Metadata {
-                         package: liblis::metadata::PackageInfo {
-                             name: metadata.name.clone(),
-                             version: metadata.version.clone(),
-                             app_id: format!("com.example.{}", metadata.name.to_lowercase().replace(" ", "-")),
-                             description: metadata.description.clone(),
-                             author: metadata.author.clone(),
-                             application_name: metadata.application_name.clone(),
-                             package_name: package_name.clone(),
-                             compression_level: project.compression_level,
-                         },
-                         installation: liblis::metadata::InstallationInfo {
-                             prefix_system: "/usr/local".to_string(),
-                             prefix_user: "~/.local".to_string(),
-                         },
-                         desktop: liblis::metadata::DesktopInfo {
-                             name: metadata.application_name.clone(),
-                             exec: metadata.name.to_lowercase().replace(" ", "-"),
-                             icon: String::new(),
-                             categories: vec!["Utility".to_string()],
-                         },
-                         dependencies: liblis::metadata::DependenciesInfo {
-                             bundled: vec![],
-                         },
-                         files: metadata_files,
-                         installer_screens: metadata_screens,
-                     };
-                     
-                     // Serialize to TOML
-                     let toml_content = match full_metadata.to_toml() {
-                         Ok(content) => content,
-                         Err(e) => {
-                             log(&format!("ERROR serializing metadata: {}", e));
-                             let _ = std::fs::remove_dir_all(&temp_dir);
-                             let alert = AlertDialog::builder().message("Build Failed").detail(&format!("Error creating metadata: {}", e)).build();
-                             alert.show(Some(&window_for_build));
-                             return;
-                         }
-                     };
-                     
-                     let mut header = tar::Header::new_gnu();
-                     header.set_size(toml_content.len() as u64);
-                     header.set_mode(0o644);
-                     header.set_cksum();
-                     
-                     if let Err(e) = tar.append_data(&mut header, "metadata.toml", toml_content.as_bytes()) {
-                          log(&format!("ERROR adding metadata: {}", e));
-                     }
+                                Ok(build::BuildEvent::Finished(result)) => {
+                                    *build_cancel.borrow_mut() = None;
+                                    build_progress_box.set_visible(false);
+                                    build_package_button.set_sensitive(true);
 
-                    log("Finalizing archive...");
-                    match tar.finish() {
-                        Ok(_) => {
-                            log("Cleaning up temporary directory...");
-                            let _ = std::fs::remove_dir_all(&temp_dir);
-                            log("Build Successful!");
-                            let alert = AlertDialog::builder().message("Build Successful").detail(&format!("Package created at {:?}", output_path_for_build)).build();
-                            alert.show(Some(&window_for_build));
-                        },
-                        Err(e) => {
-                            log(&format!("ERROR finalizing archive: {}", e));
-                            let _ = std::fs::remove_dir_all(&temp_dir);
-                            let alert = AlertDialog::builder().message("Build Failed").detail(&format!("Error finalizing archive: {}", e)).build();
-                            alert.show(Some(&window_for_build));
+                                    // Shown with the project among the recent projects
+                                    if let Some(path) = &project_path {
+                                        update_session(&session, |session| session.built(path, result.is_ok()));
+                                    }
+
+                                    let alert = match result {
+                                        Ok(path) => AlertDialog::builder()
+                                            .message("Build Successful")
+                                            .detail(format!("Package created at {:?}", path))
+                                            .build(),
+                                        Err(e) => AlertDialog::builder()
+                                            .message("Build Failed")
+                                            .detail(e)
+                                            .build(),
+                                    };
+                                    alert.show(Some(&window));
+                                    return glib::ControlFlow::Break;
+                                }
+                                Err(std::sync::mpsc::TryRecvError::Empty) => return glib::ControlFlow::Continue,
+                                Err(std::sync::mpsc::TryRecvError::Disconnected) => return glib::ControlFlow::Break,
+                            }
                         }
-                    }
+                    });
                 });
 
                 if check_path.exists() {
@@ -829,18 +1201,12 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                     alert.choose(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
                         if let Ok(res) = result {
                             if res == 1 { // Replace
-                                {
-                                    let mut end = log_buffer.end_iter();
-                                    log_buffer.insert(&mut end, "-> Overwrite confirmed.\n");
-                                    while glib::MainContext::default().iteration(false) {}
-                                }
+                                let mut end = log_buffer.end_iter();
+                                log_buffer.insert(&mut end, "-> Overwrite confirmed.\n");
                                 perform_build();
                             } else {
-                                {
-                                    let mut end = log_buffer.end_iter();
-                                    log_buffer.insert(&mut end, "-> Build cancelled by user.\n");
-                                    while glib::MainContext::default().iteration(false) {}
-                                }
+                                let mut end = log_buffer.end_iter();
+                                log_buffer.insert(&mut end, "-> Build cancelled by user.\n");
                             }
                         }
                     });
@@ -857,6 +1223,7 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     {
         let check_unsaved = check_unsaved.clone();
         let app_state = app_state.clone();
+        let session = session.clone();
         let window = window.clone();
         window.connect_close_request(move |win| {
              let state = app_state.borrow();
@@ -874,10 +1241,57 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                  }));
                  return glib::Propagation::Stop;
              }
+             // A clean close leaves nothing to recover
+             if state.owns_recovery {
+                 drop(state);
+                 update_session(&session, Session::discard_recovery);
+             }
              glib::Propagation::Proceed
         });
     }
 
+    // Load a project file, from the Open dialog or the recent projects
+    let open_project = {
+        let app_state = app_state.clone();
+        let session = session.clone();
+        let window = window.clone();
+        let update_ui = update_ui.clone();
+        let update_title = update_title.clone();
+        let update_save_action = update_save_action.clone();
+        let root_stack = root_stack.clone();
+        let content_stack = content_stack.clone();
+        move |path: PathBuf| {
+            match Project::load_from_file(&path) {
+                Ok(proj) => {
+                    let mut state = app_state.borrow_mut();
+                    // Saving again writes the current format, with relative paths
+                    state.is_modified = proj.upgraded;
+                    state.history = History::new();
+                    if proj.upgraded {
+                        state.history.mark_unsaved();
+                    }
+                    state.project = proj;
+                    state.current_path = Some(path.clone());
+                    state.profile = None;
+                    drop(state);
+                    update_session(&session, |session| session.opened(&path));
+                    update_ui();
+                    update_title();
+                    update_save_action();
+                    root_stack.set_visible_child_name("main_view"); // Switch view
+                    content_stack.set_visible_child_name("configuration");
+                }
+                Err(e) => {
+                    let alert = AlertDialog::builder()
+                        .message("Could Not Open Project")
+                        .detail(e.to_string())
+                        .build();
+                    alert.show(Some(&window));
+                }
+            }
+        }
+    };
+
     let app = window.application().unwrap();
 
     // NEW PROJECT
@@ -904,86 +1318,27 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
              let update_save_action = update_save_action.clone();
              
              check_unsaved(Rc::new(move || {
-                 let file_dialog = FileDialog::builder().title("Select .desktop File").modal(true).build();
-                 
-                 // Add filter for .desktop files
-                 let filter = gtk4::FileFilter::new();
-                 filter.add_pattern("*.desktop");
-                 filter.set_name(Some("Desktop Entry Files"));
-                 let filters = gtk4::gio::ListStore::new::<gtk4::FileFilter>();
-                 filters.append(&filter);
-                 file_dialog.set_filters(Some(&filters));
-                 
                  let app_state = app_state.clone();
                  let update_ui = update_ui.clone();
                  let update_title = update_title.clone();
                  let update_save_action = update_save_action.clone();
                  let content_stack = content_stack.clone();
                  let root_stack = root_stack.clone();
-                 let window_for_error = window.clone();
                  
-                 file_dialog.open(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
-                     if let Ok(file) = result {
-                         if let Some(desktop_path) = file.path() {
-                             // Parse .desktop file
-                             match parse_desktop_file(&desktop_path) {
-                                 Ok((app_name, description)) => {
-                                     // Get parent directory (should be 'data' folder)
-                                     if let Some(data_dir) = desktop_path.parent() {
-                                         if let Some(project_root) = data_dir.parent() {
-                                             let mut state = app_state.borrow_mut();
-                                             state.project = Project::new();
-                                             
-                                             // Set project root as output directory
-                                             state.project.metadata.output_directory = project_root.to_path_buf();
-                                             
-                                             // Extract project name from folder
-                                             if let Some(name) = project_root.file_name() {
-                                                 state.project.metadata.name = name.to_string_lossy().to_string();
-                                             }
-                                             
-                                             // Set application name and description from .desktop file
-                                             state.project.metadata.application_name = app_name;
-                                             state.project.metadata.description = description;
-                                             
-                                             // Store desktop file path
-                                             state.project.metadata.desktop_file = Some(desktop_path.clone());
-                                             
-                                             state.current_path = None;
-                                             state.project.package_name = format!("{}.lis", state.project.metadata.name.to_lowercase().replace(" ", "-"));
-                                             state.is_modified = false; 
-                                             drop(state);
-                                             
-                                             update_ui();
-                                             update_title();
-                                             update_save_action();
-                                             root_stack.set_visible_child_name("main_view");
-                                             content_stack.set_visible_child_name("configuration");
-                                         } else {
-                                             let alert = AlertDialog::builder()
-                                                 .message("Invalid File Location")
-                                                 .detail("The .desktop file must be in a 'data' folder within your project root.")
-                                                 .build();
-                                             alert.show(Some(&window_for_error));
-                                         }
-                                     } else {
-                                         let alert = AlertDialog::builder()
-                                             .message("Invalid File Location")
-                                             .detail("Could not determine project structure.")
-                                             .build();
-                                         alert.show(Some(&window_for_error));
-                                     }
-                                 },
-                                 Err(e) => {
-                                     let alert = AlertDialog::builder()
-                                         .message("Failed to Parse .desktop File")
-                                         .detail(&e)
-                                         .build();
-                                     alert.show(Some(&window_for_error));
-                                 }
-                             }
-                         }
-                     }
+                 show_new_project_dialog(&window, move |project| {
+                     let mut state = app_state.borrow_mut();
+                     state.project = project;
+                     state.current_path = None;
+                     state.profile = None;
+                     state.is_modified = false; 
+                     state.history = History::new();
+                     drop(state);
+                     
+                     update_ui();
+                     update_title();
+                     update_save_action();
+                     root_stack.set_visible_child_name("main_view");
+                     content_stack.set_visible_child_name("configuration");
                  });
              }));
         });
@@ -992,24 +1347,13 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     // OPEN PROJECT
     if let Some(action) = app.lookup_action("open-project") {
         let simple = action.downcast::<gio::SimpleAction>().unwrap();
-        let app_state = app_state.clone();
-        let update_ui = update_ui.clone();
-        let update_title = update_title.clone();
-        let update_save_action = update_save_action.clone();
-        let root_stack = root_stack.clone();
         let window_clone = window.clone(); // Clone outside
-        let content_stack = content_stack.clone(); // Clone outside
+        let check_unsaved = check_unsaved.clone();
+        let open_project = open_project.clone();
 
         simple.connect_activate(move |_, _| {
-             let app_state = app_state.clone();
-             let update_ui = update_ui.clone();
-             let update_title = update_title.clone();
-             let update_save_action = update_save_action.clone();
              let window = window_clone.clone(); // Use clone
-             let content_stack = content_stack.clone();
-             let root_stack = root_stack.clone();
-             
-             let update_save_action = update_save_action.clone();
+             let open_project = open_project.clone();
              
              check_unsaved(Rc::new(move || {
                 let file_dialog = FileDialog::builder().title("Open Project").modal(true).build();
@@ -1020,32 +1364,12 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
                 filters.append(&filter);
                 file_dialog.set_filters(Some(&filters));
                 
-                let app_state = app_state.clone();
-                let update_ui = update_ui.clone();
-                let content_stack = content_stack.clone();
-                let root_stack = root_stack.clone(); // Move to closure
-                let update_title = update_title.clone(); 
-                let update_save_action = update_save_action.clone();
+                let open_project = open_project.clone();
 
                 file_dialog.open(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
                     if let Ok(file) = result {
                         if let Some(path) = file.path() {
-                             match Project::load_from_file(&path) {
-                                 Ok(proj) => {
-                                     let mut state = app_state.borrow_mut();
-                                     state.project = proj;
-                                     state.current_path = Some(path);
-                                     state.is_modified = false;
-                                     drop(state);
-                                     update_ui();
-                                     update_ui();
-                                     update_title();
-                                     update_save_action();
-                                     root_stack.set_visible_child_name("main_view"); // Switch view
-                                     content_stack.set_visible_child_name("configuration");
-                                 },
-                                 Err(e) => eprintln!("Error: {}", e),
-                             }
+                             open_project(path);
                         }
                     }
                 });
@@ -1053,6 +1377,21 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         });
     }
 
+    // OPEN PROJECT FILE, e.g., a recent project
+    if let Some(action) = app.lookup_action("open-project-file") {
+        let simple = action.downcast::<gio::SimpleAction>().unwrap();
+        let check_unsaved = check_unsaved.clone();
+        let open_project = open_project.clone();
+
+        simple.connect_activate(move |_, parameter| {
+             let Some(path) = parameter.and_then(|p| p.get::<String>()) else {
+                 return;
+             };
+             let open_project = open_project.clone();
+             check_unsaved(Rc::new(move || open_project(PathBuf::from(&path))));
+        });
+    }
+
     // SAVE Logic
 
 
@@ -1119,6 +1458,32 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         });
     }
 
+    // UNDO / REDO
+    for (name, undo) in [("undo", true), ("redo", false)] {
+        if let Some(action) = app.lookup_action(name) {
+            let simple = action.downcast::<gio::SimpleAction>().unwrap();
+            let app_state = app_state.clone();
+            let update_ui = update_ui.clone();
+            let update_title = update_title.clone();
+            let update_save_action = update_save_action.clone();
+
+            simple.connect_activate(move |_, _| {
+                let mut state = app_state.borrow_mut();
+                let AppState { project, history, .. } = &mut *state;
+                let changed = if undo { history.undo(project) } else { history.redo(project) };
+                if !changed {
+                    return;
+                }
+                state.autosave_pending = true;
+                state.is_modified = !state.history.is_saved();
+                drop(state);
+                update_ui();
+                update_title();
+                update_save_action();
+            });
+        }
+    }
+
     // Initialize UI
     update_title();
     update_ui();
@@ -1132,7 +1497,11 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
             0 => content_stack_clone.set_visible_child_name("configuration"),
             1 => content_stack_clone.set_visible_child_name("files"),
             2 => content_stack_clone.set_visible_child_name("screens"),
-            3 => content_stack_clone.set_visible_child_name("build"),
+            3 => {
+                refresh_issues();
+                content_stack_clone.set_visible_child_name("issues");
+            }
+            4 => content_stack_clone.set_visible_child_name("build"),
             _ => {}
         }
     });
@@ -1145,15 +1514,9 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
     
     // Helper to update screen enabled state
     let update_screen_state = {
-        let app_state = app_state.clone();
-        let mark_modified = mark_modified.clone();
+        let edit = edit.clone();
         move |screen_id: &str, enabled: bool| {
-            let mut state = app_state.borrow_mut();
-            if let Some(screen) = state.project.installer_screens.iter_mut().find(|s| s.id == screen_id) {
-                screen.enabled = enabled;
-                drop(state);
-                mark_modified();
-            }
+            edit(Command::SetScreenEnabled(screen_id.to_string(), enabled));
         }
     };
     
@@ -1181,6 +1544,14 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
         });
     }
     
+    // Connect directory screen switch
+    {
+        let update_fn = update_screen_state.clone();
+        screen_directory.connect_active_notify(move |switch| {
+            update_fn("directory", switch.is_active());
+        });
+    }
+    
     // Connect finish screen switch
     {
         let update_fn = update_screen_state.clone();
@@ -1188,28 +1559,487 @@ fn setup_window_interaction(builder: &gtk4::Builder, app_state: Rc<RefCell<AppSt
             update_fn("finish", switch.is_active());
         });
     }
-    
-    // Load initial screen states from project
-    {
-        let state = app_state.borrow();
-        for screen in &state.project.installer_screens {
-            match screen.id.as_str() {
-                "welcome" => screen_welcome.set_active(screen.enabled),
-                "license" => screen_license.set_active(screen.enabled),
-                "install_location" => screen_install_location.set_active(screen.enabled),
-                "finish" => screen_finish.set_active(screen.enabled),
-                _ => {}
+
+    // Unsaved changes go to the recovery file now and then, so a crash doesn't lose them
+    glib::timeout_add_seconds_local(AUTOSAVE_INTERVAL, {
+        let app_state = app_state.clone();
+        let session = session.clone();
+        move || {
+            let mut state = app_state.borrow_mut();
+            if state.is_modified && state.autosave_pending {
+                state.autosave_pending = false;
+                state.owns_recovery = true;
+                let (project, path) = (state.project.clone(), state.current_path.clone());
+                drop(state);
+                update_session(&session, |session| {
+                    if let Err(e) = session.autosave(&project, path.as_deref()) {
+                        eprintln!("Could not save the unsaved changes: {}", e);
+                    }
+                });
+            } else if !state.is_modified && state.owns_recovery {
+                // The edits were undone back to the saved project: nothing to recover
+                state.owns_recovery = false;
+                drop(state);
+                update_session(&session, Session::discard_recovery);
             }
+            glib::ControlFlow::Continue
         }
+    });
+
+    // Once the window is shown, offer the changes a crash left behind, or
+    // reopen the last project
+    glib::idle_add_local_once(move || {
+        let reopen_last_project = {
+            let session = session.clone();
+            move || {
+                let last = {
+                    let session = session.borrow();
+                    session.last_project().filter(|_| session.reopen_last_project).map(PathBuf::from)
+                };
+                if let Some(path) = last {
+                    open_project(path);
+                }
+            }
+        };
+
+        let recovery = session.borrow().recovery.clone();
+        let recovered = session.borrow().recovered_project();
+        let (Some(recovery), Some(recovered)) = (recovery, recovered) else {
+            reopen_last_project();
+            return;
+        };
+        let project = match recovered {
+            Ok(project) => project,
+            Err(e) => {
+                eprintln!("Could not read the unsaved changes: {}", e);
+                update_session(&session, Session::discard_recovery);
+                reopen_last_project();
+                return;
+            }
+        };
+
+        let name = recovery.project.as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "an untitled project".to_string());
+        let alert = AlertDialog::builder()
+            .modal(true)
+            .message("Recover Unsaved Changes?")
+            .detail(format!("The builder didn't close normally. Changes to {} from {} weren't saved.", name, format_time(recovery.time)))
+            .buttons(["Discard", "Recover"])
+            .default_button(1)
+            .cancel_button(0)
+            .build();
+        let window_for_alert = window.clone();
+        alert.choose(Some(&window_for_alert), gtk4::gio::Cancellable::NONE, move |result| {
+            if let Ok(1) = result {
+                let mut state = app_state.borrow_mut();
+                state.project = project;
+                state.current_path = recovery.project;
+                state.profile = None;
+                state.is_modified = true;
+                state.history = History::new();
+                state.history.mark_unsaved();
+                state.autosave_pending = false;
+                state.owns_recovery = true;
+                drop(state);
+                update_ui();
+                update_title();
+                update_save_action();
+                root_stack.set_visible_child_name("main_view");
+                content_stack.set_visible_child_name("configuration");
+            } else {
+                update_session(&session, Session::discard_recovery);
+                reopen_last_project();
+            }
+        });
+    });
+}
+
+/// Most files listed in the preview of a directory or pattern
+const PREVIEW_LIMIT: usize = 200;
+
+/// Row of a directory or glob entry, with its include and exclude patterns
+/// and a preview of the files it expands to
+fn pattern_row(
+    file: &ProjectFile,
+    idx: usize,
+    app_state: &Rc<RefCell<AppState>>,
+    edit: &(impl Fn(Command) + Clone + 'static),
+    refresh: &(impl Fn() + Clone + 'static),
+) -> adw::ExpanderRow {
+    let expanded = file.expand();
+    let summary = match &expanded {
+        Ok(files) => format!("{}/ ({} files)", file.destination.trim_end_matches('/'), files.len()),
+        Err(e) => e.clone(),
+    };
+    let row = adw::ExpanderRow::builder()
+        .title(file.source.to_string_lossy().as_ref())
+        .subtitle(&summary)
+        .build();
+
+    // Pattern lists, applied with the entry's apply button
+    type PatternList = fn(&mut ProjectFile) -> &mut Vec<String>;
+    let patterns: [(&str, PatternList); 2] = [
+        ("Include (e.g., **/*.py, locale/**)", |f| &mut f.include),
+        ("Exclude (e.g., **/__pycache__/**)", |f| &mut f.exclude),
+    ];
+    for (title, list) in patterns {
+        let entry = adw::EntryRow::builder()
+            .title(title)
+            .show_apply_button(true)
+            .build();
+        entry.set_text(&list(&mut file.clone()).join(", "));
+
+        let app_state = app_state.clone();
+        let edit = edit.clone();
+        let refresh = refresh.clone();
+        entry.connect_apply(move |entry| {
+            let file = app_state.borrow().project.files.get(idx).cloned();
+            if let Some(mut file) = file {
+                *list(&mut file) = split_list(&entry.text());
+                edit(Command::SetFile(idx, file));
+            }
+            refresh();
+        });
+        row.add_row(&entry);
+    }
+
+    if let Ok(files) = &expanded {
+        for expanded_file in files.iter().take(PREVIEW_LIMIT) {
+            let preview = adw::ActionRow::builder()
+                .title(&expanded_file.destination)
+                .css_classes(["dim-label"])
+                .build();
+            row.add_row(&preview);
+        }
+        if files.len() > PREVIEW_LIMIT {
+            let more = adw::ActionRow::builder()
+                .title(format!("and {} more", files.len() - PREVIEW_LIMIT))
+                .build();
+            row.add_row(&more);
+        }
+    }
+
+    row
+}
+
+/// Row of a build profile, with the fields it overrides; the files it adds,
+/// replaces or removes are listed below them
+fn profile_row(
+    profile: &BuildProfile,
+    idx: usize,
+    app_state: &Rc<RefCell<AppState>>,
+    edit: &(impl Fn(Command) + Clone + 'static),
+    refresh: &(impl Fn() + Clone + 'static),
+) -> adw::ExpanderRow {
+    let package_name = app_state.borrow().project.resolved(Some(&profile.name))
+        .map(|project| project.package_name)
+        .unwrap_or_default();
+    let row = adw::ExpanderRow::builder()
+        .title(&profile.name)
+        .subtitle(&package_name)
+        .build();
+
+    let name_entry = adw::EntryRow::builder()
+        .title("Profile Name")
+        .show_apply_button(true)
+        .build();
+    name_entry.set_text(&profile.name);
+    name_entry.connect_apply({
+        let app_state = app_state.clone();
+        let edit = edit.clone();
+        let refresh = refresh.clone();
+        move |entry| {
+            let name = entry.text().trim().to_string();
+            let Some(mut profile) = app_state.borrow().project.profiles.get(idx).cloned() else {
+                return;
+            };
+            let previous = std::mem::replace(&mut profile.name, name.clone());
+            edit(Command::SetProfile(idx, profile));
+            // The Build page keeps building the renamed profile
+            let mut state = app_state.borrow_mut();
+            if state.profile.as_deref() == Some(previous.as_str()) {
+                state.profile = Some(name);
+            }
+            drop(state);
+            refresh();
+        }
+    });
+    row.add_row(&name_entry);
+
+    // Overrides, applied with the entry's apply button; empty keeps the project's value
+    type Override = fn(&mut BuildProfile) -> &mut Option<String>;
+    let overrides: [(&str, Override); 6] = [
+        ("Project Name", |p| &mut p.metadata.name),
+        ("Application Name", |p| &mut p.metadata.application_name),
+        ("App ID", |p| &mut p.metadata.app_id),
+        ("Version", |p| &mut p.metadata.version),
+        ("Description", |p| &mut p.metadata.description),
+        ("Package Name", |p| &mut p.package_name),
+    ];
+    for (title, field) in overrides {
+        let entry = adw::EntryRow::builder()
+            .title(title)
+            .show_apply_button(true)
+            .build();
+        entry.set_text(field(&mut profile.clone()).as_deref().unwrap_or_default());
+
+        let app_state = app_state.clone();
+        let edit = edit.clone();
+        let refresh = refresh.clone();
+        entry.connect_apply(move |entry| {
+            let text = entry.text().trim().to_string();
+            let profile = app_state.borrow().project.profiles.get(idx).cloned();
+            if let Some(mut profile) = profile {
+                *field(&mut profile) = (!text.is_empty()).then_some(text);
+                edit(Command::SetProfile(idx, profile));
+            }
+            refresh();
+        });
+        row.add_row(&entry);
+    }
+
+    // Lists, as comma separated text
+    type ProfileList = (fn(&BuildProfile) -> String, fn(&mut BuildProfile, &str));
+    let lists: [(&str, ProfileList); 2] = [
+        ("Variables (comma separated, e.g., channel=nightly)", (
+            |p| format_variables(&p.variables),
+            |p, text| p.variables = parse_variables(text),
+        )),
+        ("Removed Files (comma separated destinations)", (
+            |p| p.removed_files.join(", "),
+            |p, text| p.removed_files = split_list(text),
+        )),
+    ];
+    for (title, (get, set)) in lists {
+        let entry = adw::EntryRow::builder()
+            .title(title)
+            .show_apply_button(true)
+            .build();
+        entry.set_text(&get(profile));
+
+        let app_state = app_state.clone();
+        let edit = edit.clone();
+        let refresh = refresh.clone();
+        entry.connect_apply(move |entry| {
+            let profile = app_state.borrow().project.profiles.get(idx).cloned();
+            if let Some(mut profile) = profile {
+                set(&mut profile, &entry.text());
+                edit(Command::SetProfile(idx, profile));
+            }
+            refresh();
+        });
+        row.add_row(&entry);
     }
+
+    for file in &profile.files {
+        let file_row = adw::ActionRow::builder()
+            .title(file.source.to_string_lossy().as_ref())
+            .subtitle(&file.destination)
+            .css_classes(["dim-label"])
+            .build();
+        row.add_row(&file_row);
+    }
+
+    row
+}
+
+/// New Project wizard: pick the application's source directory and a
+/// template, detected from the directory
+fn show_new_project_dialog(window: &adw::ApplicationWindow, on_create: impl Fn(Project) + 'static) {
+    let dialog_builder = gtk4::Builder::from_string(include_str!("../data/ui/new_project.ui"));
+    let dialog: adw::Dialog = dialog_builder.object("new_project_dialog").expect("new_project_dialog");
+    let cancel_button: gtk4::Button = dialog_builder.object("new_project_cancel_button").expect("new_project_cancel_button");
+    let create_button: gtk4::Button = dialog_builder.object("new_project_create_button").expect("new_project_create_button");
+    let source_dir_row: adw::ActionRow = dialog_builder.object("source_dir_row").expect("source_dir_row");
+    let source_dir_button: gtk4::Button = dialog_builder.object("source_dir_button").expect("source_dir_button");
+    let template_row: adw::ComboRow = dialog_builder.object("template_row").expect("template_row");
+
+    // "Empty" first, then the templates in order
+    let names: Vec<&str> = std::iter::once("Empty")
+        .chain(templates::Template::ALL.iter().map(|t| t.name()))
+        .collect();
+    template_row.set_model(Some(&gtk4::StringList::new(&names)));
+
+    let source_dir: Rc<RefCell<Option<PathBuf>>> = Rc::new(RefCell::new(None));
+
+    source_dir_button.connect_clicked({
+        let dialog = dialog.clone();
+        let source_dir = source_dir.clone();
+        let source_dir_row = source_dir_row.clone();
+        let template_row = template_row.clone();
+        let create_button = create_button.clone();
+        move |_| {
+            let file_dialog = FileDialog::builder().title("Select Source Directory").modal(true).build();
+            let source_dir = source_dir.clone();
+            let source_dir_row = source_dir_row.clone();
+            let template_row = template_row.clone();
+            let create_button = create_button.clone();
+            let parent = dialog.root().and_downcast::<gtk4::Window>();
+            file_dialog.select_folder(parent.as_ref(), gtk4::gio::Cancellable::NONE, move |result| {
+                let Some(path) = result.ok().and_then(|folder| folder.path()) else {
+                    return;
+                };
+                source_dir_row.set_subtitle(&path.to_string_lossy());
+                match templates::Template::detect(&path) {
+                    Some(template) => {
+                        let index = templates::Template::ALL.iter().position(|t| *t == template).unwrap_or_default();
+                        template_row.set_selected(index as u32 + 1);
+                        template_row.set_subtitle(&format!("Detected {} project", template.name()));
+                    }
+                    None => {
+                        template_row.set_selected(0);
+                        template_row.set_subtitle("Could not detect the project type");
+                    }
+                }
+                *source_dir.borrow_mut() = Some(path);
+                create_button.set_sensitive(true);
+            });
+        }
+    });
+
+    cancel_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |_| {
+            dialog.close();
+        }
+    });
+
+    create_button.connect_clicked({
+        let dialog = dialog.clone();
+        move |_| {
+            let Some(dir) = source_dir.borrow().clone() else {
+                return;
+            };
+            let template = (template_row.selected() as usize).checked_sub(1)
+                .and_then(|index| templates::Template::ALL.get(index).copied());
+            dialog.close();
+            on_create(templates::new_project(&dir, template));
+        }
+    });
+
+    dialog.present(Some(window));
 }
 
-fn show_welcome_screen(window: &adw::ApplicationWindow, root_stack: &gtk4::Stack) {
+/// Recent projects on the welcome screen
+#[derive(Clone)]
+struct RecentProjectsView {
+    window: adw::ApplicationWindow,
+    recent_box: gtk4::Box,
+    list: gtk4::ListBox,
+    remove_missing_button: gtk4::Button,
+    session: Rc<RefCell<Session>>,
+}
+
+impl RecentProjectsView {
+    /// List the projects again, pinned first, with their last build
+    fn fill(&self) {
+        while let Some(child) = self.list.first_child() {
+            self.list.remove(&child);
+        }
+
+        let projects: Vec<session::RecentProject> = self.session.borrow().projects().into_iter().cloned().collect();
+        self.recent_box.set_visible(!projects.is_empty());
+        self.remove_missing_button.set_visible(projects.iter().any(|p| !p.path.is_file()));
+
+        for project in projects {
+            let exists = project.path.is_file();
+            let (icon, class, status) = match &project.last_build {
+                _ if !exists => ("dialog-question-symbolic", "dim-label", "Not found".to_string()),
+                Some(build) if build.succeeded => ("emblem-ok-symbolic", "success", format!("Built on {}", format_time(build.time))),
+                Some(build) => ("dialog-error-symbolic", "error", format!("Build failed on {}", format_time(build.time))),
+                None => ("document-open-recent-symbolic", "dim-label", "Not built yet".to_string()),
+            };
+            let title = project.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let row = adw::ActionRow::builder()
+                .title(&title)
+                .subtitle(format!("{}\n{}", project.path.display(), status))
+                .activatable(exists)
+                .build();
+            let image = gtk4::Image::from_icon_name(icon);
+            image.add_css_class(class);
+            row.add_prefix(&image);
+
+            let pin_button = gtk4::ToggleButton::builder()
+                .icon_name("view-pin-symbolic")
+                .tooltip_text(if project.pinned { "Unpin" } else { "Pin" })
+                .active(project.pinned)
+                .valign(gtk4::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            pin_button.connect_toggled({
+                let view = self.clone();
+                let path = project.path.clone();
+                move |button| {
+                    update_session(&view.session, |session| session.set_pinned(&path, button.is_active()));
+                    view.fill();
+                }
+            });
+            row.add_suffix(&pin_button);
+
+            let remove_button = gtk4::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Remove from Recent Projects")
+                .valign(gtk4::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            remove_button.connect_clicked({
+                let view = self.clone();
+                let path = project.path.clone();
+                move |_| {
+                    update_session(&view.session, |session| session.remove(&path));
+                    view.fill();
+                }
+            });
+            row.add_suffix(&remove_button);
+
+            row.connect_activated({
+                let window = self.window.clone();
+                let path = project.path.to_string_lossy().to_string();
+                move |_| {
+                    if let Some(app) = window.application() {
+                        app.activate_action("open-project-file", Some(&path.to_variant()));
+                    }
+                }
+            });
+            self.list.append(&row);
+        }
+    }
+}
+
+fn show_welcome_screen(window: &adw::ApplicationWindow, root_stack: &gtk4::Stack, session: &Rc<RefCell<Session>>) {
     let welcome_builder = gtk4::Builder::from_string(include_str!("../data/ui/welcome.ui"));
     let welcome_view: adw::ToolbarView = welcome_builder.object("welcome_view").expect("Could not get welcome_view");
     let new_btn: gtk4::Button = welcome_builder.object("new_project_button").expect("new_project_button");
     let open_btn: gtk4::Button = welcome_builder.object("open_project_button").expect("open_project_button");
     let close_btn: gtk4::Button = welcome_builder.object("close_welcome_button").expect("close_welcome_button");
+    let reopen_last_switch: adw::SwitchRow = welcome_builder.object("reopen_last_switch").expect("reopen_last_switch");
+
+    let recent_projects = RecentProjectsView {
+        window: window.clone(),
+        recent_box: welcome_builder.object("recent_projects_box").expect("recent_projects_box"),
+        list: welcome_builder.object("recent_projects_list").expect("recent_projects_list"),
+        remove_missing_button: welcome_builder.object("remove_missing_button").expect("remove_missing_button"),
+        session: session.clone(),
+    };
+    recent_projects.fill();
+
+    recent_projects.remove_missing_button.connect_clicked({
+        let recent_projects = recent_projects.clone();
+        move |_| {
+            update_session(&recent_projects.session, Session::remove_missing);
+            recent_projects.fill();
+        }
+    });
+
+    reopen_last_switch.set_active(session.borrow().reopen_last_project);
+    reopen_last_switch.connect_active_notify({
+        let session = session.clone();
+        move |switch| {
+            update_session(&session, |session| session.reopen_last_project = switch.is_active());
+        }
+    });
 
 
 
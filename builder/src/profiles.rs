@@ -0,0 +1,166 @@
+use crate::project::{self, BuildProfile, Project};
+
+/// Variables every project has, from its metadata and the profile being built
+pub const BUILT_IN_VARIABLES: [&str; 5] = ["name", "app_id", "version", "application_name", "profile"];
+
+impl Project {
+    /// Build profile by name
+    pub fn profile(&self, name: &str) -> Option<&BuildProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Copy of the project as built with a profile, or without one: the
+    /// profile's metadata, package name and files applied, then the
+    /// variables substituted in the package name and destinations. Unknown
+    /// variables are left as written, for validation to report.
+    pub fn resolved(&self, profile: Option<&str>) -> Result<Project, String> {
+        let profile = match profile {
+            Some(name) => Some(self.profile(name).ok_or_else(|| format!("Unknown build profile '{}'", name))?),
+            None => None,
+        };
+
+        let mut project = self.clone();
+        if let Some(profile) = profile {
+            project.apply_profile(profile);
+        }
+
+        let variables = project.variables(profile);
+        let lookup = |name: &str| variables.iter().find(|(n, _)| n == name).map(|(_, value)| value.clone());
+        project.package_name = project::substitute_variables(&project.package_name, lookup);
+        for file in &mut project.files {
+            file.destination = project::substitute_variables(&file.destination, lookup);
+        }
+        Ok(project)
+    }
+
+    fn apply_profile(&mut self, profile: &BuildProfile) {
+        let overrides = &profile.metadata;
+        let metadata = &mut self.metadata;
+        let fields = [
+            (&overrides.name, &mut metadata.name),
+            (&overrides.app_id, &mut metadata.app_id),
+            (&overrides.version, &mut metadata.version),
+            (&overrides.application_name, &mut metadata.application_name),
+            (&overrides.description, &mut metadata.description),
+        ];
+        for (value, field) in fields {
+            if let Some(value) = value {
+                *field = value.clone();
+            }
+        }
+        if let Some(package_name) = &profile.package_name {
+            self.package_name = package_name.clone();
+        }
+
+        // Destinations as written, before substituting variables
+        self.files.retain(|f| !profile.removed_files.contains(&f.destination));
+        for file in &profile.files {
+            match self.files.iter_mut().find(|f| f.destination == file.destination) {
+                Some(replaced) => *replaced = file.clone(),
+                None => self.files.push(file.clone()),
+            }
+        }
+    }
+
+    /// Values of the variables: the built-in ones, then those of the project
+    /// and of the profile, which can use the variables before them
+    fn variables(&self, profile: Option<&BuildProfile>) -> Vec<(String, String)> {
+        let metadata = &self.metadata;
+        let mut variables: Vec<(String, String)> = BUILT_IN_VARIABLES.iter()
+            .map(|name| {
+                let value = match *name {
+                    "name" => metadata.name.clone(),
+                    "app_id" => metadata.app_id.clone(),
+                    "version" => metadata.version.clone(),
+                    "application_name" => metadata.application_name.clone(),
+                    _ => profile.map(|p| p.name.clone()).unwrap_or_default(),
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+
+        let own = self.variables.iter().chain(profile.into_iter().flat_map(|p| &p.variables));
+        for variable in own {
+            let value = project::substitute_variables(&variable.value, |name| {
+                variables.iter().find(|(n, _)| n == name).map(|(_, value)| value.clone())
+            });
+            variables.retain(|(name, _)| *name != variable.name);
+            variables.push((variable.name.clone(), value));
+        }
+        variables
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::project::{ProfileMetadata, ProjectFile, ProjectVariable};
+
+    fn file(source: &str, destination: &str) -> ProjectFile {
+        ProjectFile {
+            source: PathBuf::from(source),
+            destination: destination.to_string(),
+            permissions: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    fn project() -> Project {
+        let mut project = Project::new();
+        project.metadata.app_id = "com.example.Hello".to_string();
+        project.metadata.version = "2.0.0".to_string();
+        project.package_name = "hello-${channel}-${version}.lis".to_string();
+        project.files = vec![
+            file("/src/target/release/hello", "bin/hello"),
+            file("/src/data/${channel}.svg", "share/icons/hicolor/scalable/apps/${app_id}.svg"),
+            file("/src/README", "share/doc/${app_id}/README"),
+        ];
+        project.variables = vec![ProjectVariable { name: "channel".to_string(), value: "stable".to_string() }];
+        project.profiles = vec![BuildProfile {
+            name: "nightly".to_string(),
+            metadata: ProfileMetadata {
+                app_id: Some("com.example.Hello.Nightly".to_string()),
+                ..ProfileMetadata::default()
+            },
+            package_name: None,
+            files: vec![file("/src/target/debug/hello", "bin/hello"), file("/src/NEWS", "share/doc/${app_id}/NEWS")],
+            removed_files: vec!["share/doc/${app_id}/README".to_string()],
+            variables: vec![ProjectVariable { name: "channel".to_string(), value: "${profile}".to_string() }],
+        }];
+        project
+    }
+
+    #[test]
+    fn without_profile_substitutes_project_variables() {
+        let project = project().resolved(None).unwrap();
+        assert_eq!(project.package_name, "hello-stable-2.0.0.lis");
+        assert_eq!(project.files[1].destination, "share/icons/hicolor/scalable/apps/com.example.Hello.svg");
+        // Sources are paths, with environment variables only
+        assert_eq!(project.files[1].source, PathBuf::from("/src/data/${channel}.svg"));
+    }
+
+    #[test]
+    fn profile_overrides_metadata_files_and_variables() {
+        let project = project().resolved(Some("nightly")).unwrap();
+        assert_eq!(project.metadata.app_id, "com.example.Hello.Nightly");
+        assert_eq!(project.metadata.version, "2.0.0");
+        assert_eq!(project.package_name, "hello-nightly-2.0.0.lis");
+
+        let files: Vec<(&str, &str)> = project.files.iter()
+            .map(|f| (f.source.to_str().unwrap(), f.destination.as_str()))
+            .collect();
+        assert_eq!(files, [
+            ("/src/target/debug/hello", "bin/hello"),
+            ("/src/data/${channel}.svg", "share/icons/hicolor/scalable/apps/com.example.Hello.Nightly.svg"),
+            ("/src/NEWS", "share/doc/com.example.Hello.Nightly/NEWS"),
+        ]);
+    }
+
+    #[test]
+    fn unknown_profile_is_an_error() {
+        assert!(project().resolved(Some("beta")).is_err());
+    }
+}
@@ -1,36 +1,219 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
 
-/// Represents a file to be included in the package
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use quick_xml::events::Event;
+use serde_path_to_error::Segment;
+
+/// Version of the project file format, written as `<project version="...">`.
+///
+/// 1. Unversioned `<Project>` files, with absolute paths
+/// 2. Versioned root element; paths relative to the project file
+/// 3. Build profiles and project variables
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Why a project file couldn't be loaded or saved
+#[derive(Debug)]
+pub enum ProjectError {
+    Read { path: PathBuf, error: std::io::Error },
+    Write { path: PathBuf, error: std::io::Error },
+    /// The file isn't well-formed XML
+    Syntax { line: usize, message: String },
+    /// An element is missing or has an invalid value; `element` is its path
+    /// from the root (e.g., "files[2].destination")
+    Invalid { element: String, line: Option<usize>, message: String },
+    /// The file was saved by a newer builder
+    UnsupportedVersion { version: u32 },
+    Serialize(String),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectError::Read { path, error } => write!(f, "Failed to read project file {}: {}", path.display(), error),
+            ProjectError::Write { path, error } => write!(f, "Failed to write project file {}: {}", path.display(), error),
+            ProjectError::Syntax { line, message } => write!(f, "Invalid project file at line {}: {}", line, message),
+            ProjectError::Invalid { element, line: Some(line), message } => {
+                write!(f, "Invalid <{}> at line {}: {}", element, line, message)
+            }
+            ProjectError::Invalid { element, line: None, message } => write!(f, "Invalid <{}>: {}", element, message),
+            ProjectError::UnsupportedVersion { version } => write!(
+                f,
+                "The project was saved by a newer builder (format version {}, this one reads up to {})",
+                version, SCHEMA_VERSION
+            ),
+            ProjectError::Serialize(message) => write!(f, "Failed to serialize project: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+/// Represents a file to be included in the package, or a directory or glob
+/// pattern (e.g., "/home/me/app/po/*.mo") expanded when the package is built
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProjectFile {
-    /// Source path (absolute)
+    /// Source path: a file, a directory or a glob pattern. Absolute once
+    /// loaded; see [`Project::load_from_file`]
     pub source: PathBuf,
-    /// Destination path (relative to install directory)
+    /// Destination path (relative to install directory); the directory the
+    /// matched files go to for directories and patterns
     pub destination: String,
     /// File permissions (Unix-style, e.g., "755" for executables)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub permissions: Option<String>,
+    /// Patterns a file of a directory or pattern must match, relative to its
+    /// base directory (e.g., "**/*.py"); empty means every file
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Patterns of files left out (e.g., "**/__pycache__/**")
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
 /// Installer screen configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InstallerScreen {
-    /// Screen ID (welcome, license, install_location, finish)
+    /// Screen ID (welcome, license, install_location, directory, finish)
     /// Note: progress screen is always shown automatically during installation
     pub id: String,
     /// Whether this screen is enabled
+    #[serde(default)]
     pub enabled: bool,
     /// Display order (1-based)
+    #[serde(default)]
     pub order: usize,
     /// Optional custom content (e.g., license file path)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub custom_content: Option<String>,
 }
 
-/// Project metadata
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// System requirements checked by the installer
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectRequirements {
+    /// Minimum GTK version (e.g., "4.10")
+    #[serde(default)]
+    pub min_gtk_version: String,
+    /// Minimum libadwaita version (e.g., "1.5")
+    #[serde(default)]
+    pub min_libadwaita_version: String,
+    /// Supported CPU architectures; empty means any
+    #[serde(default)]
+    pub architectures: Vec<String>,
+    /// System libraries that are not bundled (e.g., "libssl.so.3")
+    #[serde(default)]
+    pub libraries: Vec<String>,
+}
+
+/// Relations with other .lis packages, by app ID with an optional version constraint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectRelations {
+    /// Packages that must be installed first (e.g., "com.example.Runtime >= 1.2")
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Packages that can't be installed at the same time
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    /// Other app IDs this package stands in for (e.g., "com.example.Runtime = 1.2")
+    #[serde(default)]
+    pub provides: Vec<String>,
+}
+
+/// Scripts run by the installer around installation and uninstallation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectHooks {
+    /// Source paths
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_install: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_install: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_uninstall: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_uninstall: Option<PathBuf>,
+}
+
+impl ProjectHooks {
+    /// Script slot of a hook by its name (pre_install, post_install, ...)
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Option<PathBuf>> {
+        match name {
+            "pre_install" => Some(&mut self.pre_install),
+            "post_install" => Some(&mut self.post_install),
+            "pre_uninstall" => Some(&mut self.pre_uninstall),
+            "post_uninstall" => Some(&mut self.post_uninstall),
+            _ => None,
+        }
+    }
+
+    /// Every hook with a script, by name
+    pub fn defined(&self) -> Vec<(&'static str, &PathBuf)> {
+        [
+            ("pre_install", &self.pre_install),
+            ("post_install", &self.post_install),
+            ("pre_uninstall", &self.pre_uninstall),
+            ("post_uninstall", &self.post_uninstall),
+        ]
+        .into_iter()
+        .filter_map(|(name, path)| path.as_ref().map(|p| (name, p)))
+        .collect()
+    }
+}
+
+/// A variable of the project, written `${name}` in the package name and destinations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectVariable {
+    pub name: String,
+    #[serde(default)]
+    pub value: String,
+}
+
+/// Metadata a build profile overrides; unset fields keep the project's value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub application_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A variant of the project built from the same sources (e.g., "beta" or
+/// "nightly"), chosen when building. See [`Project::resolved`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildProfile {
+    /// Profile name, the value of `${profile}`
+    pub name: String,
+    /// Metadata overrides
+    pub metadata: ProfileMetadata,
+    /// Package name (output filename) of the profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package_name: Option<String>,
+    /// Files that replace the project's file with the same destination, or
+    /// are added to the project's files
+    pub files: Vec<ProjectFile>,
+    /// Destinations of project files left out of the profile's package
+    pub removed_files: Vec<String>,
+    /// Variables the profile adds or gives another value
+    pub variables: Vec<ProjectVariable>,
+}
+
+/// Project metadata. Missing elements take their default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ProjectMetadata {
     /// Project name
     pub name: String,
+    /// Reverse-DNS application ID (e.g., "com.example.App"); names the desktop
+    /// file, the installation manifest and the data directory
+    pub app_id: String,
     /// Project version
     pub version: String,
     /// Author name
@@ -42,43 +225,81 @@ pub struct ProjectMetadata {
     /// Output directory for generated .lis file
     pub output_directory: PathBuf,
     /// Desktop file path
-    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub desktop_file: Option<PathBuf>,
 }
 
-/// Main project structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for ProjectMetadata {
+    fn default() -> Self {
+        Self {
+            name: String::from("MyProject"),
+            app_id: String::new(),
+            version: String::from("1.0.0"),
+            author: String::new(),
+            description: String::new(),
+            application_name: String::from("My Application"),
+            output_directory: PathBuf::from("."),
+            desktop_file: None,
+        }
+    }
+}
+
+/// Main project structure. Missing elements take their value from
+/// [`Project::new`], so files of older versions keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Project {
+    /// Format version of the file, see [`SCHEMA_VERSION`]; read from the
+    /// root element before the rest of the file
+    #[serde(rename = "@version", skip_deserializing)]
+    version: u32,
     /// Project metadata
     pub metadata: ProjectMetadata,
     /// List of files to include
-    #[serde(default)]
     pub files: Vec<ProjectFile>,
     /// Installer screen configuration
     pub installer_screens: Vec<InstallerScreen>,
+    /// System requirements
+    pub requirements: ProjectRequirements,
+    /// Relations with other packages
+    pub relations: ProjectRelations,
+    /// Hook scripts
+    pub hooks: ProjectHooks,
     /// Package name (output filename)
     pub package_name: String,
     /// Compression level (0-9)
     pub compression_level: u8,
+    /// Variables for the package name and destinations, besides the built-in ones
+    pub variables: Vec<ProjectVariable>,
+    /// Build profiles
+    pub profiles: Vec<BuildProfile>,
+    /// Paths written with environment variables, by the path they resolved to,
+    /// so they are saved as written
+    #[serde(skip)]
+    written_paths: HashMap<PathBuf, PathBuf>,
+    /// Whether the project file was upgraded from an older format, or had
+    /// absolute paths that are saved relative to it from now on
+    #[serde(skip)]
+    pub upgraded: bool,
 }
 
 impl Project {
     /// Create a new empty project with default values
     pub fn new() -> Self {
         Self {
-            metadata: ProjectMetadata {
-                name: String::from("MyProject"),
-                version: String::from("1.0.0"),
-                author: String::new(),
-                description: String::new(),
-                application_name: String::from("My Application"),
-                output_directory: PathBuf::from("."),
-                desktop_file: None,
-            },
+            version: SCHEMA_VERSION,
+            metadata: ProjectMetadata::default(),
             files: Vec::new(),
             installer_screens: Self::default_screens(),
+            requirements: ProjectRequirements::default(),
+            relations: ProjectRelations::default(),
+            hooks: ProjectHooks::default(),
             package_name: String::from("package.lis"),
             compression_level: 9,
+            variables: Vec::new(),
+            profiles: Vec::new(),
+            written_paths: HashMap::new(),
+            upgraded: false,
         }
     }
 
@@ -103,37 +324,161 @@ impl Project {
                 order: 3,
                 custom_content: None,
             },
+            InstallerScreen {
+                id: String::from("directory"),
+                enabled: false,
+                order: 4,
+                custom_content: None,
+            },
             InstallerScreen {
                 id: String::from("finish"),
                 enabled: true,
-                order: 4,
+                order: 5,
                 custom_content: None,
             },
         ]
     }
 
-    /// Save project to XML file
-    #[allow(dead_code)]
-    pub fn save_to_file(&self, path: &PathBuf) -> Result<(), String> {
-        let xml = quick_xml::se::to_string(&self)
-            .map_err(|e| format!("Failed to serialize project: {}", e))?;
+    /// Add screens introduced after the project was saved (disabled), keeping the default order
+    fn add_missing_screens(&mut self) {
+        self.installer_screens.sort_by_key(|s| s.order);
+        for (default_idx, default) in Self::default_screens().into_iter().enumerate() {
+            if !self.installer_screens.iter().any(|s| s.id == default.id) {
+                let insert_at = self.installer_screens.len().min(default_idx);
+                self.installer_screens.insert(insert_at, InstallerScreen { enabled: false, ..default });
+            }
+        }
+
+        for (idx, screen) in self.installer_screens.iter_mut().enumerate() {
+            screen.order = idx + 1;
+        }
+    }
+
+    /// Every path of the project: output directory, desktop file, file
+    /// sources, including those of the profiles, and hooks
+    pub fn paths(&self) -> Vec<&PathBuf> {
+        let hooks = &self.hooks;
+        std::iter::once(&self.metadata.output_directory)
+            .chain(self.metadata.desktop_file.as_ref())
+            .chain(self.files.iter().map(|f| &f.source))
+            .chain(self.profiles.iter().flat_map(|p| &p.files).map(|f| &f.source))
+            .chain([&hooks.pre_install, &hooks.post_install, &hooks.pre_uninstall, &hooks.post_uninstall].into_iter().flatten())
+            .collect()
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let hooks = &mut self.hooks;
+        std::iter::once(&mut self.metadata.output_directory)
+            .chain(self.metadata.desktop_file.as_mut())
+            .chain(self.files.iter_mut().map(|f| &mut f.source))
+            .chain(self.profiles.iter_mut().flat_map(|p| &mut p.files).map(|f| &mut f.source))
+            .chain([&mut hooks.pre_install, &mut hooks.post_install, &mut hooks.pre_uninstall, &mut hooks.post_uninstall].into_iter().flatten())
+            .collect()
+    }
+
+    /// Save project to XML file. Paths are written relative to the project
+    /// file so it can be checked out anywhere; paths outside the project's
+    /// tree (e.g., /usr/lib/...) stay absolute, and paths loaded with
+    /// environment variables are written as they were.
+    pub fn save_to_file(&self, path: &PathBuf) -> Result<(), ProjectError> {
+        let project_dir = project_dir(path)?;
+        let mut portable = self.clone();
+        portable.version = SCHEMA_VERSION;
+        for path in portable.paths_mut() {
+            if let Some(written) = self.written_paths.get(path) {
+                *path = written.clone();
+            } else if let Some(relative) = relative_to(path, &project_dir) {
+                *path = relative;
+            }
+        }
+
+        let xml = quick_xml::se::to_string_with_root("project", &portable)
+            .map_err(|e| ProjectError::Serialize(e.to_string()))?;
         
         std::fs::write(path, xml)
-            .map_err(|e| format!("Failed to write project file: {}", e))?;
+            .map_err(|error| ProjectError::Write { path: path.clone(), error })?;
         
         Ok(())
     }
 
-    /// Load project from XML file
-    pub fn load_from_file(path: &PathBuf) -> Result<Self, String> {
+    /// Load project from XML file. Files of older versions are upgraded (see
+    /// [`SCHEMA_VERSION`]). Paths are resolved against the project file's
+    /// directory after substituting `${VAR}` environment variables, so the
+    /// rest of the builder only sees absolute paths.
+    pub fn load_from_file(path: &PathBuf) -> Result<Self, ProjectError> {
         let xml = std::fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read project file: {}", e))?;
+            .map_err(|error| ProjectError::Read { path: path.clone(), error })?;
         
-        let project: Project = quick_xml::de::from_str(&xml)
-            .map_err(|e| format!("Failed to deserialize project: {}", e))?;
+        let version = schema_version(&xml)?;
+        if version > SCHEMA_VERSION {
+            return Err(ProjectError::UnsupportedVersion { version });
+        }
+
+        let mut deserializer = quick_xml::de::Deserializer::from_str(&xml);
+        let mut project: Project = serde_path_to_error::deserialize(&mut deserializer)
+            .map_err(|e| invalid_element(&xml, e))?;
+        project.upgrade(version);
+        project.resolve_paths(&project_dir(path)?);
         
         Ok(project)
     }
+
+    /// Bring a project read from a file of an older format up to date
+    fn upgrade(&mut self, version: u32) {
+        // Screens added since the file was saved, in any version
+        self.add_missing_screens();
+
+        if version < 2 {
+            // Unset values were written as empty elements, which read back as empty strings
+            let hooks = &mut self.hooks;
+            let paths = [&mut self.metadata.desktop_file, &mut hooks.pre_install, &mut hooks.post_install, &mut hooks.pre_uninstall, &mut hooks.post_uninstall];
+            for path in paths {
+                if path.as_ref().is_some_and(|p| p.as_os_str().is_empty()) {
+                    *path = None;
+                }
+            }
+            for file in &mut self.files {
+                if file.permissions.as_deref() == Some("") {
+                    file.permissions = None;
+                }
+            }
+            for screen in &mut self.installer_screens {
+                if screen.custom_content.as_deref() == Some("") {
+                    screen.custom_content = None;
+                }
+            }
+
+            // Projects saved before the app ID could be set
+            if self.metadata.app_id.is_empty() {
+                if let Some(desktop_file) = &self.metadata.desktop_file {
+                    self.metadata.app_id = guess_app_id(desktop_file).unwrap_or_default();
+                }
+            }
+        }
+
+        self.upgraded = version < SCHEMA_VERSION;
+        self.version = SCHEMA_VERSION;
+    }
+
+    fn resolve_paths(&mut self, project_dir: &Path) {
+        let mut written_paths = HashMap::new();
+        let mut absolute = false;
+        for path in self.paths_mut() {
+            let written = path.clone();
+            let substituted = substitute_env_vars(&written.to_string_lossy());
+            let resolved = normalize(&project_dir.join(&substituted));
+
+            if substituted != written.to_string_lossy() {
+                written_paths.insert(resolved.clone(), written);
+            } else if written.is_absolute() && relative_to(&resolved, project_dir).is_some() {
+                // Saved before paths were relative
+                absolute = true;
+            }
+            *path = resolved;
+        }
+        self.written_paths = written_paths;
+        self.upgraded |= absolute;
+    }
 }
 
 impl Default for Project {
@@ -141,3 +486,466 @@ impl Default for Project {
         Self::new()
     }
 }
+
+/// App ID of an application from its desktop file name (e.g.,
+/// "com.example.App.desktop"), or else from the `<id>` of a metainfo file
+/// next to it
+pub fn guess_app_id(desktop_file: &Path) -> Option<String> {
+    let stem = desktop_file.file_stem()?.to_string_lossy().to_string();
+    if liblis::install::validate_app_id(&stem).is_ok() {
+        return Some(stem);
+    }
+
+    let dir = desktop_file.parent()?;
+    std::fs::read_dir(dir).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.ends_with(".metainfo.xml") || name.ends_with(".appdata.xml")
+        })
+        .find_map(|path| {
+            let xml = std::fs::read_to_string(path).ok()?;
+            let start = xml.find("<id>")? + "<id>".len();
+            let end = start + xml[start..].find("</id>")?;
+            // Older appdata files use the desktop file name as the ID
+            let id = xml[start..end].trim().trim_end_matches(".desktop").to_string();
+            liblis::install::validate_app_id(&id).is_ok().then_some(id)
+        })
+}
+
+/// Line of a byte offset of `text`, from 1
+fn line_at(text: &str, offset: u64) -> usize {
+    let offset = (offset as usize).min(text.len());
+    text.as_bytes()[..offset].iter().filter(|b| **b == b'\n').count() + 1
+}
+
+/// `version` attribute of the root element; 1 when there is none
+fn schema_version(xml: &str) -> Result<u32, ProjectError> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        let event = reader.read_event().map_err(|e| ProjectError::Syntax {
+            line: line_at(xml, reader.error_position()),
+            message: e.to_string(),
+        })?;
+        let root = match event {
+            Event::Start(root) | Event::Empty(root) => root,
+            Event::Eof => return Err(ProjectError::Syntax { line: line_at(xml, 0), message: "The file is empty".to_string() }),
+            _ => continue,
+        };
+
+        let line = line_at(xml, reader.buffer_position());
+        let Some(attribute) = root.try_get_attribute("version")
+            .map_err(|e| ProjectError::Syntax { line, message: e.to_string() })?
+        else {
+            return Ok(1);
+        };
+        let value = String::from_utf8_lossy(&attribute.value).to_string();
+        return value.trim().parse().map_err(|_| ProjectError::Invalid {
+            element: "@version".to_string(),
+            line: Some(line),
+            message: format!("'{}' is not a version number", value),
+        });
+    }
+}
+
+/// Element of a deserialization error, with its line
+fn invalid_element(xml: &str, error: serde_path_to_error::Error<quick_xml::DeError>) -> ProjectError {
+    let message = error.inner().to_string();
+    if let quick_xml::DeError::InvalidXml(e) = error.inner() {
+        return ProjectError::Syntax { line: locate_syntax_error(xml).unwrap_or(1), message: e.to_string() };
+    }
+
+    // Element names, with the index of repeated ones (e.g., the third <files>)
+    let mut elements: Vec<(String, usize)> = Vec::new();
+    for segment in error.path() {
+        match segment {
+            Segment::Map { key } => elements.push((key.clone(), 0)),
+            Segment::Seq { index } => {
+                if let Some(last) = elements.last_mut() {
+                    last.1 = *index;
+                }
+            }
+            _ => {}
+        }
+    }
+    let element = elements.iter()
+        .map(|(name, index)| if *index > 0 { format!("{}[{}]", name, index) } else { name.clone() })
+        .collect::<Vec<_>>()
+        .join(".");
+
+    ProjectError::Invalid {
+        element: if element.is_empty() { "project".to_string() } else { element },
+        line: locate_element(xml, &elements),
+        message,
+    }
+}
+
+/// Line of the deepest element of a path from the root that is in the file
+fn locate_element(xml: &str, path: &[(String, usize)]) -> Option<usize> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    // Elements open, the root being 1, and elements of `path` found so far
+    let mut depth = 0;
+    let mut matched = 0;
+    // Elements named like the next one of `path` seen among the current children
+    let mut seen = 0;
+    let mut line = None;
+
+    loop {
+        let position = reader.buffer_position();
+        let (element, empty) = match reader.read_event().ok()? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(_) => {
+                // Leaving the deepest element found: the rest of the path is missing
+                if depth == matched + 1 {
+                    break;
+                }
+                depth -= 1;
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        depth += 1;
+        if depth == 1 {
+            line = Some(line_at(xml, position));
+        } else if depth == matched + 2 && matched < path.len() && element.name().as_ref() == path[matched].0.as_bytes() {
+            if seen == path[matched].1 {
+                matched += 1;
+                seen = 0;
+                line = Some(line_at(xml, position));
+            } else {
+                seen += 1;
+            }
+        }
+        if empty {
+            depth -= 1;
+        }
+    }
+    line
+}
+
+/// Line of the first XML syntax error of a file
+fn locate_syntax_error(xml: &str) -> Option<usize> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Err(_) => return Some(line_at(xml, reader.error_position())),
+            Ok(Event::Eof) => return None,
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Absolute directory of a project file
+fn project_dir(project_file: &Path) -> Result<PathBuf, ProjectError> {
+    let project_file = std::path::absolute(project_file)
+        .map_err(|error| ProjectError::Read { path: project_file.to_path_buf(), error })?;
+    Ok(project_file.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+/// Replace `${VAR}` with the value of the environment variable. Unset
+/// variables are left as written, for validation to report.
+fn substitute_env_vars(text: &str) -> String {
+    substitute_variables(text, |name| std::env::var(name).ok())
+}
+
+/// Replace each `${name}` with the value `lookup` gives it; unknown variables
+/// are left as written
+pub fn substitute_variables(text: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        result.push_str(&rest[..start]);
+        match lookup(name) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[start..start + 3 + len]),
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Remove "." and ".." components without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// `path` relative to the directory `base`, when they share more than the
+/// root directory
+fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    if common <= 1 {
+        return None;
+    }
+
+    let relative: PathBuf = std::iter::repeat_n(Component::ParentDir, base.len() - common)
+        .chain(path[common..].iter().copied())
+        .collect();
+    Some(if relative.as_os_str().is_empty() { PathBuf::from(".") } else { relative })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    /// A project with every field set to something other than its default
+    fn full_project(dir: &Path) -> Project {
+        let mut project = Project::new();
+        project.metadata = ProjectMetadata {
+            name: "Hello".to_string(),
+            app_id: "com.example.Hello".to_string(),
+            version: "1:2.0.0-beta.1".to_string(),
+            author: "Example <dev@example.com>".to_string(),
+            description: "Says hello & waves".to_string(),
+            application_name: "Hello World".to_string(),
+            output_directory: dir.join("dist"),
+            desktop_file: Some(dir.join("data/com.example.Hello.desktop")),
+        };
+        project.files = vec![
+            ProjectFile {
+                source: dir.join("target/release/hello"),
+                destination: "bin/hello".to_string(),
+                permissions: Some("755".to_string()),
+                include: Vec::new(),
+                exclude: Vec::new(),
+            },
+            ProjectFile {
+                source: dir.join("data"),
+                destination: "share/hello".to_string(),
+                permissions: None,
+                include: vec!["**/*.ui".to_string(), "icons/**".to_string()],
+                exclude: vec!["**/*~".to_string()],
+            },
+            ProjectFile {
+                source: PathBuf::from("/usr/lib/libhello.so.1"),
+                destination: "lib/libhello.so.1".to_string(),
+                permissions: Some("644".to_string()),
+                include: Vec::new(),
+                exclude: Vec::new(),
+            },
+        ];
+        project.installer_screens[1].custom_content = Some("LICENSE".to_string());
+        project.installer_screens[3].enabled = true;
+        project.requirements = ProjectRequirements {
+            min_gtk_version: "4.10".to_string(),
+            min_libadwaita_version: "1.5".to_string(),
+            architectures: vec!["x86_64".to_string(), "aarch64".to_string()],
+            libraries: vec!["libssl.so.3".to_string()],
+        };
+        project.relations = ProjectRelations {
+            requires: vec!["com.example.Runtime >= 1.2".to_string()],
+            conflicts: vec!["com.example.OldHello".to_string()],
+            provides: vec!["com.example.Greeter = 2.0".to_string()],
+        };
+        project.hooks = ProjectHooks {
+            pre_install: Some(dir.join("hooks/pre.sh")),
+            post_install: Some(dir.join("hooks/post.sh")),
+            pre_uninstall: None,
+            post_uninstall: Some(dir.join("hooks/post-uninstall.sh")),
+        };
+        project.package_name = "hello-2.0.lis".to_string();
+        project.compression_level = 3;
+        project.variables = vec![ProjectVariable { name: "channel".to_string(), value: "stable".to_string() }];
+        project.profiles = vec![BuildProfile {
+            name: "nightly".to_string(),
+            metadata: ProfileMetadata {
+                app_id: Some("com.example.Hello.Nightly".to_string()),
+                application_name: Some("Hello Nightly".to_string()),
+                ..ProfileMetadata::default()
+            },
+            package_name: Some("hello-${profile}-${version}.lis".to_string()),
+            files: vec![ProjectFile {
+                source: dir.join("target/debug/hello"),
+                destination: "bin/hello".to_string(),
+                permissions: Some("755".to_string()),
+                include: Vec::new(),
+                exclude: Vec::new(),
+            }],
+            removed_files: vec!["lib/libhello.so.1".to_string()],
+            variables: vec![ProjectVariable { name: "channel".to_string(), value: "nightly".to_string() }],
+        }];
+        project
+    }
+
+    #[test]
+    fn round_trip_keeps_every_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.lisproj");
+        let project = full_project(dir.path());
+
+        project.save_to_file(&path).unwrap();
+        let loaded = Project::load_from_file(&path).unwrap();
+        assert_eq!(loaded, project);
+        assert!(!loaded.upgraded);
+
+        // Paths inside the project's tree are saved relative to it
+        let xml = std::fs::read_to_string(&path).unwrap();
+        assert!(xml.starts_with(&format!("<project version=\"{}\">", SCHEMA_VERSION)));
+        assert!(xml.contains("<source>target/release/hello</source>"));
+        assert!(xml.contains("<source>/usr/lib/libhello.so.1</source>"));
+        assert!(xml.contains("<source>target/debug/hello</source>"));
+        assert!(!xml.contains(&dir.path().to_string_lossy().to_string()));
+    }
+
+    #[test]
+    fn project_moves_with_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = full_project(&dir.path().join("checkout-1"));
+        std::fs::create_dir(dir.path().join("checkout-1")).unwrap();
+        project.save_to_file(&dir.path().join("checkout-1/hello.lisproj")).unwrap();
+
+        std::fs::rename(dir.path().join("checkout-1"), dir.path().join("checkout-2")).unwrap();
+        let loaded = Project::load_from_file(&dir.path().join("checkout-2/hello.lisproj")).unwrap();
+        assert_eq!(loaded, full_project(&dir.path().join("checkout-2")));
+    }
+
+    #[test]
+    fn environment_variables_are_saved_as_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.lisproj");
+        std::env::set_var("OBISION_TEST_BUILD_DIR", "/tmp/obision-test-build");
+        write(&path, r#"<project version="2">
+  <files><source>${OBISION_TEST_BUILD_DIR}/hello</source><destination>bin/hello</destination></files>
+</project>"#);
+
+        let project = Project::load_from_file(&path).unwrap();
+        assert_eq!(project.files[0].source, PathBuf::from("/tmp/obision-test-build/hello"));
+
+        project.save_to_file(&path).unwrap();
+        let xml = std::fs::read_to_string(&path).unwrap();
+        assert!(xml.contains("<source>${OBISION_TEST_BUILD_DIR}/hello</source>"));
+    }
+
+    #[test]
+    fn unversioned_file_is_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.lisproj");
+        let desktop_file = dir.path().join("data/com.example.Old.desktop");
+        write(&desktop_file, "[Desktop Entry]\nName=Old\n");
+        // Version 1: no version attribute, absolute paths, and neither
+        // installer_screens nor compression_level
+        write(&path, &format!(r#"<Project>
+  <metadata>
+    <name>Old</name>
+    <version>1.0.0</version>
+    <author>Example</author>
+    <description>An old project</description>
+    <application_name>Old App</application_name>
+    <output_directory>{dir}/dist</output_directory>
+    <desktop_file>{dir}/data/com.example.Old.desktop</desktop_file>
+  </metadata>
+  <files><source>{dir}/bin/old</source><destination>bin/old</destination><permissions>755</permissions></files>
+  <files><source>{dir}/README</source><destination>share/doc/old/README</destination><permissions/></files>
+  <hooks><pre_install/><post_install>{dir}/post.sh</post_install></hooks>
+  <package_name>old.lis</package_name>
+</Project>"#, dir = dir.path().display()));
+
+        let project = Project::load_from_file(&path).unwrap();
+        assert!(project.upgraded);
+        assert_eq!(project.metadata.app_id, "com.example.Old");
+        assert_eq!(project.installer_screens, Project::default_screens());
+        assert_eq!(project.compression_level, 9);
+        assert_eq!(project.files[0].source, dir.path().join("bin/old"));
+        assert_eq!(project.files[1].permissions, None);
+        assert_eq!(project.hooks.pre_install, None);
+        assert_eq!(project.hooks.post_install, Some(dir.path().join("post.sh")));
+        assert_eq!(project.metadata.output_directory, dir.path().join("dist"));
+
+        project.save_to_file(&path).unwrap();
+        let xml = std::fs::read_to_string(&path).unwrap();
+        assert!(xml.starts_with(&format!("<project version=\"{}\">", SCHEMA_VERSION)));
+        assert!(xml.contains("<source>bin/old</source>"));
+        assert!(!Project::load_from_file(&path).unwrap().upgraded);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.lisproj");
+        write(&path, r#"<project version="99"><metadata><name>New</name></metadata></project>"#);
+
+        let error = Project::load_from_file(&path).unwrap_err();
+        assert!(matches!(error, ProjectError::UnsupportedVersion { version: 99 }), "{}", error);
+    }
+
+    #[test]
+    fn invalid_value_points_at_element_and_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.lisproj");
+        write(&path, r#"<project version="2">
+  <metadata>
+    <name>Bad</name>
+  </metadata>
+  <compression_level>high</compression_level>
+</project>"#);
+
+        match Project::load_from_file(&path).unwrap_err() {
+            ProjectError::Invalid { element, line, .. } => {
+                assert_eq!(element, "compression_level");
+                assert_eq!(line, Some(5));
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn missing_element_points_at_its_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.lisproj");
+        write(&path, r#"<project version="2">
+  <files><source>a</source><destination>a</destination></files>
+  <files>
+    <source>b</source>
+  </files>
+</project>"#);
+
+        match Project::load_from_file(&path).unwrap_err() {
+            ProjectError::Invalid { element, line, message } => {
+                assert_eq!(element, "files[1]");
+                assert_eq!(line, Some(3));
+                assert!(message.contains("destination"), "{}", message);
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn malformed_xml_reports_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.lisproj");
+        write(&path, "<project version=\"2\">\n  <metadata>\n    <name>Bad</nome>\n  </metadata>\n</project>");
+
+        match Project::load_from_file(&path).unwrap_err() {
+            ProjectError::Syntax { line, .. } => assert_eq!(line, 3),
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::project::{Project, ProjectError};
+
+/// Recent projects kept besides the pinned ones
+const MAX_RECENT: usize = 10;
+
+const SESSION_FILE: &str = "session.toml";
+const RECOVERY_FILE: &str = "recovery.lisproj";
+
+/// Directory of the builder's session: `$XDG_STATE_HOME/obision-builder`,
+/// or `~/.local/state/obision-builder`
+fn state_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .map(|dir| dir.join("obision-builder"))
+}
+
+/// Seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Outcome of the last build of a project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastBuild {
+    pub succeeded: bool,
+    /// When the build ended, in seconds since the Unix epoch
+    pub time: u64,
+}
+
+/// A project opened in the builder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentProject {
+    pub path: PathBuf,
+    /// Pinned projects are listed first and never dropped from the list
+    #[serde(default)]
+    pub pinned: bool,
+    /// When the project was last opened or saved, in seconds since the Unix epoch
+    pub opened: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_build: Option<LastBuild>,
+}
+
+/// Unsaved changes written to the recovery file, see [`Session::autosave`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recovery {
+    /// Project file the changes belong to; None for a project never saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<PathBuf>,
+    /// When the changes were written, in seconds since the Unix epoch
+    pub time: u64,
+}
+
+/// What the builder remembers between runs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Whether the last project is opened on launch
+    pub reopen_last_project: bool,
+    /// Most recently opened first
+    pub recent: Vec<RecentProject>,
+    /// Unsaved changes left by a builder that didn't close normally
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery: Option<Recovery>,
+}
+
+impl Session {
+    /// Session of the previous runs; an empty one when there is none or it
+    /// can't be read
+    pub fn load() -> Self {
+        state_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join(SESSION_FILE)).ok())
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = state_dir().ok_or("No home directory to save the session in")?;
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+        let content = toml::to_string(self).map_err(|e| format!("Could not serialize the session: {}", e))?;
+        let path = dir.join(SESSION_FILE);
+        std::fs::write(&path, content).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+
+    /// Put a project at the top of the recent projects, keeping its pin and
+    /// last build
+    pub fn opened(&mut self, path: &Path) {
+        let position = self.recent.iter().position(|p| p.path == path);
+        let mut project = match position {
+            Some(position) => self.recent.remove(position),
+            None => RecentProject { path: path.to_path_buf(), pinned: false, opened: 0, last_build: None },
+        };
+        project.opened = now();
+        self.recent.insert(0, project);
+
+        // The oldest projects that aren't pinned go first
+        let mut unpinned = 0;
+        self.recent.retain(|p| {
+            unpinned += usize::from(!p.pinned);
+            p.pinned || unpinned <= MAX_RECENT
+        });
+    }
+
+    pub fn built(&mut self, path: &Path, succeeded: bool) {
+        if let Some(project) = self.recent.iter_mut().find(|p| p.path == path) {
+            project.last_build = Some(LastBuild { succeeded, time: now() });
+        }
+    }
+
+    pub fn set_pinned(&mut self, path: &Path, pinned: bool) {
+        if let Some(project) = self.recent.iter_mut().find(|p| p.path == path) {
+            project.pinned = pinned;
+        }
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.recent.retain(|p| p.path != path);
+    }
+
+    /// Forget the projects whose file was moved or deleted
+    pub fn remove_missing(&mut self) {
+        self.recent.retain(|p| p.path.is_file());
+    }
+
+    /// Recent projects to list: the pinned ones, then the others
+    pub fn projects(&self) -> Vec<&RecentProject> {
+        let pinned = self.recent.iter().filter(|p| p.pinned);
+        pinned.chain(self.recent.iter().filter(|p| !p.pinned)).collect()
+    }
+
+    /// The project opened last, if it still exists
+    pub fn last_project(&self) -> Option<&Path> {
+        self.recent.first().map(|p| p.path.as_path()).filter(|path| path.is_file())
+    }
+
+    /// Write the unsaved changes of a project to the recovery file, so they
+    /// survive a crash
+    pub fn autosave(&mut self, project: &Project, path: Option<&Path>) -> Result<(), String> {
+        let dir = state_dir().ok_or("No home directory to save the changes in")?;
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+        project.save_to_file(&dir.join(RECOVERY_FILE)).map_err(|e| e.to_string())?;
+        self.recovery = Some(Recovery { project: path.map(Path::to_path_buf), time: now() });
+        Ok(())
+    }
+
+    /// Project of the recovery file, with the unsaved changes
+    pub fn recovered_project(&self) -> Option<Result<Project, ProjectError>> {
+        self.recovery.as_ref()?;
+        let path = state_dir()?.join(RECOVERY_FILE);
+        Some(Project::load_from_file(&path))
+    }
+
+    /// Forget the unsaved changes, once they are saved or discarded
+    pub fn discard_recovery(&mut self) {
+        if self.recovery.take().is_some() {
+            if let Some(dir) = state_dir() {
+                let _ = std::fs::remove_file(dir.join(RECOVERY_FILE));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_projects_keep_pins_and_build_status() {
+        let mut session = Session::default();
+        for n in 0..=MAX_RECENT + 2 {
+            session.opened(Path::new(&format!("/projects/{}.lisproj", n)));
+            if n == 0 {
+                session.set_pinned(Path::new("/projects/0.lisproj"), true);
+                session.built(Path::new("/projects/0.lisproj"), true);
+            }
+        }
+
+        // The pinned project stays, the oldest unpinned ones are dropped
+        let projects = session.projects();
+        assert_eq!(projects.len(), MAX_RECENT + 1);
+        assert_eq!(projects[0].path, Path::new("/projects/0.lisproj"));
+        assert!(projects[0].last_build.as_ref().is_some_and(|b| b.succeeded));
+        assert_eq!(projects[1].path, PathBuf::from(format!("/projects/{}.lisproj", MAX_RECENT + 2)));
+        assert!(!session.recent.iter().any(|p| p.path == Path::new("/projects/1.lisproj")));
+
+        // Opening a project again moves it to the top
+        session.opened(Path::new("/projects/5.lisproj"));
+        assert_eq!(session.recent[0].path, Path::new("/projects/5.lisproj"));
+        assert_eq!(session.recent.iter().filter(|p| p.path == Path::new("/projects/5.lisproj")).count(), 1);
+    }
+
+    #[test]
+    fn session_round_trips_through_toml() {
+        let mut session = Session { reopen_last_project: true, ..Session::default() };
+        session.opened(Path::new("/projects/hello.lisproj"));
+        session.built(Path::new("/projects/hello.lisproj"), false);
+        session.recovery = Some(Recovery { project: None, time: 42 });
+
+        let content = toml::to_string(&session).unwrap();
+        assert_eq!(toml::from_str::<Session>(&content).unwrap(), session);
+    }
+
+    #[test]
+    fn unsaved_changes_are_recovered_until_discarded() {
+        let state = tempfile::tempdir().unwrap();
+        std::env::set_var("XDG_STATE_HOME", state.path());
+        let recovery_file = state.path().join("obision-builder").join(RECOVERY_FILE);
+
+        let mut project = Project::new();
+        project.metadata.app_id = "com.example.Hello".to_string();
+        project.metadata.version = "2.0.0".to_string();
+
+        let mut session = Session::default();
+        assert!(session.recovered_project().is_none());
+        session.autosave(&project, Some(Path::new("/projects/hello.lisproj"))).unwrap();
+        session.save().unwrap();
+
+        // The next run finds the changes through the saved session
+        let session = &mut Session::load();
+        let recovery = session.recovery.clone().unwrap();
+        assert_eq!(recovery.project.as_deref(), Some(Path::new("/projects/hello.lisproj")));
+        let recovered = session.recovered_project().unwrap().unwrap();
+        assert_eq!(recovered.metadata.app_id, "com.example.Hello");
+        assert_eq!(recovered.metadata.version, "2.0.0");
+
+        session.discard_recovery();
+        assert!(session.recovery.is_none());
+        assert!(!recovery_file.exists());
+        assert!(session.recovered_project().is_none());
+    }
+}
@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::build;
+use crate::project::{self, Project, ProjectFile};
+
+/// Directories of build output and dependencies, never searched
+const SKIPPED_DIRS: &[&str] = &["target", "builddir", "build", "node_modules", "__pycache__", ".git"];
+
+/// Application ecosystems with a project template, like the apps in `examples/`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    Rust,
+    C,
+    Cpp,
+    Python,
+    Gjs,
+}
+
+impl Template {
+    pub const ALL: [Template; 5] = [Template::Rust, Template::C, Template::Cpp, Template::Python, Template::Gjs];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Template::Rust => "Rust",
+            Template::C => "C",
+            Template::Cpp => "C++",
+            Template::Python => "Python",
+            Template::Gjs => "JavaScript (GJS)",
+        }
+    }
+
+    /// Guess the template of a source directory from its files: a Cargo.toml,
+    /// or else the language of the sources
+    pub fn detect(dir: &Path) -> Option<Template> {
+        if dir.join("Cargo.toml").is_file() {
+            return Some(Template::Rust);
+        }
+
+        let sources = if dir.join("src").is_dir() { dir.join("src") } else { dir.to_path_buf() };
+        let extensions: Vec<String> = source_files(&sources, 4)
+            .filter_map(|path| Some(path.extension()?.to_string_lossy().to_lowercase()))
+            .collect();
+        let has = |wanted: &[&str]| extensions.iter().any(|e| wanted.contains(&e.as_str()));
+
+        if has(&["cpp", "cc", "cxx"]) {
+            Some(Template::Cpp)
+        } else if has(&["c"]) {
+            Some(Template::C)
+        } else if has(&["py"]) {
+            Some(Template::Python)
+        } else if has(&["js"]) {
+            Some(Template::Gjs)
+        } else {
+            None
+        }
+    }
+
+    /// System libraries the application loads at run time
+    fn libraries(self) -> Vec<String> {
+        let mut libraries = vec!["libgtk-4.so.1".to_string(), "libadwaita-1.so.0".to_string()];
+        if self == Template::Gjs {
+            libraries.push("libgjs.so.0".to_string());
+        }
+        libraries
+    }
+
+    /// Entries of the program itself: the binary, or the script and its modules
+    fn program_files(self, dir: &Path, exec: &str) -> Vec<ProjectFile> {
+        let executable = |source: PathBuf| ProjectFile {
+            source,
+            destination: format!("bin/{}", exec),
+            permissions: Some("755".to_string()),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        };
+        // Script modules go to the application's data directory
+        let modules = |extension: &str, name: &str| ProjectFile {
+            source: dir.join("src"),
+            destination: format!("share/${{app_id}}/{}", name),
+            permissions: None,
+            include: vec![format!("**/*.{}", extension)],
+            exclude: vec!["**/__pycache__/**".to_string()],
+        };
+
+        match self {
+            Template::Rust => vec![executable(dir.join("target/release").join(exec))],
+            // Meson builds, as in the examples
+            Template::C | Template::Cpp => vec![executable(dir.join("builddir").join(exec))],
+            Template::Python => vec![executable(dir.join("src/main.py")), modules("py", "python")],
+            Template::Gjs => vec![executable(dir.join("src/main.js")), modules("js", "js")],
+        }
+    }
+}
+
+/// Files below `dir`, leaving out build output
+fn source_files(dir: &Path, max_depth: usize) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !SKIPPED_DIRS.contains(&e.file_name().to_string_lossy().as_ref()))
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+}
+
+/// Desktop file of the application: in data/, or else anywhere near the top
+fn find_desktop_file(dir: &Path) -> Option<PathBuf> {
+    let is_desktop = |path: &PathBuf| path.extension().is_some_and(|e| e == "desktop");
+    source_files(&dir.join("data"), 1).find(is_desktop)
+        .or_else(|| source_files(dir, 3).find(is_desktop))
+}
+
+/// Version declared by Cargo.toml or meson.build
+fn find_version(dir: &Path) -> Option<String> {
+    let quoted = |line: &str, prefix: &str| -> Option<String> {
+        let value = line.trim().strip_prefix(prefix)?.trim();
+        Some(value.trim_matches(|c| c == '"' || c == '\'' || c == ',').to_string())
+    };
+    let cargo = std::fs::read_to_string(dir.join("Cargo.toml")).unwrap_or_default();
+    let meson = std::fs::read_to_string(dir.join("meson.build")).unwrap_or_default();
+    cargo.lines().find_map(|line| quoted(line, "version = "))
+        .or_else(|| meson.lines().find_map(|line| quoted(line, "version:")))
+        .filter(|version| liblis::Version::parse(version).is_ok())
+}
+
+/// A new project for the application in `dir`, with the metadata of its
+/// desktop file and, with a template, the usual files, installer screens and
+/// requirements of its ecosystem
+pub fn new_project(dir: &Path, template: Option<Template>) -> Project {
+    let mut project = Project::new();
+    let name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+    project.metadata.name = name.clone();
+    project.metadata.output_directory = dir.to_path_buf();
+    project.package_name = format!("{}.lis", name.to_lowercase().replace(' ', "-"));
+    if let Some(version) = find_version(dir) {
+        project.metadata.version = version;
+    }
+
+    if let Some(desktop_file) = find_desktop_file(dir) {
+        project.metadata.app_id = project::guess_app_id(&desktop_file).unwrap_or_default();
+        project.metadata.desktop_file = Some(desktop_file);
+        if let Some(application_name) = build::desktop_value(&project, "Name") {
+            project.metadata.application_name = application_name;
+        }
+        project.metadata.description = build::desktop_value(&project, "Comment").unwrap_or_default();
+    }
+
+    let Some(template) = template else {
+        return project;
+    };
+
+    // Destinations in the application's directories follow the app ID through ${app_id}
+    let exec = build::desktop_exec(&project).unwrap_or_else(|| name.to_lowercase().replace(' ', "-"));
+    let entry = |source: PathBuf, destination: String, include: &[&str]| ProjectFile {
+        source,
+        destination,
+        permissions: None,
+        include: include.iter().map(|p| p.to_string()).collect(),
+        exclude: Vec::new(),
+    };
+
+    let mut files = template.program_files(dir, &exec);
+    let data = dir.join("data");
+    if data.join("ui").is_dir() {
+        files.push(entry(data.join("ui"), "share/${app_id}/ui".to_string(), &[]));
+    }
+    // data/icons/<size>/ holds the icons of each size, e.g., scalable
+    let icon_sizes = std::fs::read_dir(data.join("icons")).into_iter().flatten().flatten()
+        .filter(|e| e.path().is_dir());
+    let mut icon_sizes: Vec<PathBuf> = icon_sizes.map(|e| e.path()).collect();
+    icon_sizes.sort();
+    for size_dir in icon_sizes {
+        let size = size_dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+        files.push(entry(size_dir, format!("share/icons/hicolor/{}/apps", size), &["*.svg", "*.png"]));
+    }
+    if source_files(&data, 1).any(|p| p.to_string_lossy().ends_with(".metainfo.xml") || p.to_string_lossy().ends_with(".appdata.xml")) {
+        files.push(entry(data, "share/metainfo".to_string(), &["*.metainfo.xml", "*.appdata.xml"]));
+    }
+
+    let license = ["LICENSE", "LICENSE.md", "LICENSE.txt", "COPYING"].into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file());
+    if let Some(license) = &license {
+        let file_name = license.file_name().unwrap_or_default().to_string_lossy().to_string();
+        files.push(entry(license.clone(), format!("share/licenses/${{app_id}}/{}", file_name), &[]));
+    }
+    project.files = files;
+
+    // The license screen needs a license; the other defaults suit every ecosystem
+    for screen in &mut project.installer_screens {
+        if screen.id == "license" {
+            screen.enabled = license.is_some();
+        }
+    }
+
+    // The toolkit versions the examples are built against
+    project.requirements.min_gtk_version = "4.10".to_string();
+    project.requirements.min_libadwaita_version = "1.5".to_string();
+    project.requirements.libraries = template.libraries();
+
+    project
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, files: &[(&str, &str)]) {
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+    }
+
+    /// A C application laid out like examples/c
+    fn c_app(dir: &Path) {
+        write(dir, &[
+            ("meson.build", "project('demo',\n  'c',\n  version: '0.1.0',\n)\n"),
+            ("src/main.c", "int main(void) { return 0; }\n"),
+            ("builddir/example-c", ""),
+            ("data/com.obision.example.C.desktop", "[Desktop Entry]\nName=C Demo\nComment=A demo in C\nExec=example-c\n"),
+            ("data/com.obision.example.C.metainfo.xml", "<component/>\n"),
+            ("data/ui/window.ui", "<interface/>\n"),
+            ("data/icons/scalable/com.obision.example.C.svg", "<svg/>\n"),
+            ("LICENSE", "MIT\n"),
+        ]);
+    }
+
+    /// A Python application laid out like examples/python, whose desktop
+    /// file isn't named after an app ID
+    fn python_app(dir: &Path) {
+        write(dir, &[
+            ("meson.build", "project('demo',\n  version: '1.2.0',\n)\n"),
+            ("src/main.py", "print('hello')\n"),
+            ("src/window.py", "\n"),
+            ("data/hello.desktop", "[Desktop Entry]\nName=Hello\nExec=hello-py\n"),
+            ("data/icons/48x48/hello.png", ""),
+            ("data/icons/scalable/hello.svg", "<svg/>\n"),
+        ]);
+    }
+
+    fn destinations(project: &Project) -> Vec<&str> {
+        project.files.iter().map(|f| f.destination.as_str()).collect()
+    }
+
+    #[test]
+    fn detects_the_ecosystem_from_the_sources() {
+        let c = tempfile::tempdir().unwrap();
+        c_app(c.path());
+        assert_eq!(Template::detect(c.path()), Some(Template::C));
+
+        let python = tempfile::tempdir().unwrap();
+        python_app(python.path());
+        assert_eq!(Template::detect(python.path()), Some(Template::Python));
+
+        // Cargo.toml wins over the sources, build output is never searched
+        let rust = tempfile::tempdir().unwrap();
+        write(rust.path(), &[("Cargo.toml", "[package]\n"), ("src/main.rs", ""), ("build/gen.c", "")]);
+        assert_eq!(Template::detect(rust.path()), Some(Template::Rust));
+
+        let empty = tempfile::tempdir().unwrap();
+        write(empty.path(), &[("README.md", ""), ("build/main.cpp", "")]);
+        assert_eq!(Template::detect(empty.path()), None);
+    }
+
+    #[test]
+    fn new_c_project_takes_metadata_and_files_from_the_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        c_app(dir.path());
+        let project = new_project(dir.path(), Some(Template::C));
+
+        assert_eq!(project.metadata.app_id, "com.obision.example.C");
+        assert_eq!(project.metadata.version, "0.1.0");
+        assert_eq!(project.metadata.application_name, "C Demo");
+        assert_eq!(project.metadata.description, "A demo in C");
+        assert_eq!(project.files[0].source, dir.path().join("builddir/example-c"));
+        assert_eq!(project.files[0].permissions.as_deref(), Some("755"));
+        assert_eq!(destinations(&project), [
+            "bin/example-c",
+            "share/${app_id}/ui",
+            "share/icons/hicolor/scalable/apps",
+            "share/metainfo",
+            "share/licenses/${app_id}/LICENSE",
+        ]);
+        assert!(project.installer_screens.iter().any(|s| s.id == "license" && s.enabled));
+        assert_eq!(project.requirements.libraries, ["libgtk-4.so.1", "libadwaita-1.so.0"]);
+
+        let resolved = project.resolved(None).unwrap();
+        assert_eq!(resolved.files[1].destination, "share/com.obision.example.C/ui");
+    }
+
+    #[test]
+    fn destinations_follow_an_app_id_set_later() {
+        let dir = tempfile::tempdir().unwrap();
+        python_app(dir.path());
+        let mut project = new_project(dir.path(), Some(Template::Python));
+
+        assert_eq!(project.metadata.app_id, "");
+        assert_eq!(project.metadata.version, "1.2.0");
+        assert_eq!(destinations(&project), [
+            "bin/hello-py",
+            "share/${app_id}/python",
+            "share/icons/hicolor/48x48/apps",
+            "share/icons/hicolor/scalable/apps",
+        ]);
+        assert_eq!(project.files[1].include, ["**/*.py"]);
+        assert!(project.installer_screens.iter().any(|s| s.id == "license" && !s.enabled));
+
+        project.metadata.app_id = "com.example.Hello".to_string();
+        let resolved = project.resolved(None).unwrap();
+        assert_eq!(resolved.files[1].destination, "share/com.example.Hello/python");
+    }
+
+    #[test]
+    fn project_without_template_has_only_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        c_app(dir.path());
+        let project = new_project(dir.path(), None);
+        assert_eq!(project.metadata.app_id, "com.obision.example.C");
+        assert!(project.files.is_empty());
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::build;
+use crate::profiles::BUILT_IN_VARIABLES;
+use crate::project::{Project, ProjectFile, ProjectVariable};
+
+/// How serious an issue is: errors stop the build, warnings don't
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a project
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, message: message.into() }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "ERROR: {}", self.message),
+            Severity::Warning => write!(f, "WARNING: {}", self.message),
+        }
+    }
+}
+
+/// Whether any of the issues stops the build
+pub fn has_errors(issues: &[Issue]) -> bool {
+    issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+/// File names of the destinations, to look up binaries and icons
+fn destination_names(files: &[ProjectFile]) -> Vec<&str> {
+    files.iter()
+        .filter_map(|f| Path::new(&f.destination).file_name()?.to_str())
+        .collect()
+}
+
+/// Destination without "." components, for comparing destinations
+fn normalize(destination: &str) -> PathBuf {
+    Path::new(destination).components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+impl Project {
+    /// Check everything the build and the installer rely on, for the
+    /// project as built with a profile (see [`Project::resolved`]). Errors
+    /// are listed before warnings.
+    pub fn validate(&self, profile: Option<&str>) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        self.validate_profiles(&mut issues);
+        match self.resolved(profile) {
+            Ok(project) => {
+                project.validate_metadata(&mut issues);
+                project.validate_paths(&mut issues);
+                project.validate_variables(&mut issues);
+                let files = project.validate_files(&mut issues);
+                project.validate_desktop(&files, &mut issues);
+                project.validate_screens(&files, &mut issues);
+            }
+            Err(e) => issues.push(Issue::error(e)),
+        }
+        issues.sort_by_key(|i| i.severity == Severity::Warning);
+        issues
+    }
+
+    fn validate_profiles(&self, issues: &mut Vec<Issue>) {
+        let check_variables = |variables: &[ProjectVariable], issues: &mut Vec<Issue>| {
+            for variable in variables {
+                if variable.name.trim().is_empty() {
+                    issues.push(Issue::error("A variable has no name"));
+                } else if BUILT_IN_VARIABLES.contains(&variable.name.as_str()) {
+                    issues.push(Issue::error(format!("Variable '{}' is built in and can't be set", variable.name)));
+                }
+            }
+        };
+        check_variables(&self.variables, issues);
+
+        for (idx, profile) in self.profiles.iter().enumerate() {
+            if profile.name.trim().is_empty() {
+                issues.push(Issue::error("A build profile has no name"));
+            } else if self.profiles[..idx].iter().any(|p| p.name == profile.name) {
+                issues.push(Issue::error(format!("Build profile '{}' is defined more than once", profile.name)));
+            }
+            check_variables(&profile.variables, issues);
+
+            for removed in &profile.removed_files {
+                if !self.files.iter().any(|f| f.destination == *removed) {
+                    issues.push(Issue::warning(format!(
+                        "Build profile '{}' removes '{}', which is not the destination of any file",
+                        profile.name, removed
+                    )));
+                }
+            }
+        }
+    }
+
+    fn validate_metadata(&self, issues: &mut Vec<Issue>) {
+        let metadata = &self.metadata;
+        let required = [
+            ("Project Name", &metadata.name),
+            ("Application Name", &metadata.application_name),
+            ("App ID", &metadata.app_id),
+            ("Version", &metadata.version),
+            ("Author", &metadata.author),
+            ("Description", &metadata.description),
+            ("Package Name", &self.package_name),
+        ];
+        for (field, value) in required {
+            if value.trim().is_empty() {
+                issues.push(Issue::error(format!("{} is required", field)));
+            }
+        }
+
+        if !metadata.app_id.trim().is_empty() {
+            if let Err(e) = liblis::install::validate_app_id(metadata.app_id.trim()) {
+                issues.push(Issue::error(e.to_string()));
+            }
+        }
+        if !metadata.version.trim().is_empty() {
+            if let Err(e) = liblis::Version::parse(metadata.version.trim()) {
+                issues.push(Issue::error(e.to_string()));
+            }
+        }
+    }
+
+    /// Variables left in a path after loading were not set
+    fn validate_paths(&self, issues: &mut Vec<Issue>) {
+        for path in self.paths() {
+            if path.to_string_lossy().contains("${") {
+                issues.push(Issue::error(format!("{} uses an environment variable that is not set", path.display())));
+            }
+        }
+    }
+
+    /// Variables left in the package name or a destination after resolving
+    /// the project are unknown
+    fn validate_variables(&self, issues: &mut Vec<Issue>) {
+        let names = std::iter::once(("Package name", &self.package_name))
+            .chain(self.files.iter().map(|f| ("Destination", &f.destination)));
+        for (field, value) in names {
+            if value.contains("${") {
+                issues.push(Issue::error(format!("{} '{}' uses an unknown variable", field, value)));
+            }
+        }
+    }
+
+    /// Check the file entries and return the files they expand to
+    fn validate_files(&self, issues: &mut Vec<Issue>) -> Vec<ProjectFile> {
+        if self.files.is_empty() {
+            issues.push(Issue::warning("The project has no files"));
+            return Vec::new();
+        }
+
+        let mut files = Vec::new();
+        for entry in &self.files {
+            if let Some(permissions) = &entry.permissions {
+                if let Err(e) = liblis::install::parse_permissions(permissions) {
+                    issues.push(Issue::error(format!("{} of {}", e, entry.destination)));
+                }
+            }
+
+            if !entry.is_pattern() {
+                if !entry.source.is_file() {
+                    issues.push(Issue::error(format!("Source file not found: {}", entry.source.display())));
+                }
+                files.push(entry.clone());
+                continue;
+            }
+            match entry.expand() {
+                Ok(expanded) if expanded.is_empty() => {
+                    issues.push(Issue::warning(format!("{} matches no files", entry.source.display())));
+                }
+                Ok(expanded) => files.extend(expanded),
+                Err(e) => issues.push(Issue::error(e)),
+            }
+        }
+
+        // Normalized destinations; a destination can't be used twice nor be the
+        // directory of another one
+        let mut destinations: BTreeMap<PathBuf, &str> = BTreeMap::new();
+        for file in &files {
+            if !liblis::install::is_safe_relative_path(Path::new(&file.destination)) {
+                issues.push(Issue::error(format!(
+                    "Destination '{}' must be a relative path inside the installation prefix",
+                    file.destination
+                )));
+            } else if destinations.insert(normalize(&file.destination), &file.destination).is_some() {
+                issues.push(Issue::error(format!("Destination '{}' is used by more than one file", file.destination)));
+            }
+        }
+        for (path, destination) in &destinations {
+            for ancestor in path.ancestors().skip(1) {
+                if let Some(other) = destinations.get(ancestor) {
+                    issues.push(Issue::error(format!(
+                        "Destinations '{}' and '{}' overlap: a file can't also be a directory",
+                        other, destination
+                    )));
+                }
+            }
+        }
+
+        let executable = files.iter().any(|f| {
+            f.permissions.as_deref()
+                .and_then(|p| liblis::install::parse_permissions(p).ok())
+                .is_some_and(|mode| mode & 0o111 != 0)
+        });
+        if !executable {
+            issues.push(Issue::warning("No file is executable; set permissions such as 755 on the application binary"));
+        }
+        files
+    }
+
+    fn validate_desktop(&self, files: &[ProjectFile], issues: &mut Vec<Issue>) {
+        match &self.metadata.desktop_file {
+            None => {
+                issues.push(Issue::warning("No desktop file: the application won't appear in the menu"));
+                return;
+            }
+            Some(path) if !path.is_file() => {
+                issues.push(Issue::error(format!("Desktop file not found: {}", path.display())));
+                return;
+            }
+            Some(_) => {}
+        }
+
+        let names = destination_names(files);
+        match build::desktop_exec(self) {
+            Some(exec) if !names.contains(&exec.as_str()) => issues.push(Issue::warning(format!(
+                "The desktop file runs '{}', which is not among the packaged files",
+                exec
+            ))),
+            Some(_) => {}
+            None => issues.push(Issue::warning("The desktop file has no Exec line")),
+        }
+
+        match build::desktop_value(self, "Icon").filter(|icon| !icon.is_empty()) {
+            None => issues.push(Issue::warning("The desktop file has no icon")),
+            Some(icon) if Path::new(&icon).is_absolute() => {
+                if !Path::new(&icon).is_file() {
+                    issues.push(Issue::warning(format!("Icon not found: {}", icon)));
+                }
+            }
+            Some(icon) => {
+                // Icons are looked up by name, without the extension
+                let packaged = names.iter().any(|name| Path::new(name).file_stem().is_some_and(|stem| stem == icon.as_str()));
+                if !packaged {
+                    issues.push(Issue::warning(format!("Icon '{}' is not among the packaged files", icon)));
+                }
+            }
+        }
+    }
+
+    fn validate_screens(&self, files: &[ProjectFile], issues: &mut Vec<Issue>) {
+        let Some(license) = self.installer_screens.iter().find(|s| s.id == "license" && s.enabled) else {
+            return;
+        };
+
+        // Like `lis cat package license`: the file the screen names, or a usual name
+        let has_license = match license.custom_content.as_deref().filter(|c| !c.trim().is_empty()) {
+            Some(content) => files.iter().any(|f| Path::new(&f.destination).ends_with(content)),
+            None => destination_names(files).iter().any(|name| {
+                let name = name.to_uppercase();
+                name.starts_with("LICENSE") || name.starts_with("COPYING")
+            }),
+        };
+        if !has_license {
+            issues.push(Issue::warning(
+                "The License Agreement screen is enabled but the package has no license file",
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::BuildProfile;
+
+    fn file(source: PathBuf, destination: &str, permissions: Option<&str>) -> ProjectFile {
+        ProjectFile {
+            source,
+            destination: destination.to_string(),
+            permissions: permissions.map(str::to_string),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// A project without issues, with its sources in `dir`
+    fn project(dir: &Path) -> Project {
+        for (path, content) in [
+            ("hello", "#!/bin/sh\n"),
+            ("data/com.example.Hello.svg", "<svg/>\n"),
+            ("data/hello.desktop", "[Desktop Entry]\nName=Hello\nExec=hello --new-window\nIcon=com.example.Hello\n"),
+            ("LICENSE", "MIT\n"),
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let mut project = Project::new();
+        project.metadata.name = "Hello".to_string();
+        project.metadata.app_id = "com.example.Hello".to_string();
+        project.metadata.author = "Example".to_string();
+        project.metadata.description = "Says hello".to_string();
+        project.metadata.output_directory = dir.join("dist");
+        project.metadata.desktop_file = Some(dir.join("data/hello.desktop"));
+        project.files = vec![
+            file(dir.join("hello"), "bin/hello", Some("755")),
+            file(dir.join("data/com.example.Hello.svg"), "share/icons/hicolor/scalable/apps/${app_id}.svg", None),
+            file(dir.join("LICENSE"), "share/licenses/${app_id}/LICENSE", None),
+        ];
+        project
+    }
+
+    fn messages(project: &Project) -> Vec<String> {
+        project.validate(None).iter().map(|i| i.to_string()).collect()
+    }
+
+    /// Assert that validating the project gives exactly `expected`
+    fn assert_issues(project: &Project, expected: &[&str]) {
+        assert_eq!(messages(project), expected);
+    }
+
+    #[test]
+    fn valid_project_has_no_issues() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_issues(&project(dir.path()), &[]);
+    }
+
+    #[test]
+    fn metadata_is_required_and_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.metadata.author = " ".to_string();
+        project.metadata.version = "one".to_string();
+        project.metadata.app_id = "hello".to_string();
+        assert_issues(&project, &[
+            "ERROR: Author is required",
+            "ERROR: App ID 'hello' must be a reverse-DNS name such as com.example.App",
+            "ERROR: Invalid version 'one': it must start with numbers separated by dots (e.g., 1.2.0)",
+            // The icon is installed as ${app_id}.svg
+            "WARNING: Icon 'com.example.Hello' is not among the packaged files",
+        ]);
+    }
+
+    #[test]
+    fn destinations_must_not_overlap() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.files.push(file(dir.path().join("LICENSE"), "./bin/hello", None));
+        project.files.push(file(dir.path().join("LICENSE"), "bin/hello/LICENSE", None));
+        assert_issues(&project, &[
+            "ERROR: Destination './bin/hello' is used by more than one file",
+            "ERROR: Destinations './bin/hello' and 'bin/hello/LICENSE' overlap: a file can't also be a directory",
+        ]);
+    }
+
+    #[test]
+    fn destinations_stay_inside_the_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.files.push(file(dir.path().join("LICENSE"), "../etc/hello", None));
+        project.files.push(file(dir.path().join("LICENSE"), "/etc/hello", None));
+        assert_issues(&project, &[
+            "ERROR: Destination '../etc/hello' must be a relative path inside the installation prefix",
+            "ERROR: Destination '/etc/hello' must be a relative path inside the installation prefix",
+        ]);
+    }
+
+    #[test]
+    fn permissions_must_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.files[0].permissions = Some("rwx".to_string());
+        project.files[2].permissions = Some("4755".to_string());
+
+        let messages = messages(&project);
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].starts_with("ERROR: ") && messages[0].ends_with(" of bin/hello"));
+        assert!(messages[1].starts_with("ERROR: ") && messages[1].ends_with(" of share/licenses/com.example.Hello/LICENSE"));
+        // Neither file counts as executable
+        assert!(messages[2].starts_with("WARNING: No file is executable"));
+    }
+
+    #[test]
+    fn sources_must_exist_and_patterns_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.files.push(file(dir.path().join("NEWS"), "share/doc/hello/NEWS", None));
+        project.files.push(file(dir.path().join("data/*.png"), "share/hello", None));
+        assert_issues(&project, &[
+            &format!("ERROR: Source file not found: {}", dir.path().join("NEWS").display()),
+            &format!("WARNING: {} matches no files", dir.path().join("data/*.png").display()),
+        ]);
+
+        project.files.clear();
+        assert_issues(&project, &[
+            "WARNING: The project has no files",
+            "WARNING: The desktop file runs 'hello', which is not among the packaged files",
+            "WARNING: Icon 'com.example.Hello' is not among the packaged files",
+            "WARNING: The License Agreement screen is enabled but the package has no license file",
+        ]);
+    }
+
+    #[test]
+    fn variables_must_be_known() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.package_name = "hello-${channel}.lis".to_string();
+        project.variables = vec![ProjectVariable { name: "version".to_string(), value: "1".to_string() }];
+        assert_issues(&project, &[
+            "ERROR: Variable 'version' is built in and can't be set",
+            "ERROR: Package name 'hello-${channel}.lis' uses an unknown variable",
+        ]);
+    }
+
+    #[test]
+    fn profiles_need_unique_names_and_known_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        let profile = BuildProfile {
+            name: "nightly".to_string(),
+            removed_files: vec!["share/doc/README".to_string()],
+            ..BuildProfile::default()
+        };
+        project.profiles = vec![profile.clone(), profile];
+        assert_issues(&project, &[
+            "ERROR: Build profile 'nightly' is defined more than once",
+            "WARNING: Build profile 'nightly' removes 'share/doc/README', which is not the destination of any file",
+            "WARNING: Build profile 'nightly' removes 'share/doc/README', which is not the destination of any file",
+        ]);
+        assert!(!project.validate(Some("beta")).is_empty());
+    }
+
+    #[test]
+    fn desktop_exec_must_be_packaged() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.files[0].destination = "bin/hello-bin".to_string();
+        assert_issues(&project, &["WARNING: The desktop file runs 'hello', which is not among the packaged files"]);
+
+        project.metadata.desktop_file = Some(dir.path().join("missing.desktop"));
+        assert_issues(&project, &[&format!("ERROR: Desktop file not found: {}", dir.path().join("missing.desktop").display())]);
+
+        project.metadata.desktop_file = None;
+        assert_issues(&project, &["WARNING: No desktop file: the application won't appear in the menu"]);
+    }
+
+    #[test]
+    fn icon_is_looked_up_by_name_without_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        // A PNG of the same name is as good as the SVG
+        project.files[1].destination = "share/icons/hicolor/48x48/apps/com.example.Hello.png".to_string();
+        assert_issues(&project, &[]);
+
+        project.files[1].destination = "share/icons/hicolor/48x48/apps/hello.png".to_string();
+        assert_issues(&project, &["WARNING: Icon 'com.example.Hello' is not among the packaged files"]);
+
+        std::fs::write(dir.path().join("data/hello.desktop"), "[Desktop Entry]\nExec=hello\n").unwrap();
+        assert_issues(&project, &["WARNING: The desktop file has no icon"]);
+    }
+
+    #[test]
+    fn license_screen_needs_a_license_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.files[2].destination = "share/doc/hello/TERMS.txt".to_string();
+        assert_issues(&project, &["WARNING: The License Agreement screen is enabled but the package has no license file"]);
+
+        // The file the screen names, matched by the end of the destination
+        let license = project.installer_screens.iter_mut().find(|s| s.id == "license").unwrap();
+        license.custom_content = Some("hello/TERMS.txt".to_string());
+        assert_issues(&project, &[]);
+
+        let license = project.installer_screens.iter_mut().find(|s| s.id == "license").unwrap();
+        license.custom_content = None;
+        license.enabled = false;
+        assert_issues(&project, &[]);
+    }
+
+    #[test]
+    fn errors_come_before_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut project = project(dir.path());
+        project.metadata.desktop_file = None;
+        project.package_name = String::new();
+
+        let issues = project.validate(None);
+        assert!(has_errors(&issues));
+        assert_eq!(issues.iter().map(|i| i.severity).collect::<Vec<_>>(), [Severity::Error, Severity::Warning]);
+
+        project.package_name = "hello.lis".to_string();
+        assert!(!has_errors(&project.validate(None)));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, BufRead, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+
+use liblis::install::{self, ConflictResolution, InstallProgress};
+
+/// Privileged helper for system-wide installations.
+///
+/// It is started through pkexec (see com.obision.appinstall.policy) by the
+/// installer, which keeps running as the desktop user. Nothing the GUI has
+/// checked is trusted: the package is copied to a private directory and
+/// validated again before any file is written.
+///
+/// Usage:
+///   obision-appinstall-helper install [--prefix <dir>] [--on-conflict abort|overwrite|skip] <package.lis>
+///   obision-appinstall-helper uninstall <app_id>
+///
+/// Progress is reported on stdout as `PROGRESS <bytes_done> <bytes_total> <message>`
+/// lines. Writing `cancel` to stdin rolls the installation back.
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("install") => run_install(&args[1..]),
+        Some("uninstall") => run_uninstall(&args[1..]),
+        _ => Err(anyhow::anyhow!(
+            "Usage: obision-appinstall-helper install [--prefix <dir>] [--on-conflict abort|overwrite|skip] <package.lis>\n       obision-appinstall-helper uninstall <app_id>"
+        )),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ERROR: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn ensure_root() -> Result<()> {
+    if unsafe { libc::geteuid() } != 0 {
+        anyhow::bail!("The helper must be run as root (through pkexec)");
+    }
+    Ok(())
+}
+
+fn report_progress(progress: InstallProgress) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "PROGRESS {} {} {}", progress.bytes_done, progress.bytes_total, progress.message);
+    let _ = stdout.flush();
+}
+
+/// Set the returned flag when the installer writes `cancel` to stdin
+fn watch_for_cancel() -> Arc<AtomicBool> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if line.trim() == "cancel" {
+                flag.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+    });
+    cancel
+}
+
+/// Copy the package somewhere the calling user can no longer modify it
+fn copy_to_private_dir(package: &Path) -> Result<(PathBuf, PathBuf)> {
+    let private_dir = std::env::temp_dir().join(format!("obision-helper-{}", std::process::id()));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .with_context(|| format!("Failed to create {}", private_dir.display()))?;
+
+    let private_copy = private_dir.join("package.lis");
+    let mut src = File::open(package)
+        .with_context(|| format!("Failed to open {}", package.display()))?;
+    let mut dst = File::create(&private_copy)?;
+    io::copy(&mut src, &mut dst).context("Failed to copy package")?;
+
+    Ok((private_dir, private_copy))
+}
+
+fn run_install(args: &[String]) -> Result<()> {
+    ensure_root()?;
+
+    let mut prefix = None;
+    let mut on_conflict = ConflictResolution::Abort;
+    let mut package = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--prefix" => prefix = Some(iter.next().context("--prefix needs a value")?.clone()),
+            "--on-conflict" => {
+                let value = iter.next().context("--on-conflict needs a value")?;
+                on_conflict = ConflictResolution::from_name(value)
+                    .with_context(|| format!("Invalid --on-conflict value '{}'", value))?;
+            }
+            _ if package.is_none() => package = Some(PathBuf::from(arg)),
+            _ => anyhow::bail!("Unexpected argument '{}'", arg),
+        }
+    }
+    let package = package.context("No package given")?;
+
+    let (private_dir, private_copy) = copy_to_private_dir(&package)?;
+
+    let result = (|| {
+        let metadata = install::validate_package(&private_copy)?;
+        let prefix = prefix.unwrap_or(metadata.installation.prefix_system);
+        if !install::is_safe_relative_path(Path::new(prefix.trim_start_matches('/'))) || !prefix.starts_with('/') {
+            anyhow::bail!("Invalid installation prefix '{}'", prefix);
+        }
+
+        let cancel = watch_for_cancel();
+        install::perform_installation(&private_copy, &prefix, true, on_conflict, &cancel, report_progress)?;
+        Ok(())
+    })();
+
+    let _ = fs::remove_dir_all(&private_dir);
+    result
+}
+
+fn run_uninstall(args: &[String]) -> Result<()> {
+    ensure_root()?;
+
+    let app_id = args.first().context("No app_id given")?;
+    if !install::is_safe_app_id(app_id) {
+        anyhow::bail!("Invalid app_id '{}'", app_id);
+    }
+
+    report_progress(InstallProgress { bytes_done: 0, bytes_total: 1, message: format!("Removing {}...", app_id) });
+    install::uninstall(app_id, true)?;
+    report_progress(InstallProgress { bytes_done: 1, bytes_total: 1, message: "Uninstallation complete!".to_string() });
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use libadwaita as adw;
+use liblis::format::format_size;
+use liblis::install::{self, FileConflict, InstallKind};
+use liblis::relations::{self, PackageRelations};
+use liblis::requirements::{self, RequirementCheck, RuntimeVersions};
+
+use crate::disk;
+
+/// Versions of GTK and libadwaita this installer is running with
+fn runtime_versions() -> RuntimeVersions {
+    RuntimeVersions {
+        gtk: Some(format!("{}.{}.{}", gtk4::major_version(), gtk4::minor_version(), gtk4::micro_version())),
+        libadwaita: Some(format!("{}.{}.{}", adw::major_version(), adw::minor_version(), adw::micro_version())),
+    }
+}
+
+/// Check the chosen prefix and the package requirements before installing
+pub fn run_checks(
+    metadata: &liblis::Metadata,
+    prefix: &str,
+    required_space: u64,
+    for_all_users: bool,
+    runtime: &RuntimeVersions,
+) -> Vec<RequirementCheck> {
+    let prefix_path = Path::new(prefix);
+    let disk_check = match disk::validate_install_dir(prefix_path, required_space, !for_all_users) {
+        Ok(()) => {
+            let free = disk::nearest_existing_ancestor(prefix_path).and_then(|dir| disk::free_space(&dir));
+            RequirementCheck {
+                title: format!("Disk space in {}", prefix),
+                detail: match free {
+                    Some(free) => format!("{} required, {} available", format_size(required_space), format_size(free)),
+                    None => format!("{} required", format_size(required_space)),
+                },
+                passed: true,
+            }
+        }
+        Err(e) => RequirementCheck {
+            title: format!("Disk space in {}", prefix),
+            detail: e,
+            passed: false,
+        },
+    };
+
+    let mut checks = vec![disk_check];
+    let version = &metadata.package.version;
+    match install::install_kind(metadata, for_all_users) {
+        InstallKind::New => {}
+        InstallKind::Upgrade { from } => checks.push(RequirementCheck {
+            title: format!("Upgrade from version {}", from),
+            detail: format!("Version {} will replace the installed version", version),
+            passed: true,
+        }),
+        InstallKind::Reinstall => checks.push(RequirementCheck {
+            title: format!("Version {} is already installed", version),
+            detail: "It will be installed again".to_string(),
+            passed: true,
+        }),
+        InstallKind::Downgrade { from } => checks.push(RequirementCheck {
+            title: format!("Downgrade from version {}", from),
+            detail: format!("The installed version is newer; the older version {} will replace it", version),
+            passed: true,
+        }),
+    }
+    if let Some(delta) = &metadata.delta {
+        let base = install::check_delta_base(metadata, prefix, for_all_users);
+        checks.push(RequirementCheck {
+            title: format!("Update for version {}", delta.base_version),
+            detail: match &base {
+                Ok(_) => format!("Unchanged files ({}) are kept from the installed version", delta.unchanged.len()),
+                Err(e) => e.to_string(),
+            },
+            passed: base.is_ok(),
+        });
+    }
+    if metadata.package.architecture.is_some() || metadata.package.libc.is_some() {
+        let built_for = [&metadata.package.architecture, &metadata.package.libc]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        let compatible = liblis::platform::check_compatible(&metadata.package);
+        checks.push(RequirementCheck {
+            title: format!("Built for {}", built_for),
+            detail: match &compatible {
+                Ok(()) => "Compatible with this system".to_string(),
+                Err(e) => e.to_string(),
+            },
+            passed: compatible.is_ok(),
+        });
+    }
+    checks.extend(requirements::check_requirements(&metadata.requirements, runtime));
+    checks
+}
+
+/// Outcome of resolving the package relations
+pub struct RelationChecks {
+    pub checks: Vec<RequirementCheck>,
+    /// Required packages to install first, in order
+    pub packages: Vec<(PathBuf, liblis::Metadata)>,
+    /// Some requirements are not installed and were not found in `packages_dir`
+    pub missing: bool,
+}
+
+/// Resolve the packages the package requires or conflicts with against the
+/// installed ones, taking missing requirements from `packages_dir` if given
+pub fn check_relations(metadata: &liblis::Metadata, packages_dir: Option<&Path>) -> RelationChecks {
+    let mut result = RelationChecks { checks: Vec::new(), packages: Vec::new(), missing: false };
+
+    let package = match PackageRelations::from_metadata(metadata) {
+        Ok(package) => package,
+        Err(e) => {
+            result.checks.push(RequirementCheck {
+                title: "Package relations".to_string(),
+                detail: format!("{:#}", e),
+                passed: false,
+            });
+            return result;
+        }
+    };
+
+    let available = match packages_dir.map(relations::scan_directory) {
+        Some(Ok(available)) => available,
+        Some(Err(e)) => {
+            result.checks.push(RequirementCheck {
+                title: "Required packages folder".to_string(),
+                detail: format!("{:#}", e),
+                passed: false,
+            });
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+
+    let installed = relations::installed_packages();
+    let resolution = relations::resolve(&package, &installed, &available);
+
+    for relation in &package.requires {
+        if installed.iter().any(|p| p.app_id != package.app_id && p.satisfies(relation)) {
+            result.checks.push(RequirementCheck {
+                title: format!("Requires {}", relation),
+                detail: "Installed".to_string(),
+                passed: true,
+            });
+        }
+    }
+    for available in &resolution.packages {
+        result.checks.push(RequirementCheck {
+            title: format!("Will install {} {}", available.metadata.package.application_name, available.metadata.package.version),
+            detail: available.path.to_string_lossy().to_string(),
+            passed: true,
+        });
+        result.packages.push((available.path.clone(), available.metadata.clone()));
+    }
+    for problem in &resolution.problems {
+        result.checks.push(RequirementCheck {
+            title: problem.to_string(),
+            detail: if problem.is_missing_requirement() {
+                "Choose a folder containing the required .lis packages".to_string()
+            } else {
+                "Uninstall it before installing this package".to_string()
+            },
+            passed: false,
+        });
+        result.missing |= problem.is_missing_requirement();
+    }
+
+    result
+}
+
+/// Everything the checks screen shows
+pub struct ChecksOutcome {
+    pub checks: Vec<RequirementCheck>,
+    pub conflicts: Vec<FileConflict>,
+    /// Required packages to install first, in order
+    pub required_packages: Vec<(PathBuf, liblis::Metadata)>,
+    /// Some requirements are not installed and were not found in `packages_dir`
+    pub missing_packages: bool,
+}
+
+/// Run the disk space, requirement, package relation and file conflict
+/// checks on a worker thread: finding conflicts reads the whole package and
+/// the relations scan the packages folder, which can take a while
+pub fn spawn_checks(
+    metadata: liblis::Metadata,
+    lis_file: Option<PathBuf>,
+    prefix: String,
+    required_space: u64,
+    for_all_users: bool,
+    packages_dir: Option<PathBuf>,
+) -> mpsc::Receiver<ChecksOutcome> {
+    // libadwaita only tells its version on the main thread
+    let runtime = runtime_versions();
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut checks = run_checks(&metadata, &prefix, required_space, for_all_users, &runtime);
+
+        let relations = check_relations(&metadata, packages_dir.as_deref());
+        checks.extend(relations.checks);
+
+        let conflicts = match lis_file.map(|lis| install::find_conflicts(&lis, &prefix)) {
+            Some(Ok(conflicts)) => conflicts,
+            Some(Err(e)) => {
+                checks.push(RequirementCheck {
+                    title: "Installed files".to_string(),
+                    detail: format!("{:#}", e),
+                    passed: false,
+                });
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        let _ = sender.send(ChecksOutcome {
+            checks,
+            conflicts,
+            required_packages: relations.packages,
+            missing_packages: relations.missing,
+        });
+    });
+
+    receiver
+}
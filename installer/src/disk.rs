@@ -0,0 +1,63 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use liblis::format::format_size;
+
+/// Walk up from `path` until an existing directory is found.
+/// The installer creates missing directories, so checks are done on that ancestor.
+pub fn nearest_existing_ancestor(path: &Path) -> Option<PathBuf> {
+    let mut current = Some(path);
+    while let Some(dir) = current {
+        if dir.is_dir() {
+            return Some(dir.to_path_buf());
+        }
+        current = dir.parent();
+    }
+    None
+}
+
+/// Check whether the current user can write to the given directory
+pub fn is_writable(dir: &Path) -> bool {
+    let Ok(c_path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// Free space (in bytes) available to unprivileged users on the filesystem containing `dir`
+pub fn free_space(dir: &Path) -> Option<u64> {
+    let c_path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Validate a chosen installation prefix.
+/// System-wide installations run through the privileged helper, so writability is not required.
+pub fn validate_install_dir(prefix: &Path, required_bytes: u64, check_writable: bool) -> Result<(), String> {
+    if !prefix.is_absolute() {
+        return Err(format!("{} is not an absolute path", prefix.display()));
+    }
+
+    let existing = nearest_existing_ancestor(prefix)
+        .ok_or_else(|| format!("{} is not a valid location", prefix.display()))?;
+
+    if check_writable && !is_writable(&existing) {
+        return Err(format!("{} is not writable", existing.display()));
+    }
+
+    if let Some(free) = free_space(&existing) {
+        if free < required_bytes {
+            return Err(format!(
+                "Not enough free space: {} required, {} available",
+                format_size(required_bytes),
+                format_size(free)
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -5,16 +5,46 @@ use adw::prelude::*;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::fs;
-use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+mod checks;
+mod disk;
+mod runner;
+
+use liblis::format::format_size;
+use liblis::install::ConflictResolution;
+use runner::InstallMessage;
 
 const APP_ID: &str = "com.obision.appinstall.Installer";
 
+/// Installation directory chosen on the directory screen
+#[derive(Debug, Clone, PartialEq)]
+enum InstallDir {
+    /// prefix_user or prefix_system, depending on install_for_all_users
+    Default,
+    /// /opt/<app_id>
+    Opt,
+    /// Arbitrary prefix picked by the user
+    Custom(PathBuf),
+}
+
 struct AppState {
     lis_file: Option<PathBuf>,
     metadata: Option<liblis::Metadata>,
     current_screen: usize,
     install_for_all_users: bool,  // false = actual user, true = for all users
+    install_dir: InstallDir,
+    required_space: u64,  // Installed size of the package in bytes
+    install_cancel: Option<Arc<AtomicBool>>,  // Set while the installation is running
+    installed: bool,
+    checks_passed: bool,  // Result of the last system check
+    has_conflicts: bool,  // The package would overwrite files it doesn't own
+    on_conflict: ConflictResolution,
+    packages_dir: Option<PathBuf>,  // Folder to take missing required packages from
+    required_packages: Vec<(PathBuf, liblis::Metadata)>,  // Installed before the package, in order
+    checks_run: u64,  // Number of the latest system check; older results are dropped
 }
 
 impl AppState {
@@ -24,10 +54,48 @@ impl AppState {
             metadata: None,
             current_screen: 0,
             install_for_all_users: false,  // Default to actual user
+            install_dir: InstallDir::Default,
+            required_space: 0,
+            install_cancel: None,
+            installed: false,
+            checks_passed: false,
+            has_conflicts: false,
+            on_conflict: ConflictResolution::Abort,
+            packages_dir: None,
+            required_packages: Vec::new(),
+            checks_run: 0,
+        }
+    }
+
+    /// Whether the checks screen lets the installation go on
+    fn can_install(&self) -> bool {
+        self.checks_passed && (!self.has_conflicts || self.on_conflict != ConflictResolution::Abort)
+    }
+
+    /// Resolve the installation prefix from the current choices
+    fn install_prefix(&self, metadata: &liblis::Metadata) -> String {
+        match &self.install_dir {
+            InstallDir::Default => default_prefix(metadata, self.install_for_all_users),
+            InstallDir::Opt => opt_prefix(metadata),
+            InstallDir::Custom(path) => path.to_string_lossy().to_string(),
         }
     }
 }
 
+/// Default prefix declared in the package for the user or system installation
+fn default_prefix(metadata: &liblis::Metadata, for_all_users: bool) -> String {
+    if for_all_users {
+        liblis::install::paths::expand_home(&metadata.installation.prefix_system)
+    } else {
+        liblis::install::paths::expand_home(&metadata.installation.prefix_user)
+    }
+}
+
+/// Self-contained prefix under /opt for the application
+fn opt_prefix(metadata: &liblis::Metadata) -> String {
+    format!("/opt/{}", metadata.package.app_id)
+}
+
 fn main() -> glib::ExitCode {
     adw::init().expect("Failed to initialize libadwaita");
 
@@ -74,179 +142,6 @@ fn start_installer_with_file(app: &Application, path: PathBuf) {
     show_wizard(app, app_state);
 }
 
-/// Helper functions to build FHS-compliant installation paths
-mod install_paths {
-    use std::path::PathBuf;
-    
-    /// Get the bin directory for the given prefix
-    pub fn bin_dir(prefix: &str) -> PathBuf {
-        PathBuf::from(prefix).join("bin")
-    }
-    
-    /// Get the share directory for the given prefix
-    pub fn share_dir(prefix: &str) -> PathBuf {
-        PathBuf::from(prefix).join("share")
-    }
-    
-    /// Get the applications directory (for .desktop files)
-    pub fn applications_dir(prefix: &str) -> PathBuf {
-        share_dir(prefix).join("applications")
-    }
-    
-    /// Get the app-specific data directory
-    pub fn app_data_dir(prefix: &str, app_id: &str) -> PathBuf {
-        share_dir(prefix).join(app_id)
-    }
-    
-    /// Get the icons directory
-    pub fn icons_dir(prefix: &str) -> PathBuf {
-        share_dir(prefix).join("icons").join("hicolor")
-    }
-    
-    /// Get the metainfo directory
-    pub fn metainfo_dir(prefix: &str) -> PathBuf {
-        share_dir(prefix).join("metainfo")
-    }
-    
-    /// Expand ~ to home directory
-    pub fn expand_home(path: &str) -> String {
-        if path.starts_with("~/") {
-            if let Some(home) = std::env::var_os("HOME") {
-                return home.to_string_lossy().to_string() + &path[1..];
-            }
-        }
-        path.to_string()
-    }
-}
-
-/// Installation manifest for uninstallation
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct InstallationManifest {
-    /// Application ID
-    app_id: String,
-    /// Application name
-    app_name: String,
-    /// Version
-    version: String,
-    /// Installation prefix used
-    prefix: String,
-    /// Timestamp of installation
-    installed_at: String,
-    /// List of all installed files (absolute paths)
-    installed_files: Vec<String>,
-    /// List of all created directories (absolute paths)
-    created_directories: Vec<String>,
-}
-
-impl InstallationManifest {
-    fn new(metadata: &liblis::Metadata, prefix: String) -> Self {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        
-        Self {
-            app_id: metadata.package.app_id.clone(),
-            app_name: metadata.package.application_name.clone(),
-            version: metadata.package.version.clone(),
-            prefix,
-            installed_at: format!("{}", now),
-            installed_files: Vec::new(),
-            created_directories: Vec::new(),
-        }
-    }
-    
-    /// Get the registry directory for installation manifests
-    fn registry_dir(for_all_users: bool) -> PathBuf {
-        if for_all_users {
-            // System-wide registry
-            PathBuf::from("/var/lib/obision-installer/manifests")
-        } else {
-            // User registry
-            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-            PathBuf::from(home).join(".local/share/obision-installer/manifests")
-        }
-    }
-    
-    /// Get the manifest file path for this app
-    fn manifest_path(&self, for_all_users: bool) -> PathBuf {
-        Self::registry_dir(for_all_users).join(format!("{}.json", self.app_id))
-    }
-    
-    /// Save the manifest to disk
-    fn save(&self, for_all_users: bool) -> Result<(), String> {
-        let manifest_path = self.manifest_path(for_all_users);
-        
-        // Create registry directory if it doesn't exist
-        if let Some(parent) = manifest_path.parent() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create manifest directory: {}", e))?;
-        }
-        
-        // Serialize to JSON
-        let json = serde_json::to_string_pretty(self)
-            .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
-        
-        // Write to file
-        fs::write(&manifest_path, json)
-            .map_err(|e| format!("Failed to write manifest: {}", e))?;
-        
-        Ok(())
-    }
-}
-
-/// Perform the actual installation
-fn perform_installation(
-    metadata: &liblis::Metadata,
-    lis_file: &PathBuf,
-    for_all_users: bool,
-    progress_callback: impl Fn(f64, &str),
-) -> Result<(), String> {
-    // Determine installation prefix
-    let prefix = if for_all_users {
-        install_paths::expand_home(&metadata.installation.prefix_system)
-    } else {
-        install_paths::expand_home(&metadata.installation.prefix_user)
-    };
-    
-    progress_callback(0.1, &format!("Installing to {}...", prefix));
-    
-    // TODO: Extract .lis file (tar.gz) to temporary directory
-    // For now, we'll create the directory structure
-    
-    // Create necessary directories
-    let bin_dir = install_paths::bin_dir(&prefix);
-    let share_dir = install_paths::share_dir(&prefix);
-    let app_data_dir = install_paths::app_data_dir(&prefix, &metadata.package.app_id);
-    let applications_dir = install_paths::applications_dir(&prefix);
-    let icons_dir = install_paths::icons_dir(&prefix);
-    
-    progress_callback(0.2, "Creating directories...");
-    
-    // Create directories
-    fs::create_dir_all(&bin_dir).map_err(|e| format!("Failed to create bin directory: {}", e))?;
-    fs::create_dir_all(&share_dir).map_err(|e| format!("Failed to create share directory: {}", e))?;
-    fs::create_dir_all(&app_data_dir).map_err(|e| format!("Failed to create app data directory: {}", e))?;
-    fs::create_dir_all(&applications_dir).map_err(|e| format!("Failed to create applications directory: {}", e))?;
-    fs::create_dir_all(&icons_dir).map_err(|e| format!("Failed to create icons directory: {}", e))?;
-    
-    progress_callback(0.5, "Extracting files...");
-    
-    // TODO: Extract files from .lis archive
-    // This would involve:
-    // 1. Opening the .lis file (tar.gz)
-    // 2. Extracting to temp directory
-    // 3. Copying files to their destinations based on metadata.files
-    
-    progress_callback(0.8, "Setting permissions...");
-    
-    // TODO: Set executable permissions on binaries
-    
-    progress_callback(1.0, "Installation complete!");
-    
-    Ok(())
-}
-
 fn show_file_selector(app: &Application, app_state: Rc<RefCell<AppState>>) {
     let window = adw::ApplicationWindow::builder()
         .application(app)
@@ -327,15 +222,40 @@ fn show_file_selector(app: &Application, app_state: Rc<RefCell<AppState>>) {
 
 fn show_wizard(app: &Application, app_state: Rc<RefCell<AppState>>) {
     // Extract and load metadata from .lis file
-    let _lis_path = match &app_state.borrow().lis_file {
+    let lis_path = match &app_state.borrow().lis_file {
         Some(path) => path.clone(),
         None => return,
     };
     
-    // TODO: Extract metadata.toml from .lis and parse it
-    // For now, create a dummy metadata for testing
-    let metadata = create_dummy_metadata();
-    app_state.borrow_mut().metadata = Some(metadata.clone());
+    let metadata = match liblis::LisPackage::read_metadata(&lis_path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            let alert = gtk4::AlertDialog::builder()
+                .message("Invalid Package")
+                .detail(format!("Could not read {}: {}", lis_path.display(), e))
+                .build();
+            alert.show(None::<&gtk4::Window>);
+            return;
+        }
+    };
+    
+    {
+        let mut state = app_state.borrow_mut();
+        state.metadata = Some(metadata.clone());
+        state.required_space = liblis::LisPackage::installed_size(&lis_path).unwrap_or(0);
+        
+        // Upgrades default to the location of the previous installation
+        if let Some((manifest, for_all_users)) = liblis::InstallationManifest::find_installed(&metadata.package.app_id) {
+            state.install_for_all_users = for_all_users;
+            state.install_dir = if manifest.prefix == default_prefix(&metadata, for_all_users) {
+                InstallDir::Default
+            } else if manifest.prefix == opt_prefix(&metadata) {
+                InstallDir::Opt
+            } else {
+                InstallDir::Custom(PathBuf::from(manifest.prefix))
+            };
+        }
+    }
     
     let window = adw::ApplicationWindow::builder()
         .application(app)
@@ -366,22 +286,57 @@ fn show_wizard(app: &Application, app_state: Rc<RefCell<AppState>>) {
     
     // Always insert progress screen before finish (if not already present)
     if !screens.iter().any(|s| s.id == "progress") {
-        // Find the finish screen index
-        if let Some(finish_idx) = screens.iter().position(|s| s.id == "finish") {
-            screens.insert(finish_idx, liblis::metadata::InstallerScreen {
-                id: "progress".to_string(),
-                enabled: true,
-                order: screens[finish_idx].order,
-                custom_content: None,
-            });
-        }
+        // Before the finish screen, or last when there is none
+        let progress_idx = screens.iter().position(|s| s.id == "finish").unwrap_or(screens.len());
+        screens.insert(progress_idx, liblis::metadata::InstallerScreen {
+            id: "progress".to_string(),
+            enabled: true,
+            order: screens.get(progress_idx).map(|s| s.order).unwrap_or(usize::MAX),
+            custom_content: None,
+        });
+    }
+
+    // Disk space and requirements are always checked before installing
+    {
+        let progress_idx = screens.iter().position(|s| s.id == "progress").unwrap_or(screens.len());
+        screens.insert(progress_idx, liblis::metadata::InstallerScreen {
+            id: "checks".to_string(),
+            enabled: true,
+            order: screens[progress_idx].order,
+            custom_content: None,
+        });
+    }
+
+    // Packages with hook scripts always warn about them right before installing
+    if !metadata.hooks.is_empty() {
+        let progress_idx = screens.iter().position(|s| s.id == "progress").unwrap_or(screens.len());
+        screens.insert(progress_idx, liblis::metadata::InstallerScreen {
+            id: "hooks".to_string(),
+            enabled: true,
+            order: screens[progress_idx].order,
+            custom_content: None,
+        });
     }
 
     // Create screens
+    let mut progress_screen = None;
+    let mut checks_screen = None;
     for screen in &screens {
-        let screen_widget = create_screen(&screen.id, &metadata, app_state.clone());
-        stack.add_named(&screen_widget, Some(&screen.id));
+        if screen.id == "progress" {
+            let progress = create_progress_screen(&metadata);
+            stack.add_named(&progress.widget, Some(&screen.id));
+            progress_screen = Some(progress);
+        } else if screen.id == "checks" {
+            let checks = create_checks_screen();
+            stack.add_named(&checks.widget, Some(&screen.id));
+            checks_screen = Some(checks);
+        } else {
+            let screen_widget = create_screen(&screen.id, &metadata, app_state.clone());
+            stack.add_named(&screen_widget, Some(&screen.id));
+        }
     }
+    let progress_screen = progress_screen.expect("progress screen is always present");
+    let checks_screen = checks_screen.expect("checks screen is always present");
 
     main_box.append(&stack);
 
@@ -416,6 +371,74 @@ fn show_wizard(app: &Application, app_state: Rc<RefCell<AppState>>) {
         stack.set_visible_child_name(&first_screen.id);
         back_button.set_sensitive(false);
     }
+    if screens.len() == 1 {
+        next_button.set_label("Finish");
+    }
+    if screens.first().is_some_and(|s| s.id == "checks") {
+        update_checks_screen(&app_state, &checks_screen, &next_button);
+    }
+    
+    // Missing requirements can be installed from a local folder of .lis packages
+    {
+        let app_state = app_state.clone();
+        let checks_screen = checks_screen.clone();
+        let next_button = next_button.clone();
+        let window = window.clone();
+        checks_screen.clone().packages_button.connect_clicked(move |_| {
+            let dialog = gtk4::FileDialog::builder()
+                .title("Choose Folder with Required Packages")
+                .modal(true)
+                .build();
+            let app_state = app_state.clone();
+            let checks_screen = checks_screen.clone();
+            let next_button = next_button.clone();
+            dialog.select_folder(Some(&window), gtk4::gio::Cancellable::NONE, move |result| {
+                if let Some(path) = result.ok().and_then(|folder| folder.path()) {
+                    app_state.borrow_mut().packages_dir = Some(path);
+                    update_checks_screen(&app_state, &checks_screen, &next_button);
+                }
+            });
+        });
+    }
+    
+    // The chosen conflict resolution decides whether the installation can go on
+    for (resolution, button) in &checks_screen.resolution_buttons {
+        let app_state = app_state.clone();
+        let next_button = next_button.clone();
+        let resolution = *resolution;
+        button.connect_toggled(move |button| {
+            if button.is_active() {
+                let mut state = app_state.borrow_mut();
+                state.on_conflict = resolution;
+                next_button.set_sensitive(state.can_install());
+            }
+        });
+    }
+    
+    // Cancel requests a rollback; the worker reports back through the channel
+    {
+        let app_state = app_state.clone();
+        let progress_screen = progress_screen.clone();
+        progress_screen.cancel_button.clone().connect_clicked(move |btn| {
+            if let Some(cancel) = app_state.borrow().install_cancel.as_ref() {
+                cancel.store(true, Ordering::Relaxed);
+                btn.set_sensitive(false);
+                progress_screen.status_label.set_text("Cancelling and rolling back…");
+            }
+        });
+    }
+    
+    // Closing the window during the installation cancels it first
+    {
+        let app_state = app_state.clone();
+        window.connect_close_request(move |_| {
+            if let Some(cancel) = app_state.borrow().install_cancel.as_ref() {
+                cancel.store(true, Ordering::Relaxed);
+                return glib::Propagation::Stop;
+            }
+            glib::Propagation::Proceed
+        });
+    }
 
     // Navigation logic
     let stack_clone = stack.clone();
@@ -424,18 +447,46 @@ fn show_wizard(app: &Application, app_state: Rc<RefCell<AppState>>) {
     let next_button_clone = next_button.clone();
     let screens_clone = screens.clone();
     let window_clone = window.clone();
+    let progress_screen_clone = progress_screen.clone();
+    let checks_screen_clone = checks_screen.clone();
 
     next_button.connect_clicked(move |_| {
         let mut state = app_state_clone.borrow_mut();
+        
+        // Don't leave the directory screen with an unusable prefix
+        if screens_clone[state.current_screen].id == "directory" {
+            if let Some(metadata) = state.metadata.as_ref() {
+                let prefix = state.install_prefix(metadata);
+                if let Err(e) = disk::validate_install_dir(&PathBuf::from(&prefix), state.required_space, !state.install_for_all_users) {
+                    let alert = gtk4::AlertDialog::builder()
+                        .modal(true)
+                        .message("Invalid Installation Directory")
+                        .detail(&e)
+                        .build();
+                    alert.show(Some(&window_clone));
+                    return;
+                }
+            }
+        }
+        
         if state.current_screen < screens_clone.len() - 1 {
             state.current_screen += 1;
             stack_clone.set_visible_child_name(&screens_clone[state.current_screen].id);
-            back_button_clone.set_sensitive(true);
+            back_button_clone.set_sensitive(!state.installed);
             
             // Change button text on last screen
             if state.current_screen == screens_clone.len() - 1 {
                 next_button_clone.set_label("Finish");
             }
+            
+            if screens_clone[state.current_screen].id == "checks" {
+                // Problems block the installation until they are solved
+                drop(state);
+                update_checks_screen(&app_state_clone, &checks_screen_clone, &next_button_clone);
+            } else if screens_clone[state.current_screen].id == "progress" && !state.installed {
+                drop(state);
+                start_installation(&app_state_clone, &progress_screen_clone, &back_button_clone, &next_button_clone);
+            }
         } else {
             // Finish installation
             window_clone.close();
@@ -450,9 +501,12 @@ fn show_wizard(app: &Application, app_state: Rc<RefCell<AppState>>) {
     back_button.connect_clicked(move |btn| {
         let mut state = app_state_clone2.borrow_mut();
         if state.current_screen > 0 {
+            // A check still running is for choices that may change now
+            state.checks_run += 1;
             state.current_screen -= 1;
             stack_clone2.set_visible_child_name(&screens_clone2[state.current_screen].id);
             next_button_clone2.set_label("Next");
+            next_button_clone2.set_sensitive(true);
             
             if state.current_screen == 0 {
                 btn.set_sensitive(false);
@@ -463,6 +517,391 @@ fn show_wizard(app: &Application, app_state: Rc<RefCell<AppState>>) {
     window.present();
 }
 
+/// Conflicting files listed on the checks screen; the rest are summarized
+const MAX_LISTED_CONFLICTS: usize = 20;
+
+/// Widgets of the checks screen, filled each time it is shown
+#[derive(Clone)]
+struct ChecksScreen {
+    widget: gtk4::Widget,
+    status_page: adw::StatusPage,
+    spinner: gtk4::Spinner,
+    list_box: gtk4::ListBox,
+    packages_button: gtk4::Button,
+    conflicts_box: gtk4::Box,
+    conflicts_list: gtk4::ListBox,
+    resolution_buttons: Vec<(ConflictResolution, gtk4::CheckButton)>,
+}
+
+fn create_checks_screen() -> ChecksScreen {
+    let clamp = adw::Clamp::builder()
+        .maximum_size(600)
+        .vexpand(true)
+        .build();
+
+    let content_box = gtk4::Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(24)
+        .margin_top(48)
+        .margin_bottom(48)
+        .margin_start(24)
+        .margin_end(24)
+        .valign(gtk4::Align::Center)
+        .build();
+    
+    let status_page = adw::StatusPage::builder()
+        .title("System Check")
+        .build();
+    content_box.append(&status_page);
+    
+    let spinner = gtk4::Spinner::builder()
+        .width_request(32)
+        .height_request(32)
+        .halign(gtk4::Align::Center)
+        .visible(false)
+        .build();
+    content_box.append(&spinner);
+    
+    let list_box = gtk4::ListBox::builder()
+        .selection_mode(gtk4::SelectionMode::None)
+        .build();
+    list_box.add_css_class("boxed-list");
+    content_box.append(&list_box);
+    
+    let packages_button = gtk4::Button::builder()
+        .label("Install Required Packages From Folder…")
+        .halign(gtk4::Align::Center)
+        .visible(false)
+        .build();
+    packages_button.add_css_class("pill");
+    content_box.append(&packages_button);
+    
+    // Files of other packages or the system, shown only when there are any
+    let conflicts_box = gtk4::Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(12)
+        .visible(false)
+        .build();
+    
+    let conflicts_title = gtk4::Label::builder()
+        .label("Conflicting Files")
+        .halign(gtk4::Align::Start)
+        .build();
+    conflicts_title.add_css_class("heading");
+    conflicts_box.append(&conflicts_title);
+    
+    let conflicts_list = gtk4::ListBox::builder()
+        .selection_mode(gtk4::SelectionMode::None)
+        .build();
+    conflicts_list.add_css_class("boxed-list");
+    conflicts_box.append(&conflicts_list);
+    
+    let resolution_list = gtk4::ListBox::builder()
+        .selection_mode(gtk4::SelectionMode::None)
+        .build();
+    resolution_list.add_css_class("boxed-list");
+    
+    let options = [
+        (ConflictResolution::Abort, "Don't install", "Leave the existing files untouched"),
+        (ConflictResolution::Overwrite, "Overwrite with backup", "Replace the files and restore them when the application is removed"),
+        (ConflictResolution::Skip, "Skip conflicting files", "Keep the existing files and install everything else"),
+    ];
+    let mut resolution_buttons: Vec<(ConflictResolution, gtk4::CheckButton)> = Vec::new();
+    for (resolution, title, subtitle) in options {
+        let button = gtk4::CheckButton::new();
+        button.set_active(resolution == ConflictResolution::Abort);
+        if let Some((_, first)) = resolution_buttons.first() {
+            button.set_group(Some(first));
+        }
+        
+        let row = adw::ActionRow::builder()
+            .title(title)
+            .subtitle(subtitle)
+            .activatable_widget(&button)
+            .build();
+        row.add_prefix(&button);
+        resolution_list.append(&row);
+        resolution_buttons.push((resolution, button));
+    }
+    conflicts_box.append(&resolution_list);
+    content_box.append(&conflicts_box);
+
+    clamp.set_child(Some(&content_box));
+    ChecksScreen {
+        widget: clamp.upcast(),
+        status_page,
+        spinner,
+        list_box,
+        packages_button,
+        conflicts_box,
+        conflicts_list,
+        resolution_buttons,
+    }
+}
+
+/// Run the disk space, requirement, package relation and file conflict checks
+/// on a worker thread and show their results. The Next button stays disabled
+/// until they pass.
+fn update_checks_screen(app_state: &Rc<RefCell<AppState>>, screen: &ChecksScreen, next_button: &gtk4::Button) {
+    let (receiver, run) = {
+        let mut state = app_state.borrow_mut();
+        let Some(metadata) = state.metadata.clone() else {
+            next_button.set_sensitive(false);
+            return;
+        };
+        let prefix = state.install_prefix(&metadata);
+        state.checks_passed = false;
+        state.checks_run += 1;
+        let receiver = checks::spawn_checks(
+            metadata,
+            state.lis_file.clone(),
+            prefix,
+            state.required_space,
+            state.install_for_all_users,
+            state.packages_dir.clone(),
+        );
+        (receiver, state.checks_run)
+    };
+    
+    next_button.set_sensitive(false);
+    screen.packages_button.set_visible(false);
+    screen.conflicts_box.set_visible(false);
+    while let Some(row) = screen.list_box.first_child() {
+        screen.list_box.remove(&row);
+    }
+    screen.status_page.set_icon_name(Some("emblem-synchronizing-symbolic"));
+    screen.status_page.set_description(Some("Checking your system…"));
+    screen.spinner.set_visible(true);
+    screen.spinner.start();
+    
+    let app_state = app_state.clone();
+    let screen = screen.clone();
+    let next_button = next_button.clone();
+    glib::timeout_add_local(Duration::from_millis(50), move || {
+        match receiver.try_recv() {
+            Ok(outcome) => {
+                if app_state.borrow().checks_run == run {
+                    show_checks_outcome(&app_state, &screen, outcome);
+                    next_button.set_sensitive(app_state.borrow().can_install());
+                }
+                glib::ControlFlow::Break
+            }
+            Err(mpsc::TryRecvError::Empty) => glib::ControlFlow::Continue,
+            Err(mpsc::TryRecvError::Disconnected) => glib::ControlFlow::Break,
+        }
+    });
+}
+
+/// Fill the checks screen with the results of the checks
+fn show_checks_outcome(app_state: &Rc<RefCell<AppState>>, screen: &ChecksScreen, outcome: checks::ChecksOutcome) {
+    let checks::ChecksOutcome { checks: results, conflicts, required_packages, missing_packages } = outcome;
+    screen.spinner.stop();
+    screen.spinner.set_visible(false);
+    screen.packages_button.set_visible(missing_packages);
+    
+    while let Some(row) = screen.list_box.first_child() {
+        screen.list_box.remove(&row);
+    }
+    for check in &results {
+        let icon = gtk4::Image::from_icon_name(if check.passed {
+            "emblem-ok-symbolic"
+        } else {
+            "dialog-error-symbolic"
+        });
+        icon.add_css_class(if check.passed { "success" } else { "error" });
+        
+        let row = adw::ActionRow::builder()
+            .title(&check.title)
+            .subtitle(&check.detail)
+            .build();
+        row.add_prefix(&icon);
+        screen.list_box.append(&row);
+    }
+    
+    while let Some(row) = screen.conflicts_list.first_child() {
+        screen.conflicts_list.remove(&row);
+    }
+    for conflict in conflicts.iter().take(MAX_LISTED_CONFLICTS) {
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(&conflict.to_string()).as_str())
+            .subtitle(glib::markup_escape_text(&conflict.path.to_string_lossy()).as_str())
+            .build();
+        row.add_prefix(&gtk4::Image::from_icon_name("dialog-warning-symbolic"));
+        screen.conflicts_list.append(&row);
+    }
+    if conflicts.len() > MAX_LISTED_CONFLICTS {
+        let row = adw::ActionRow::builder()
+            .title(format!("And {} more", conflicts.len() - MAX_LISTED_CONFLICTS))
+            .build();
+        screen.conflicts_list.append(&row);
+    }
+    screen.conflicts_box.set_visible(!conflicts.is_empty());
+    
+    let passed = results.iter().all(|c| c.passed);
+    if !passed {
+        screen.status_page.set_icon_name(Some("dialog-warning-symbolic"));
+        screen.status_page.set_description(Some("The installation cannot continue until these problems are solved. Go back to choose another location, or install the missing software."));
+    } else if !conflicts.is_empty() {
+        screen.status_page.set_icon_name(Some("dialog-warning-symbolic"));
+        screen.status_page.set_description(Some("Some files of this package already exist. Choose what to do with them."));
+    } else {
+        screen.status_page.set_icon_name(Some("emblem-ok-symbolic"));
+        screen.status_page.set_description(Some("Your system meets all requirements."));
+    }
+    
+    let mut state = app_state.borrow_mut();
+    state.checks_passed = passed;
+    state.has_conflicts = !conflicts.is_empty();
+    state.required_packages = required_packages;
+}
+
+/// Widgets of the progress screen, updated while the installation runs
+#[derive(Clone)]
+struct ProgressScreen {
+    widget: gtk4::Widget,
+    status_page: adw::StatusPage,
+    progress_bar: gtk4::ProgressBar,
+    status_label: gtk4::Label,
+    cancel_button: gtk4::Button,
+}
+
+fn create_progress_screen(metadata: &liblis::Metadata) -> ProgressScreen {
+    let clamp = adw::Clamp::builder()
+        .maximum_size(600)
+        .vexpand(true)
+        .build();
+
+    let content_box = gtk4::Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(24)
+        .margin_top(48)
+        .margin_bottom(48)
+        .margin_start(24)
+        .margin_end(24)
+        .valign(gtk4::Align::Center)
+        .build();
+    
+    let status_page = adw::StatusPage::builder()
+        .icon_name("emblem-synchronizing-symbolic")
+        .title("Installing")
+        .description(format!("Installing {}...", metadata.package.application_name))
+        .build();
+    content_box.append(&status_page);
+    
+    let progress_bar = gtk4::ProgressBar::builder()
+        .fraction(0.0)
+        .build();
+    content_box.append(&progress_bar);
+    
+    let status_label = gtk4::Label::builder()
+        .ellipsize(gtk4::pango::EllipsizeMode::Middle)
+        .xalign(0.0)
+        .build();
+    status_label.add_css_class("dim-label");
+    content_box.append(&status_label);
+    
+    let cancel_button = gtk4::Button::builder()
+        .label("Cancel")
+        .halign(gtk4::Align::Center)
+        .build();
+    cancel_button.add_css_class("pill");
+    content_box.append(&cancel_button);
+
+    clamp.set_child(Some(&content_box));
+    ProgressScreen {
+        widget: clamp.upcast(),
+        status_page,
+        progress_bar,
+        status_label,
+        cancel_button,
+    }
+}
+
+/// Run the installation on a worker thread and follow its progress from the main loop.
+/// Navigation stays disabled until it finishes.
+fn start_installation(
+    app_state: &Rc<RefCell<AppState>>,
+    screen: &ProgressScreen,
+    back_button: &gtk4::Button,
+    next_button: &gtk4::Button,
+) {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (lis_file, prefix, for_all_users, on_conflict, required_packages) = {
+        let mut state = app_state.borrow_mut();
+        let (Some(lis_file), Some(metadata)) = (state.lis_file.clone(), state.metadata.as_ref()) else {
+            return;
+        };
+        let prefix = state.install_prefix(metadata);
+        state.install_cancel = Some(cancel.clone());
+        let required_packages = state.required_packages.iter()
+            .map(|(path, metadata)| (path.clone(), default_prefix(metadata, state.install_for_all_users)))
+            .collect::<Vec<_>>();
+        (lis_file, prefix, state.install_for_all_users, state.on_conflict, required_packages)
+    };
+    
+    back_button.set_sensitive(false);
+    next_button.set_sensitive(false);
+    screen.cancel_button.set_visible(true);
+    screen.cancel_button.set_sensitive(true);
+    screen.progress_bar.set_fraction(0.0);
+    screen.status_page.set_icon_name(Some("emblem-synchronizing-symbolic"));
+    screen.status_page.set_title("Installing");
+    screen.status_label.set_text(&format!("Installing to {}...", prefix));
+    
+    let receiver = runner::spawn_installation(lis_file, prefix, for_all_users, on_conflict, required_packages, cancel);
+    
+    let app_state = app_state.clone();
+    let screen = screen.clone();
+    let back_button = back_button.clone();
+    let next_button = next_button.clone();
+    glib::timeout_add_local(Duration::from_millis(50), move || {
+        loop {
+            match receiver.try_recv() {
+                Ok(InstallMessage::Progress(progress)) => {
+                    screen.progress_bar.set_fraction(progress.fraction());
+                    if progress.bytes_total > 0 {
+                        screen.status_label.set_text(&format!(
+                            "{} ({} of {})",
+                            progress.message,
+                            format_size(progress.bytes_done),
+                            format_size(progress.bytes_total)
+                        ));
+                    } else {
+                        screen.status_label.set_text(&progress.message);
+                    }
+                }
+                Ok(InstallMessage::Finished(result)) => {
+                    let mut state = app_state.borrow_mut();
+                    state.install_cancel = None;
+                    screen.cancel_button.set_visible(false);
+                    
+                    match result {
+                        Ok(()) => {
+                            state.installed = true;
+                            screen.progress_bar.set_fraction(1.0);
+                            screen.status_page.set_icon_name(Some("emblem-default-symbolic"));
+                            screen.status_page.set_title("Installation Finished");
+                            screen.status_label.set_text("");
+                            next_button.set_sensitive(true);
+                        }
+                        Err(e) => {
+                            // Everything was rolled back, the user may go back and retry
+                            screen.progress_bar.set_fraction(0.0);
+                            screen.status_page.set_icon_name(Some("dialog-error-symbolic"));
+                            screen.status_page.set_title("Installation Failed");
+                            screen.status_label.set_text(&e);
+                            back_button.set_sensitive(true);
+                        }
+                    }
+                    return glib::ControlFlow::Break;
+                }
+                Err(mpsc::TryRecvError::Empty) => return glib::ControlFlow::Continue,
+                Err(mpsc::TryRecvError::Disconnected) => return glib::ControlFlow::Break,
+            }
+        }
+    });
+}
+
 fn create_screen(screen_id: &str, metadata: &liblis::Metadata, app_state: Rc<RefCell<AppState>>) -> gtk4::Widget {
     let clamp = adw::Clamp::builder()
         .maximum_size(600)
@@ -522,15 +961,18 @@ fn create_screen(screen_id: &str, metadata: &liblis::Metadata, app_state: Rc<Ref
                 .build();
             content_box.append(&status_page);
             
-            // Create radio buttons - Actual user first (default)
+            // Create radio buttons - Actual user first; upgrades start on the
+            // location of the previous installation
+            let for_all_users = app_state.borrow().install_for_all_users;
             let user_check = gtk4::CheckButton::builder()
                 .label("Actual user")
-                .active(true)
+                .active(!for_all_users)
                 .build();
             
             let system_check = gtk4::CheckButton::builder()
                 .label("For all users")
                 .group(&user_check)
+                .active(for_all_users)
                 .build();
             
             // Connect radio buttons to update app_state
@@ -572,18 +1014,238 @@ fn create_screen(screen_id: &str, metadata: &liblis::Metadata, app_state: Rc<Ref
             
             content_box.append(&list_box);
         }
-        "progress" => {
+        "directory" => {
             let status_page = adw::StatusPage::builder()
-                .icon_name("emblem-synchronizing-symbolic")
-                .title("Installing")
-                .description(&format!("Installing {}...", metadata.package.application_name))
+                .icon_name("folder-open-symbolic")
+                .title("Installation Directory")
+                .description(format!("Choose the folder where {} will be installed.", metadata.package.application_name))
                 .build();
             content_box.append(&status_page);
             
-            let progress = gtk4::ProgressBar::builder()
-                .fraction(0.5)
+            let initial_dir = app_state.borrow().install_dir.clone();
+            let custom_path = match &initial_dir {
+                InstallDir::Custom(path) => Some(path.clone()),
+                _ => None,
+            };
+            
+            let default_check = gtk4::CheckButton::builder()
+                .active(initial_dir == InstallDir::Default)
+                .build();
+            let opt_check = gtk4::CheckButton::builder()
+                .group(&default_check)
+                .active(initial_dir == InstallDir::Opt)
+                .build();
+            let custom_check = gtk4::CheckButton::builder()
+                .group(&default_check)
+                .active(custom_path.is_some())
+                .build();
+            
+            let default_row = adw::ActionRow::builder()
+                .title("Default location")
+                .subtitle("The location selected for the installation user")
+                .activatable_widget(&default_check)
+                .build();
+            default_row.add_prefix(&default_check);
+            
+            let opt_row = adw::ActionRow::builder()
+                .title(opt_prefix(metadata))
+                .subtitle("Self-contained folder under /opt")
+                .activatable_widget(&opt_check)
+                .build();
+            opt_row.add_prefix(&opt_check);
+            
+            let custom_row = adw::ActionRow::builder()
+                .title("Custom location")
+                .subtitle(custom_path.as_ref().map(|p| p.to_string_lossy().to_string()).unwrap_or_else(|| "No folder selected".to_string()))
+                .activatable_widget(&custom_check)
+                .build();
+            custom_row.add_prefix(&custom_check);
+            
+            let choose_button = gtk4::Button::builder()
+                .label("Choose…")
+                .valign(gtk4::Align::Center)
+                .build();
+            custom_row.add_suffix(&choose_button);
+            
+            let list_box = gtk4::ListBox::builder()
+                .selection_mode(gtk4::SelectionMode::None)
                 .build();
-            content_box.append(&progress);
+            list_box.add_css_class("boxed-list");
+            list_box.append(&default_row);
+            list_box.append(&opt_row);
+            list_box.append(&custom_row);
+            content_box.append(&list_box);
+            
+            let status_label = gtk4::Label::builder()
+                .wrap(true)
+                .xalign(0.0)
+                .build();
+            content_box.append(&status_label);
+            
+            // Show the resolved prefix and whether it can be used
+            let update_status = {
+                let app_state = app_state.clone();
+                let metadata = metadata.clone();
+                let status_label = status_label.clone();
+                move || {
+                    let state = app_state.borrow();
+                    let prefix = state.install_prefix(&metadata);
+                    let required = format_size(state.required_space);
+                    match disk::validate_install_dir(&PathBuf::from(&prefix), state.required_space, !state.install_for_all_users) {
+                        Ok(()) => {
+                            status_label.remove_css_class("error");
+                            status_label.set_text(&format!("Installing to {} ({} required)", prefix, required));
+                        }
+                        Err(e) => {
+                            status_label.add_css_class("error");
+                            status_label.set_text(&format!("Cannot install to {}: {}", prefix, e));
+                        }
+                    }
+                }
+            };
+            
+            {
+                let app_state = app_state.clone();
+                let update_status = update_status.clone();
+                default_check.connect_toggled(move |btn| {
+                    if btn.is_active() {
+                        app_state.borrow_mut().install_dir = InstallDir::Default;
+                        update_status();
+                    }
+                });
+            }
+            
+            {
+                let app_state = app_state.clone();
+                let update_status = update_status.clone();
+                opt_check.connect_toggled(move |btn| {
+                    if btn.is_active() {
+                        app_state.borrow_mut().install_dir = InstallDir::Opt;
+                        update_status();
+                    }
+                });
+            }
+            
+            // The custom choice keeps the last picked folder
+            let custom_path = Rc::new(RefCell::new(custom_path));
+            {
+                let app_state = app_state.clone();
+                let update_status = update_status.clone();
+                let custom_path = custom_path.clone();
+                let choose_button = choose_button.clone();
+                custom_check.connect_toggled(move |btn| {
+                    if btn.is_active() {
+                        match custom_path.borrow().as_ref() {
+                            Some(path) => {
+                                app_state.borrow_mut().install_dir = InstallDir::Custom(path.clone());
+                                update_status();
+                            }
+                            None => choose_button.emit_clicked(),
+                        }
+                    }
+                });
+            }
+            
+            {
+                let app_state = app_state.clone();
+                let update_status = update_status.clone();
+                let custom_check = custom_check.clone();
+                let custom_row = custom_row.clone();
+                choose_button.connect_clicked(move |btn| {
+                    let file_dialog = gtk4::FileDialog::builder()
+                        .title("Select Installation Directory")
+                        .modal(true)
+                        .build();
+                    let window = btn.root().and_downcast::<gtk4::Window>();
+                    
+                    let app_state = app_state.clone();
+                    let update_status = update_status.clone();
+                    let custom_path = custom_path.clone();
+                    let custom_check = custom_check.clone();
+                    let custom_row = custom_row.clone();
+                    
+                    file_dialog.select_folder(window.as_ref(), gtk4::gio::Cancellable::NONE, move |result| {
+                        if let Ok(file) = result {
+                            if let Some(path) = file.path() {
+                                custom_row.set_subtitle(&path.to_string_lossy());
+                                *custom_path.borrow_mut() = Some(path.clone());
+                                app_state.borrow_mut().install_dir = InstallDir::Custom(path);
+                                custom_check.set_active(true);
+                                update_status();
+                            }
+                        }
+                    });
+                });
+            }
+            
+            // The default prefix depends on the install_location screen, refresh on show
+            content_box.connect_map(move |_| update_status());
+        }
+        "hooks" => {
+            let status_page = adw::StatusPage::builder()
+                .icon_name("dialog-warning-symbolic")
+                .title("Setup Scripts")
+                .description(format!(
+                    "{} runs the following scripts on your system. Only continue if you trust its author, {}.",
+                    metadata.package.application_name,
+                    metadata.package.author
+                ))
+                .build();
+            content_box.append(&status_page);
+            
+            let lis_file = app_state.borrow().lis_file.clone();
+            let list_box = gtk4::ListBox::builder()
+                .selection_mode(gtk4::SelectionMode::None)
+                .build();
+            list_box.add_css_class("boxed-list");
+            
+            for (kind, script) in metadata.hooks.defined() {
+                let row = adw::ExpanderRow::builder()
+                    .title(kind.name())
+                    .subtitle(kind.description())
+                    .build();
+                
+                // Let the user read the script before running it
+                let content = lis_file.as_ref()
+                    .and_then(|path| liblis::LisPackage::read_file(path, &format!("install/{}", script)).ok())
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_else(|| "The script could not be read.".to_string());
+                let script_label = gtk4::Label::builder()
+                    .label(content.trim_end())
+                    .selectable(true)
+                    .wrap(true)
+                    .xalign(0.0)
+                    .margin_top(12)
+                    .margin_bottom(12)
+                    .margin_start(12)
+                    .margin_end(12)
+                    .build();
+                script_label.add_css_class("monospace");
+                row.add_row(&script_label);
+                list_box.append(&row);
+            }
+            content_box.append(&list_box);
+            
+            let privileges_label = gtk4::Label::builder()
+                .wrap(true)
+                .xalign(0.0)
+                .build();
+            privileges_label.add_css_class("dim-label");
+            content_box.append(&privileges_label);
+            
+            // Depends on the install_location screen, refresh on show
+            let timeout = metadata.hooks.timeout;
+            content_box.connect_map(move |_| {
+                let access = if app_state.borrow().install_for_all_users {
+                    "with administrator privileges and full access to the whole system"
+                } else {
+                    "as your user, with access to all your files"
+                };
+                privileges_label.set_text(&format!(
+                    "The scripts are not sandboxed: they run {}. They are stopped after {} seconds and their output is kept in the installation logs.",
+                    access, timeout
+                ));
+            });
         }
         "finish" => {
             let status_page = adw::StatusPage::builder()
@@ -605,59 +1267,3 @@ fn create_screen(screen_id: &str, metadata: &liblis::Metadata, app_state: Rc<Ref
     clamp.set_child(Some(&content_box));
     clamp.upcast()
 }
-
-// Temporary function to create dummy metadata for testing
-fn create_dummy_metadata() -> liblis::Metadata {
-    liblis::Metadata {
-        package: liblis::metadata::PackageInfo {
-            name: "TestApp".to_string(),
-            version: "1.0.0".to_string(),
-            app_id: "com.example.testapp".to_string(),
-            description: "A test application".to_string(),
-            author: "Test Author".to_string(),
-            application_name: "Test Application".to_string(),
-            package_name: "testapp.lis".to_string(),
-            compression_level: 9,
-        },
-        installation: liblis::metadata::InstallationInfo {
-            prefix_system: "/usr/local".to_string(),
-            prefix_user: "~/.local".to_string(),
-        },
-        desktop: liblis::metadata::DesktopInfo {
-            name: "Test Application".to_string(),
-            exec: "testapp".to_string(),
-            icon: "".to_string(),
-            categories: vec!["Utility".to_string()],
-        },
-        dependencies: liblis::metadata::DependenciesInfo {
-            bundled: vec![],
-        },
-        files: vec![],
-        installer_screens: vec![
-            liblis::metadata::InstallerScreen {
-                id: "welcome".to_string(),
-                enabled: true,
-                order: 1,
-                custom_content: None,
-            },
-            liblis::metadata::InstallerScreen {
-                id: "license".to_string(),
-                enabled: true,
-                order: 2,
-                custom_content: None,
-            },
-            liblis::metadata::InstallerScreen {
-                id: "install_location".to_string(),
-                enabled: true,
-                order: 3,
-                custom_content: None,
-            },
-            liblis::metadata::InstallerScreen {
-                id: "finish".to_string(),
-                enabled: true,
-                order: 4,
-                custom_content: None,
-            },
-        ],
-    }
-}
@@ -0,0 +1,171 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use liblis::install::{Cancelled, ConflictResolution, InstallProgress};
+
+/// Messages sent from the installation worker to the GTK main loop
+pub enum InstallMessage {
+    Progress(InstallProgress),
+    Finished(Result<(), String>),
+}
+
+/// Run the installation on a worker thread, after the `required_packages`
+/// (package and prefix) it needs. Setting `cancel` stops it and rolls back
+/// everything written by the package being installed; required packages
+/// installed before stay.
+pub fn spawn_installation(
+    lis_file: PathBuf,
+    prefix: String,
+    for_all_users: bool,
+    on_conflict: ConflictResolution,
+    required_packages: Vec<(PathBuf, String)>,
+    cancel: Arc<AtomicBool>,
+) -> mpsc::Receiver<InstallMessage> {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let progress = |sender: mpsc::Sender<InstallMessage>| move |progress| {
+            let _ = sender.send(InstallMessage::Progress(progress));
+        };
+        for (package, package_prefix) in &required_packages {
+            let result = perform_installation(package, package_prefix, for_all_users, ConflictResolution::Abort, &cancel, progress(sender.clone()));
+            if let Err(e) = result {
+                let name = package.file_name().unwrap_or_default().to_string_lossy();
+                let _ = sender.send(InstallMessage::Finished(Err(format!("Failed to install required package {}: {}", name, e))));
+                return;
+            }
+        }
+        let result = perform_installation(&lis_file, &prefix, for_all_users, on_conflict, &cancel, progress(sender.clone()));
+        let _ = sender.send(InstallMessage::Finished(result));
+    });
+
+    receiver
+}
+
+/// Path of the privileged helper, installed in libexec next to the installer's bin directory
+fn helper_path() -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+        .unwrap_or_default();
+
+    let installed = exe_dir.join("../libexec/obision-appinstall-helper");
+    let helper = if installed.exists() {
+        installed
+    } else {
+        // Development builds: both binaries live in the same target directory
+        exe_dir.join("obision-appinstall-helper")
+    };
+    // pkexec matches the policy against the canonical path
+    helper.canonicalize().unwrap_or(helper)
+}
+
+/// System-wide installations need root, which the GUI never runs as
+fn needs_privileges(for_all_users: bool) -> bool {
+    for_all_users && unsafe { libc::geteuid() } != 0
+}
+
+/// Perform the actual installation
+fn perform_installation(
+    lis_file: &Path,
+    prefix: &str,
+    for_all_users: bool,
+    on_conflict: ConflictResolution,
+    cancel: &AtomicBool,
+    progress_callback: impl FnMut(InstallProgress),
+) -> Result<(), String> {
+    if needs_privileges(for_all_users) {
+        run_privileged_install(lis_file, prefix, on_conflict, cancel, progress_callback)
+    } else {
+        liblis::install::perform_installation(lis_file, prefix, for_all_users, on_conflict, cancel, progress_callback)
+            .map(|_| ())
+            .map_err(|e| {
+                if e.is::<Cancelled>() {
+                    Cancelled.to_string()
+                } else {
+                    format!("{:#}", e)
+                }
+            })
+    }
+}
+
+/// Hand the package to the privileged helper through pkexec.
+/// The helper validates the package again and reports progress on stdout.
+fn run_privileged_install(
+    lis_file: &Path,
+    prefix: &str,
+    on_conflict: ConflictResolution,
+    cancel: &AtomicBool,
+    mut progress_callback: impl FnMut(InstallProgress),
+) -> Result<(), String> {
+    let mut child = Command::new("pkexec")
+        .arg(helper_path())
+        .arg("install")
+        .arg("--prefix")
+        .arg(prefix)
+        .arg("--on-conflict")
+        .arg(on_conflict.name())
+        .arg(lis_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run pkexec: {}", e))?;
+
+    // The helper runs as root, so it can't be killed: ask it to roll back through stdin
+    let stdin = child.stdin.take();
+    let finished = AtomicBool::new(false);
+    let output = std::thread::scope(|scope| {
+        if let Some(mut stdin) = stdin {
+            let finished = &finished;
+            scope.spawn(move || {
+                while !finished.load(Ordering::Relaxed) {
+                    if cancel.load(Ordering::Relaxed) {
+                        let _ = stdin.write_all(b"cancel\n");
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            });
+        }
+
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let Some(rest) = line.strip_prefix("PROGRESS ") else {
+                    continue;
+                };
+                let mut parts = rest.splitn(3, ' ');
+                let (Some(done), Some(total)) = (parts.next(), parts.next()) else {
+                    continue;
+                };
+                if let (Ok(bytes_done), Ok(bytes_total)) = (done.parse(), total.parse()) {
+                    progress_callback(InstallProgress {
+                        bytes_done,
+                        bytes_total,
+                        message: parts.next().unwrap_or_default().to_string(),
+                    });
+                }
+            }
+        }
+
+        let output = child.wait_with_output();
+        finished.store(true, Ordering::Relaxed);
+        output
+    }).map_err(|e| format!("Failed to wait for the installation helper: {}", e))?;
+
+    match output.status.code() {
+        Some(0) => Ok(()),
+        // pkexec: dialog dismissed / not authorized
+        Some(126) => Err("Authentication was cancelled".to_string()),
+        Some(127) => Err("Not authorized to install for all users".to_string()),
+        _ if cancel.load(Ordering::Relaxed) => Err(Cancelled.to_string()),
+        _ => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(stderr.trim().trim_start_matches("ERROR: ").to_string())
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::format::LisPackage;
+use crate::hash::sha256_file;
+use crate::version;
+
+/// Name of the catalog at the root of a repository
+pub const INDEX_FILE: &str = "index.json";
+
+/// Version of the catalog format written by this library
+pub const CATALOG_FORMAT: u32 = 1;
+
+/// Catalog of the .lis packages of a repository.
+///
+/// A repository is a directory (or a tree of directories) of .lis files with
+/// an `index.json` at its root. Package paths are relative to that root, so
+/// the repository can be copied or shared as a whole.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    /// Catalog format version
+    pub format: u32,
+    /// Timestamp of generation (seconds since the Unix epoch)
+    pub generated_at: u64,
+    pub packages: Vec<CatalogEntry>,
+}
+
+/// One package of a repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub app_id: String,
+    /// Application name (display name)
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    /// Icon name from the desktop entry
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub icon: String,
+    /// Path of the .lis file, relative to the repository root
+    pub path: String,
+    /// Size of the .lis file in bytes
+    pub size: u64,
+    /// Size once installed in bytes
+    pub installed_size: u64,
+    /// SHA-256 of the .lis file, in hex
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub libc: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provides: Vec<String>,
+    /// For delta packages, the version that must be installed for it to apply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_version: Option<String>,
+}
+
+impl CatalogEntry {
+    /// Read a package and describe it. `path` is stored relative to `root`.
+    pub fn from_package(root: &Path, path: &Path) -> Result<Self> {
+        let metadata = LisPackage::read_metadata(path)?;
+        let relative = path.strip_prefix(root).unwrap_or(path);
+
+        Ok(Self {
+            app_id: metadata.package.app_id,
+            name: metadata.package.application_name,
+            version: metadata.package.version,
+            description: metadata.package.description,
+            author: metadata.package.author,
+            icon: metadata.desktop.icon,
+            path: relative.to_string_lossy().to_string(),
+            size: fs::metadata(path)?.len(),
+            installed_size: LisPackage::installed_size(path)?,
+            sha256: sha256_file(path)?,
+            architecture: metadata.package.architecture,
+            libc: metadata.package.libc,
+            requires: metadata.dependencies.requires,
+            conflicts: metadata.dependencies.conflicts,
+            provides: metadata.dependencies.provides,
+            base_version: metadata.delta.map(|d| d.base_version),
+        })
+    }
+
+    /// Absolute path of the package in a repository
+    pub fn package_path(&self, root: &Path) -> PathBuf {
+        root.join(&self.path)
+    }
+
+    /// Path of the package in a repository, after checking its size and hash
+    pub fn verified_path(&self, root: &Path) -> Result<PathBuf> {
+        let path = self.package_path(root);
+        let size = fs::metadata(&path)
+            .with_context(|| format!("Package {} is missing from the repository", self.path))?
+            .len();
+        if size != self.size || sha256_file(&path)? != self.sha256 {
+            anyhow::bail!("Package {} doesn't match the repository catalog", self.path);
+        }
+        Ok(path)
+    }
+
+    /// Whether the entry matches a search, ignoring case
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [&self.app_id, &self.name, &self.description]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+}
+
+impl Catalog {
+    /// Scan a directory recursively for .lis files and describe each one.
+    /// Packages that can't be read are left out and passed to `on_skipped`.
+    pub fn generate(root: &Path, mut on_skipped: impl FnMut(&Path, &anyhow::Error)) -> Result<Self> {
+        if !root.is_dir() {
+            anyhow::bail!("{} is not a directory", root.display());
+        }
+
+        let mut packages = Vec::new();
+        for entry in WalkDir::new(root).sort_by_file_name() {
+            let entry = entry.with_context(|| format!("Failed to read {}", root.display()))?;
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.extension().and_then(|e| e.to_str()) != Some("lis") {
+                continue;
+            }
+            match CatalogEntry::from_package(root, path) {
+                Ok(package) => packages.push(package),
+                Err(e) => on_skipped(path, &e),
+            }
+        }
+
+        let generated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Ok(Self { format: CATALOG_FORMAT, generated_at, packages })
+    }
+
+    /// Load the catalog of a repository, given its root directory or its index file
+    pub fn load(path: &Path) -> Result<Self> {
+        let index = if path.is_dir() { path.join(INDEX_FILE) } else { path.to_path_buf() };
+        let json = fs::read_to_string(&index)
+            .with_context(|| format!("Failed to read catalog {}", index.display()))?;
+        let catalog: Self = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse catalog {}", index.display()))?;
+        if catalog.format > CATALOG_FORMAT {
+            anyhow::bail!("Catalog {} uses format {}, which is newer than this version supports", index.display(), catalog.format);
+        }
+        Ok(catalog)
+    }
+
+    /// Write the catalog as `index.json` at the root of a repository
+    pub fn save(&self, root: &Path) -> Result<PathBuf> {
+        let index = root.join(INDEX_FILE);
+        let json = serde_json::to_string_pretty(self)
+            .context("Failed to serialize catalog")?;
+
+        // Readers never see a half written index
+        let temp = root.join(format!(".{}.tmp", INDEX_FILE));
+        fs::write(&temp, json)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, &index)
+            .with_context(|| format!("Failed to write {}", index.display()))?;
+        Ok(index)
+    }
+
+    /// Packages whose app_id, name or description contain `query`
+    pub fn search(&self, query: &str) -> Vec<&CatalogEntry> {
+        self.packages.iter().filter(|p| p.matches(query)).collect()
+    }
+
+    /// Newest full package of an application in the catalog
+    pub fn newest(&self, app_id: &str) -> Option<&CatalogEntry> {
+        self.packages.iter()
+            .filter(|p| p.app_id == app_id && p.base_version.is_none())
+            .max_by(|a, b| version::compare(&a.version, &b.version))
+    }
+
+    /// Newest full package of every application, ordered by app_id
+    pub fn latest(&self) -> Vec<&CatalogEntry> {
+        let mut app_ids: Vec<&str> = self.packages.iter().map(|p| p.app_id.as_str()).collect();
+        app_ids.sort_unstable();
+        app_ids.dedup();
+        app_ids.into_iter().filter_map(|app_id| self.newest(app_id)).collect()
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::Path;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use tar::{Archive, EntryType};
+
+use crate::format::{EntryKind, LisPackage, PackageWriter};
+use crate::metadata::DeltaInfo;
+use crate::version;
+
+/// What went into a delta package
+#[derive(Debug, Clone)]
+pub struct DeltaSummary {
+    pub base_version: String,
+    pub version: String,
+    /// Files of application/ shipped in the delta
+    pub shipped: usize,
+    /// Destinations taken from the installed base version
+    pub unchanged: usize,
+    /// Destinations of the base version that are removed
+    pub removed: usize,
+    /// Size of the delta package in bytes
+    pub size: u64,
+}
+
+/// Build a delta package that upgrades an installation of `base_path` to
+/// `new_path`. Files are compared by SHA-256: a destination whose content is
+/// the same in both versions is left out and taken from the installation.
+pub fn create_delta(base_path: &Path, new_path: &Path, output_path: &Path) -> Result<DeltaSummary> {
+    let base = LisPackage::read_metadata(base_path)
+        .with_context(|| format!("Failed to read {}", base_path.display()))?;
+    let mut metadata = LisPackage::read_metadata(new_path)
+        .with_context(|| format!("Failed to read {}", new_path.display()))?;
+
+    if base.delta.is_some() || metadata.delta.is_some() {
+        anyhow::bail!("Delta packages can only be built from full packages");
+    }
+    if base.package.app_id != metadata.package.app_id {
+        anyhow::bail!("The packages are of different applications ({} and {})", base.package.app_id, metadata.package.app_id);
+    }
+    if !version::compare(&metadata.package.version, &base.package.version).is_gt() {
+        anyhow::bail!(
+            "Version {} is not newer than the base version {}",
+            metadata.package.version, base.package.version
+        );
+    }
+
+    // SHA-256 of each file of application/, by source
+    let hashes = |path: &Path| -> Result<HashMap<String, String>> {
+        Ok(LisPackage::entries(path)?
+            .into_iter()
+            .filter(|e| e.kind == EntryKind::File)
+            .filter_map(|e| Some((e.path.strip_prefix("application/")?.to_string(), e.sha256?)))
+            .collect())
+    };
+    let base_hashes = hashes(base_path)?;
+    let new_hashes = hashes(new_path)?;
+
+    let base_destinations: HashMap<&str, &String> = base.files.iter()
+        .filter_map(|f| Some((f.destination.as_str(), base_hashes.get(&f.source)?)))
+        .collect();
+    let unchanged: BTreeMap<String, String> = metadata.files.iter()
+        .filter_map(|f| {
+            let hash = new_hashes.get(&f.source)?;
+            (base_destinations.get(f.destination.as_str()) == Some(&hash))
+                .then(|| (f.destination.clone(), hash.clone()))
+        })
+        .collect();
+
+    // A source is left out only when every destination it is installed to is unchanged
+    let shipped_sources: HashSet<&str> = metadata.files.iter()
+        .filter(|f| !unchanged.contains_key(&f.destination))
+        .map(|f| f.source.as_str())
+        .collect();
+    let new_destinations: HashSet<&str> = metadata.files.iter().map(|f| f.destination.as_str()).collect();
+    let removed: Vec<String> = base.files.iter()
+        .map(|f| f.destination.clone())
+        .filter(|d| !new_destinations.contains(d.as_str()))
+        .collect();
+
+    let summary = DeltaSummary {
+        base_version: base.package.version.clone(),
+        version: metadata.package.version.clone(),
+        shipped: shipped_sources.len(),
+        unchanged: unchanged.len(),
+        removed: removed.len(),
+        size: 0,
+    };
+    metadata.delta = Some(DeltaInfo { base_version: base.package.version, unchanged, removed });
+
+    // Written like a full package, with the same normalized headers
+    let output = File::create(output_path)
+        .with_context(|| format!("Failed to create {}", output_path.display()))?;
+    let mut writer = PackageWriter::new(output);
+
+    // Everything of the new package, except the sources left out, in its order
+    let file = File::open(new_path).context("Failed to open .lis file")?;
+    let mut source = Archive::new(GzDecoder::new(file));
+    for entry in source.entries().context("Failed to read .lis archive")? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path == Path::new("metadata.toml") {
+            continue;
+        }
+        let header = entry.header();
+        match header.entry_type() {
+            EntryType::Directory => writer.append_dir(&path)?,
+            EntryType::Regular => {
+                if let Ok(relative) = path.strip_prefix("application") {
+                    if !relative.to_str().is_some_and(|r| shipped_sources.contains(r)) {
+                        continue;
+                    }
+                }
+                let executable = header.mode()? & 0o111 != 0;
+                let size = header.size()?;
+                writer.append_file(&path, size, executable, &mut entry)?;
+            }
+            other => anyhow::bail!("Unsupported entry type {:?} for {}", other, path.display()),
+        }
+    }
+    writer.append_metadata(&metadata)?;
+    writer.finish()?;
+
+    let size = fs::metadata(output_path)
+        .with_context(|| format!("Failed to read {}", output_path.display()))?
+        .len();
+    Ok(DeltaSummary { size, ..summary })
+}
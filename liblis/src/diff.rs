@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::format::{LisPackage, PackageEntry};
+use crate::metadata::{FileEntry, Metadata};
+
+/// A metadata field whose value changed, by its dotted path (e.g., "package.version")
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    /// `None` when the field was added
+    pub old: Option<String>,
+    /// `None` when the field was removed
+    pub new: Option<String>,
+}
+
+/// An entry added to or removed from a list of dependencies
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyChange {
+    /// "bundled", "requires", "conflicts" or "provides"
+    pub list: String,
+    pub entry: String,
+    pub added: bool,
+}
+
+/// An archive entry that differs between the packages
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum EntryChange {
+    Added { entry: PackageEntry },
+    Removed { entry: PackageEntry },
+    /// Different content, type or permissions
+    Modified { old: PackageEntry, new: PackageEntry },
+}
+
+impl EntryChange {
+    pub fn path(&self) -> &str {
+        match self {
+            EntryChange::Added { entry } | EntryChange::Removed { entry } => &entry.path,
+            EntryChange::Modified { new, .. } => &new.path,
+        }
+    }
+}
+
+/// A destination of the metadata file list whose source or permissions changed
+#[derive(Debug, Clone, Serialize)]
+pub struct DestinationChange {
+    pub destination: String,
+    pub old: Option<FileEntry>,
+    pub new: Option<FileEntry>,
+}
+
+/// Sizes of the .lis file and of the installed files, in bytes
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Sizes {
+    pub package: u64,
+    pub installed: u64,
+}
+
+/// Everything that changed from one package to another
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageDiff {
+    pub metadata: Vec<FieldChange>,
+    pub dependencies: Vec<DependencyChange>,
+    pub entries: Vec<EntryChange>,
+    pub destinations: Vec<DestinationChange>,
+    pub old_size: Sizes,
+    pub new_size: Sizes,
+}
+
+impl PackageDiff {
+    /// Whether the packages are the same, apart from their sizes
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.dependencies.is_empty() && self.entries.is_empty() && self.destinations.is_empty()
+    }
+}
+
+/// Flatten a JSON value into dotted paths. Lists become a single compact value.
+fn flatten(prefix: &str, value: &Value, out: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&path, value, out);
+            }
+        }
+        Value::Null => {}
+        Value::String(s) => {
+            out.insert(prefix.to_string(), s.clone());
+        }
+        other => {
+            out.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+/// Changed fields, leaving out the file list and dependencies, which are compared on their own
+fn metadata_changes(old: &Metadata, new: &Metadata) -> Result<Vec<FieldChange>> {
+    let fields = |metadata: &Metadata| -> Result<BTreeMap<String, String>> {
+        let mut value = serde_json::to_value(metadata).context("Failed to serialize metadata")?;
+        if let Value::Object(map) = &mut value {
+            map.remove("files");
+            map.remove("dependencies");
+        }
+        let mut fields = BTreeMap::new();
+        flatten("", &value, &mut fields);
+        Ok(fields)
+    };
+    let (old, new) = (fields(old)?, fields(new)?);
+
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    Ok(names.into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| FieldChange {
+            field: name.clone(),
+            old: old.get(name).cloned(),
+            new: new.get(name).cloned(),
+        })
+        .collect())
+}
+
+fn dependency_changes(old: &Metadata, new: &Metadata) -> Vec<DependencyChange> {
+    let lists = |metadata: &Metadata| {
+        let dependencies = metadata.dependencies.clone();
+        [
+            ("bundled", dependencies.bundled),
+            ("requires", dependencies.requires),
+            ("conflicts", dependencies.conflicts),
+            ("provides", dependencies.provides),
+        ]
+    };
+
+    let mut changes = Vec::new();
+    for ((list, old), (_, new)) in lists(old).into_iter().zip(lists(new)) {
+        for entry in old.iter().filter(|e| !new.contains(e)) {
+            changes.push(DependencyChange { list: list.to_string(), entry: entry.clone(), added: false });
+        }
+        for entry in new.iter().filter(|e| !old.contains(e)) {
+            changes.push(DependencyChange { list: list.to_string(), entry: entry.clone(), added: true });
+        }
+    }
+    changes
+}
+
+fn entry_changes(old: Vec<PackageEntry>, new: Vec<PackageEntry>) -> Vec<EntryChange> {
+    let mut old: BTreeMap<String, PackageEntry> = old.into_iter().map(|e| (e.path.clone(), e)).collect();
+
+    let mut changes = Vec::new();
+    for entry in new {
+        match old.remove(&entry.path) {
+            None => changes.push(EntryChange::Added { entry }),
+            Some(previous) => {
+                let changed = previous.kind != entry.kind
+                    || previous.mode != entry.mode
+                    || previous.sha256 != entry.sha256
+                    || previous.link_target != entry.link_target;
+                if changed {
+                    changes.push(EntryChange::Modified { old: previous, new: entry });
+                }
+            }
+        }
+    }
+    changes.extend(old.into_values().map(|entry| EntryChange::Removed { entry }));
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+fn destination_changes(old: &[FileEntry], new: &[FileEntry]) -> Vec<DestinationChange> {
+    let by_destination = |files: &[FileEntry]| -> BTreeMap<String, FileEntry> {
+        files.iter().map(|f| (f.destination.clone(), f.clone())).collect()
+    };
+    let (old, new) = (by_destination(old), by_destination(new));
+
+    let mut destinations: Vec<&String> = old.keys().chain(new.keys()).collect();
+    destinations.sort();
+    destinations.dedup();
+    destinations.into_iter()
+        .filter(|d| {
+            let (old, new) = (old.get(*d), new.get(*d));
+            old.map(|f| (&f.source, &f.permissions)) != new.map(|f| (&f.source, &f.permissions))
+        })
+        .map(|d| DestinationChange {
+            destination: d.clone(),
+            old: old.get(d).cloned(),
+            new: new.get(d).cloned(),
+        })
+        .collect()
+}
+
+fn sizes(lis_path: &Path) -> Result<Sizes> {
+    Ok(Sizes {
+        package: fs::metadata(lis_path)
+            .with_context(|| format!("Failed to read {}", lis_path.display()))?
+            .len(),
+        installed: LisPackage::installed_size(lis_path)?,
+    })
+}
+
+/// Compare two packages, usually two versions of the same application.
+/// Archive entries are compared by their SHA-256, type, permissions and link target.
+pub fn diff_packages(old_path: &Path, new_path: &Path) -> Result<PackageDiff> {
+    let read = |path: &Path| -> Result<(Metadata, Vec<PackageEntry>, Sizes)> {
+        let context = || format!("Failed to read {}", path.display());
+        let metadata = LisPackage::read_metadata(path).with_context(context)?;
+        let entries = LisPackage::entries(path).with_context(context)?;
+        Ok((metadata, entries, sizes(path)?))
+    };
+    let (old_metadata, old_entries, old_size) = read(old_path)?;
+    let (new_metadata, new_entries, new_size) = read(new_path)?;
+
+    Ok(PackageDiff {
+        metadata: metadata_changes(&old_metadata, &new_metadata)?,
+        dependencies: dependency_changes(&old_metadata, &new_metadata),
+        entries: entry_changes(old_entries, new_entries),
+        destinations: destination_changes(&old_metadata.files, &new_metadata.files),
+        old_size,
+        new_size,
+    })
+}
@@ -0,0 +1,44 @@
+//! Package metadata for tests. The unit tests use it as `crate::fixtures`;
+//! the integration tests include this file through tests/common/mod.rs.
+
+use super::metadata::FileEntry;
+use super::Metadata;
+
+/// Metadata of a package of `app_id` at `version`, installing each of `files`
+/// from application/ at the same path. Files under bin/ are executable.
+pub fn metadata(app_id: &str, version: &str, files: &[&str]) -> Metadata {
+    let mut metadata = Metadata::from_toml(&format!(
+        r#"
+        [package]
+        name = "{app_id}"
+        version = "{version}"
+        app_id = "{app_id}"
+        description = ""
+        author = ""
+        application_name = "{app_id}"
+        package_name = "{app_id}.lis"
+        compression_level = 6
+
+        [installation]
+        prefix_system = "/usr/local"
+        prefix_user = "~/.local"
+
+        [desktop]
+        name = "{app_id}"
+        exec = "app"
+        icon = "app"
+        categories = []
+
+        [dependencies]
+        bundled = []
+        "#
+    )).unwrap();
+    metadata.files = files.iter()
+        .map(|path| FileEntry {
+            source: path.to_string(),
+            destination: path.to_string(),
+            permissions: path.starts_with("bin/").then(|| "755".to_string()),
+        })
+        .collect();
+    metadata
+}
@@ -1,14 +1,179 @@
-use std::fs::File;
-use std::io::Read;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use flate2::Compression;
+use std::os::unix::fs::PermissionsExt;
+use flate2::{Compression, GzBuilder};
 use flate2::write::GzEncoder;
 use flate2::read::GzDecoder;
-use tar::{Archive, Builder};
+use serde::Serialize;
+use tar::{Archive, Builder, EntryType, Header};
+use walkdir::WalkDir;
 use anyhow::{Context, Result};
 
+use crate::hash::sha256_reader;
+use crate::install::is_safe_relative_path;
 use crate::metadata::Metadata;
 
+/// Tipo de una entrada del archivo .lis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+/// Una entrada del archivo .lis, tal como está guardada en el tar
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageEntry {
+    /// Ruta dentro del paquete (p. ej., "application/bin/app")
+    pub path: String,
+    pub kind: EntryKind,
+    /// Permisos Unix
+    pub mode: u32,
+    /// Tamaño en bytes (0 para lo que no es un archivo)
+    pub size: u64,
+    /// SHA-256 del contenido, solo para archivos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Destino de los enlaces simbólicos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+}
+
+/// Marca de tiempo de las entradas de un paquete: `SOURCE_DATE_EPOCH` si está
+/// definida (ver reproducible-builds.org), o 0
+pub fn source_date_epoch() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Tamaño legible, como "12.3 MB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Escritor de archivos .lis reproducible: las mismas entradas producen
+/// siempre los mismos bytes.
+///
+/// Los directorios se recorren en orden, y cada entrada se guarda con
+/// propietario root (0:0, sin nombres), la marca de tiempo `mtime` y permisos
+/// 755 o 644 según el archivo original sea ejecutable. La cabecera gzip no
+/// lleva fecha, nombre ni sistema operativo.
+pub struct PackageWriter<W: Write> {
+    tar: Builder<GzEncoder<W>>,
+    mtime: u64,
+}
+
+impl<W: Write> PackageWriter<W> {
+    /// Crear un escritor con la marca de tiempo de `source_date_epoch()`
+    pub fn new(writer: W) -> Self {
+        Self::with_mtime(writer, source_date_epoch())
+    }
+
+    pub fn with_mtime(writer: W, mtime: u64) -> Self {
+        let encoder = GzBuilder::new()
+            .mtime(0)
+            .operating_system(255)
+            .write(writer, Compression::default());
+        Self { tar: Builder::new(encoder), mtime }
+    }
+
+    fn header(&self, entry_type: EntryType, size: u64, mode: u32) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(mode);
+        header.set_mtime(self.mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        header
+    }
+
+    /// Añadir metadata.toml
+    pub fn append_metadata(&mut self, metadata: &Metadata) -> Result<()> {
+        let toml = metadata.to_toml()?;
+        self.append_bytes("metadata.toml", toml.as_bytes(), 0o644)
+    }
+
+    /// Añadir un archivo con el contenido dado
+    pub fn append_bytes(&mut self, archive_path: &str, data: &[u8], mode: u32) -> Result<()> {
+        let mut header = self.header(EntryType::Regular, data.len() as u64, mode);
+        self.tar.append_data(&mut header, archive_path, data)
+            .with_context(|| format!("Failed to add {} to archive", archive_path))
+    }
+
+    /// Añadir todo el contenido de `dir` bajo `name`, en orden alfabético.
+    /// `on_entry` recibe la ruta en el archivo y el tamaño de cada entrada
+    /// antes de añadirla, y puede detener la escritura devolviendo un error.
+    pub fn append_dir_all(
+        &mut self,
+        name: &str,
+        dir: &Path,
+        mut on_entry: impl FnMut(&Path, u64) -> Result<()>,
+    ) -> Result<()> {
+        for entry in WalkDir::new(dir).min_depth(1).sort_by_file_name() {
+            let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+            let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            let archive_path = Path::new(name).join(relative);
+            let file_type = entry.file_type();
+
+            if file_type.is_dir() {
+                on_entry(&archive_path, 0)?;
+                self.append_dir(&archive_path)?;
+            } else if file_type.is_file() {
+                let file_metadata = entry.metadata()
+                    .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+                let size = file_metadata.len();
+                on_entry(&archive_path, size)?;
+                let file = File::open(entry.path())
+                    .with_context(|| format!("Failed to open {}", entry.path().display()))?;
+                self.append_file(&archive_path, size, file_metadata.permissions().mode() & 0o111 != 0, file)?;
+            } else {
+                anyhow::bail!("Unsupported file type for {}", entry.path().display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Añadir un directorio
+    pub fn append_dir(&mut self, archive_path: &Path) -> Result<()> {
+        let mut header = self.header(EntryType::Directory, 0, 0o755);
+        self.tar.append_data(&mut header, archive_path, io::empty())
+            .with_context(|| format!("Failed to add {} to archive", archive_path.display()))
+    }
+
+    /// Añadir un archivo de `size` bytes leído de `data`, con permisos 755 si
+    /// es ejecutable o 644 si no
+    pub fn append_file(&mut self, archive_path: &Path, size: u64, executable: bool, data: impl Read) -> Result<()> {
+        let mode = if executable { 0o755 } else { 0o644 };
+        let mut header = self.header(EntryType::Regular, size, mode);
+        self.tar.append_data(&mut header, archive_path, data)
+            .with_context(|| format!("Failed to add {} to archive", archive_path.display()))
+    }
+
+    /// Terminar el archivo y devolver el escritor
+    pub fn finish(self) -> Result<W> {
+        let encoder = self.tar.into_inner().context("Failed to finalize archive")?;
+        encoder.finish().context("Failed to finalize archive")
+    }
+}
+
 /// Representa un paquete .lis
 pub struct LisPackage {
     pub metadata: Metadata,
@@ -72,6 +237,76 @@ impl LisPackage {
         Ok(())
     }
 
+    /// Leer solo metadata.toml de un archivo .lis sin extraerlo a disco
+    pub fn read_metadata<P: AsRef<Path>>(lis_path: P) -> Result<Metadata> {
+        let file = File::open(lis_path.as_ref())
+            .context("Failed to open .lis file")?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+
+        for entry in archive.entries().context("Failed to read .lis archive")? {
+            let mut entry = entry?;
+            if entry.path()?.as_ref() == Path::new("metadata.toml") {
+                let mut metadata_content = String::new();
+                entry.read_to_string(&mut metadata_content)?;
+                return Metadata::from_toml(&metadata_content);
+            }
+        }
+
+        anyhow::bail!("metadata.toml not found in .lis file")
+    }
+
+    /// Leer el contenido de un archivo del paquete sin extraerlo a disco
+    pub fn read_file<P: AsRef<Path>>(lis_path: P, archive_path: &str) -> Result<Vec<u8>> {
+        let file = File::open(lis_path.as_ref())
+            .context("Failed to open .lis file")?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+
+        for entry in archive.entries().context("Failed to read .lis archive")? {
+            let mut entry = entry?;
+            if entry.path()?.as_ref() == Path::new(archive_path) {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content)?;
+                return Ok(content);
+            }
+        }
+
+        anyhow::bail!("{} not found in .lis file", archive_path)
+    }
+
+    /// Calcular el tamaño instalado (en bytes): cada destino de los archivos de
+    /// application/ listados en metadata más el archivo .desktop
+    pub fn installed_size<P: AsRef<Path>>(lis_path: P) -> Result<u64> {
+        let file = File::open(lis_path.as_ref())
+            .context("Failed to open .lis file")?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+
+        let mut metadata = None;
+        let mut sizes = HashMap::new();
+        let mut desktop_size = 0;
+        for entry in archive.entries().context("Failed to read .lis archive")? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if path == Path::new("metadata.toml") {
+                let mut metadata_content = String::new();
+                entry.read_to_string(&mut metadata_content)?;
+                metadata = Some(Metadata::from_toml(&metadata_content)?);
+            } else if !entry.header().entry_type().is_file() {
+                continue;
+            } else if let Ok(relative) = path.strip_prefix("application") {
+                sizes.insert(relative.to_path_buf(), entry.size());
+            } else if path.starts_with("install") && path.extension().is_some_and(|e| e == "desktop") {
+                desktop_size += entry.size();
+            }
+        }
+
+        let metadata = metadata.context("metadata.toml not found in .lis file")?;
+        let files_size: u64 = metadata.files.iter()
+            .filter_map(|f| sizes.get(Path::new(&f.source)))
+            .sum();
+
+        Ok(files_size + desktop_size)
+    }
+
     /// Extraer y leer un archivo .lis
     pub fn extract<P: AsRef<Path>>(lis_path: P, output_dir: P) -> Result<Metadata> {
         let file = File::open(lis_path.as_ref())
@@ -91,4 +326,105 @@ impl LisPackage {
 
         Metadata::from_toml(&metadata_content)
     }
+    /// Listar las entradas del paquete con el SHA-256 de cada archivo,
+    /// leyendo el gzip en streaming sin extraer nada a disco
+    pub fn entries<P: AsRef<Path>>(lis_path: P) -> Result<Vec<PackageEntry>> {
+        let file = File::open(lis_path.as_ref())
+            .context("Failed to open .lis file")?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+
+        let mut entries = Vec::new();
+        for entry in archive.entries().context("Failed to read .lis archive")? {
+            let entry = entry?;
+            let header = entry.header();
+            let kind = match header.entry_type() {
+                EntryType::Regular => EntryKind::File,
+                EntryType::Directory => EntryKind::Directory,
+                EntryType::Symlink => EntryKind::Symlink,
+                _ => EntryKind::Other,
+            };
+            let path = entry.path()?.to_string_lossy().to_string();
+            let mode = header.mode()?;
+            let size = if kind == EntryKind::File { entry.size() } else { 0 };
+            let link_target = entry.link_name()?.map(|target| target.to_string_lossy().to_string());
+            let sha256 = match kind {
+                EntryKind::File => Some(sha256_reader(entry).with_context(|| format!("Failed to read {}", path))?),
+                _ => None,
+            };
+            entries.push(PackageEntry { path, kind, mode, size, sha256, link_target });
+        }
+        Ok(entries)
+    }
+
+    /// Copiar el contenido de un archivo del paquete a `writer` sin extraerlo a disco.
+    /// Devuelve el número de bytes copiados.
+    pub fn copy_file<P: AsRef<Path>>(lis_path: P, archive_path: &str, writer: &mut impl Write) -> Result<u64> {
+        let file = File::open(lis_path.as_ref())
+            .context("Failed to open .lis file")?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+
+        for entry in archive.entries().context("Failed to read .lis archive")? {
+            let mut entry = entry?;
+            if entry.path()?.as_ref() != Path::new(archive_path) {
+                continue;
+            }
+            if entry.header().entry_type() != EntryType::Regular {
+                anyhow::bail!("{} is not a file", archive_path);
+            }
+            return io::copy(&mut entry, writer).with_context(|| format!("Failed to read {}", archive_path));
+        }
+
+        anyhow::bail!("{} not found in .lis file", archive_path)
+    }
+
+    /// Extraer una sola ruta del paquete (un archivo, o un directorio con su
+    /// contenido) dentro de `output_dir`, con su último componente como nombre.
+    /// Devuelve las rutas escritas.
+    pub fn extract_path<P: AsRef<Path>>(lis_path: P, archive_path: &str, output_dir: &Path) -> Result<Vec<PathBuf>> {
+        let target = Path::new(archive_path.trim_end_matches('/'));
+        if !is_safe_relative_path(target) {
+            anyhow::bail!("Invalid path '{}'", archive_path);
+        }
+        let parent = target.parent().unwrap_or(Path::new(""));
+
+        let file = File::open(lis_path.as_ref())
+            .context("Failed to open .lis file")?;
+        let mut archive = Archive::new(GzDecoder::new(file));
+
+        let mut written = Vec::new();
+        for entry in archive.entries().context("Failed to read .lis archive")? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if !path.starts_with(target) {
+                continue;
+            }
+            let relative = path.strip_prefix(parent).unwrap_or(&path);
+            if !is_safe_relative_path(relative) {
+                anyhow::bail!("Unsafe path '{}' in package", path.display());
+            }
+
+            // Nunca escribir a través de un enlace simbólico extraído antes
+            let dest = output_dir.join(relative);
+            let mut ancestor = dest.parent();
+            while let Some(dir) = ancestor.filter(|dir| dir.starts_with(output_dir) && *dir != output_dir) {
+                if fs::symlink_metadata(dir).is_ok_and(|m| m.file_type().is_symlink()) {
+                    anyhow::bail!("Refusing to extract {} through a symbolic link", path.display());
+                }
+                ancestor = dir.parent();
+            }
+
+            if let Some(dir) = dest.parent() {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            entry.unpack(&dest)
+                .with_context(|| format!("Failed to extract {}", path.display()))?;
+            written.push(dest);
+        }
+
+        if written.is_empty() {
+            anyhow::bail!("{} not found in .lis file", archive_path);
+        }
+        Ok(written)
+    }
 }
@@ -0,0 +1,26 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of everything `reader` yields, in hex
+pub fn sha256_reader(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// SHA-256 of a file, in hex
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    sha256_reader(file).with_context(|| format!("Failed to read {}", path.display()))
+}
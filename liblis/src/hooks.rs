@@ -0,0 +1,224 @@
+use std::fmt;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::metadata::HooksInfo;
+
+/// Largest amount of output kept from a hook
+const MAX_OUTPUT: usize = 1024 * 1024;
+
+/// PATH given to hooks; nothing is inherited from the caller's environment
+const HOOK_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// The points where a package can run a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    PreInstall,
+    PostInstall,
+    PreUninstall,
+    PostUninstall,
+}
+
+impl HookKind {
+    pub const ALL: [HookKind; 4] = [
+        HookKind::PreInstall,
+        HookKind::PostInstall,
+        HookKind::PreUninstall,
+        HookKind::PostUninstall,
+    ];
+
+    /// Name used in metadata.toml and for the stored scripts
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookKind::PreInstall => "pre_install",
+            HookKind::PostInstall => "post_install",
+            HookKind::PreUninstall => "pre_uninstall",
+            HookKind::PostUninstall => "post_uninstall",
+        }
+    }
+
+    /// Human readable description of when the hook runs
+    pub fn description(&self) -> &'static str {
+        match self {
+            HookKind::PreInstall => "Runs before any file is installed",
+            HookKind::PostInstall => "Runs after all files are installed",
+            HookKind::PreUninstall => "Runs before the application is removed",
+            HookKind::PostUninstall => "Runs after the application is removed",
+        }
+    }
+
+    /// Whether the script has to be kept after installation to be run on uninstall
+    pub fn runs_on_uninstall(&self) -> bool {
+        matches!(self, HookKind::PreUninstall | HookKind::PostUninstall)
+    }
+}
+
+impl fmt::Display for HookKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl HooksInfo {
+    /// Script of a hook, relative to install/
+    pub fn script(&self, kind: HookKind) -> Option<&str> {
+        match kind {
+            HookKind::PreInstall => self.pre_install.as_deref(),
+            HookKind::PostInstall => self.post_install.as_deref(),
+            HookKind::PreUninstall => self.pre_uninstall.as_deref(),
+            HookKind::PostUninstall => self.post_uninstall.as_deref(),
+        }
+    }
+
+    /// Every hook defined by the package with its script
+    pub fn defined(&self) -> Vec<(HookKind, &str)> {
+        HookKind::ALL.iter()
+            .filter_map(|kind| self.script(*kind).map(|script| (*kind, script)))
+            .collect()
+    }
+}
+
+/// Variables given to a hook
+#[derive(Debug, Clone)]
+pub struct HookEnvironment {
+    pub prefix: String,
+    pub app_id: String,
+    pub version: String,
+    /// Version being replaced, if this is an upgrade
+    pub old_version: Option<String>,
+    /// System-wide hooks run as root and don't get the caller's HOME
+    pub for_all_users: bool,
+}
+
+/// A hook that did not finish successfully
+#[derive(Debug)]
+pub struct HookError {
+    pub kind: HookKind,
+    pub reason: String,
+    /// Everything the script wrote to stdout and stderr
+    pub output: String,
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The {} hook {}", self.kind, self.reason)?;
+
+        // The end of the output usually explains the failure
+        let lines: Vec<&str> = self.output.lines().filter(|l| !l.trim().is_empty()).collect();
+        if !lines.is_empty() {
+            write!(f, ":\n{}", lines[lines.len().saturating_sub(5)..].join("\n"))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// Run a hook script with `/bin/sh` and return its output.
+///
+/// The script gets a clean environment (`PATH`, `PREFIX`, `APP_ID`, `VERSION`,
+/// `OLD_VERSION`, `HOOK`), no stdin and the folder it lives in as working
+/// directory. It runs in its own process group, which is killed when the
+/// timeout expires, when `cancel` is set, and once the script exits so no
+/// process outlives it.
+///
+/// This is not a sandbox: the script can do anything the caller can, which
+/// for system-wide installations means anything root can.
+pub fn run_hook(
+    script: &Path,
+    kind: HookKind,
+    env: &HookEnvironment,
+    timeout: Duration,
+    cancel: &AtomicBool,
+) -> Result<String, HookError> {
+    let error = |reason: String, output: String| HookError { kind, reason, output };
+
+    let work_dir = script.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("/"));
+    let home = if env.for_all_users {
+        work_dir.clone()
+    } else {
+        std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| work_dir.clone())
+    };
+
+    let (mut reader, writer) = std::io::pipe()
+        .map_err(|e| error(format!("could not be started: {}", e), String::new()))?;
+    let writer_err = writer.try_clone()
+        .map_err(|e| error(format!("could not be started: {}", e), String::new()))?;
+
+    let mut command = Command::new("/bin/sh");
+    command
+        .arg(script)
+        .current_dir(&work_dir)
+        .env_clear()
+        .env("PATH", HOOK_PATH)
+        .env("HOME", home)
+        .env("LANG", "C.UTF-8")
+        .env("PREFIX", &env.prefix)
+        .env("APP_ID", &env.app_id)
+        .env("VERSION", &env.version)
+        .env("OLD_VERSION", env.old_version.as_deref().unwrap_or_default())
+        .env("HOOK", kind.name())
+        .stdin(Stdio::null())
+        .stdout(writer)
+        .stderr(writer_err)
+        .process_group(0);
+
+    let mut child = command.spawn()
+        .map_err(|e| error(format!("could not be started: {}", e), String::new()))?;
+    // Our copies of the pipe must be closed for the reader to see the end of the output
+    drop(command);
+
+    let (output_sender, output_receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 8192];
+        while let Ok(read) = reader.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            if output.len() < MAX_OUTPUT {
+                output.extend_from_slice(&buffer[..read]);
+            }
+        }
+        let _ = output_sender.send(output);
+    });
+
+    let kill_group = |pid: u32| unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    };
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if cancel.load(Ordering::Relaxed) => break Err("was cancelled".to_string()),
+            Ok(None) if Instant::now() >= deadline => {
+                break Err(format!("timed out after {} seconds", timeout.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(100)),
+            Err(e) => break Err(format!("could not be waited for: {}", e)),
+        }
+    };
+
+    kill_group(child.id());
+    let _ = child.wait();
+
+    // A process that left the group may still hold the pipe open; don't wait for it
+    let mut output = output_receiver.recv_timeout(Duration::from_secs(2)).unwrap_or_default();
+    output.truncate(MAX_OUTPUT);
+    let output = String::from_utf8_lossy(&output).into_owned();
+
+    match status {
+        Ok(status) if status.success() => Ok(output),
+        Ok(status) => match status.code() {
+            Some(code) => Err(error(format!("failed with exit code {}", code), output)),
+            None => Err(error("was killed by a signal".to_string(), output)),
+        },
+        Err(reason) => Err(error(reason, output)),
+    }
+}
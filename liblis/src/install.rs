@@ -0,0 +1,996 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use tar::{Archive, EntryType};
+
+use crate::hash::sha256_file;
+use crate::hooks::{self, HookEnvironment, HookKind};
+use crate::manifest::{BackupFile, InstallationManifest};
+use crate::platform;
+use crate::relations::{self, PackageRelations};
+use crate::version;
+use crate::metadata::{FileEntry, Metadata};
+
+/// Helper functions to build FHS-compliant installation paths
+pub mod paths {
+    use std::path::PathBuf;
+
+    /// Get the bin directory for the given prefix
+    pub fn bin_dir(prefix: &str) -> PathBuf {
+        PathBuf::from(prefix).join("bin")
+    }
+
+    /// Get the share directory for the given prefix
+    pub fn share_dir(prefix: &str) -> PathBuf {
+        PathBuf::from(prefix).join("share")
+    }
+
+    /// Get the applications directory (for .desktop files)
+    pub fn applications_dir(prefix: &str) -> PathBuf {
+        share_dir(prefix).join("applications")
+    }
+
+    /// Get the app-specific data directory
+    pub fn app_data_dir(prefix: &str, app_id: &str) -> PathBuf {
+        share_dir(prefix).join(app_id)
+    }
+
+    /// Get the icons directory
+    pub fn icons_dir(prefix: &str) -> PathBuf {
+        share_dir(prefix).join("icons").join("hicolor")
+    }
+
+    /// Get the metainfo directory
+    pub fn metainfo_dir(prefix: &str) -> PathBuf {
+        share_dir(prefix).join("metainfo")
+    }
+
+    /// Expand ~ to home directory
+    pub fn expand_home(path: &str) -> String {
+        if let Some(rest) = path.strip_prefix("~/") {
+            if let Some(home) = std::env::var_os("HOME") {
+                return PathBuf::from(home).join(rest).to_string_lossy().to_string();
+            }
+        }
+        path.to_string()
+    }
+}
+
+/// Check that a path from the package is relative and stays inside its root
+pub fn is_safe_relative_path(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Check that an app_id can be used as a file name (manifest, data directory)
+pub fn is_safe_app_id(app_id: &str) -> bool {
+    !app_id.is_empty()
+        && !app_id.starts_with('.')
+        && app_id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Check that an app_id is a reverse-DNS name as the freedesktop specifications
+/// require (e.g., "com.example.App"): two or more elements separated by dots,
+/// made of letters, digits, '_' and '-', none starting with a digit
+pub fn validate_app_id(app_id: &str) -> Result<()> {
+    if app_id.is_empty() {
+        anyhow::bail!("The app ID is empty");
+    }
+    if app_id.len() > 255 {
+        anyhow::bail!("App ID '{}' is longer than 255 characters", app_id);
+    }
+    let elements: Vec<&str> = app_id.split('.').collect();
+    if elements.len() < 2 {
+        anyhow::bail!("App ID '{}' must be a reverse-DNS name such as com.example.App", app_id);
+    }
+    for element in elements {
+        if element.is_empty() {
+            anyhow::bail!("App ID '{}' has an empty element", app_id);
+        }
+        if !element.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-')) {
+            anyhow::bail!("App ID '{}' can only have letters, digits, '_', '-' and dots", app_id);
+        }
+        if element.starts_with(|c: char| c.is_ascii_digit()) {
+            anyhow::bail!("Element '{}' of app ID '{}' starts with a digit", element, app_id);
+        }
+    }
+    Ok(())
+}
+
+/// Parse a Unix permission string such as "755". Setuid/setgid/sticky bits are rejected.
+pub fn parse_permissions(permissions: &str) -> Result<u32> {
+    let mode = u32::from_str_radix(permissions, 8)
+        .with_context(|| format!("Invalid permissions '{}'", permissions))?;
+    if mode > 0o777 {
+        anyhow::bail!("Permissions '{}' are not allowed", permissions);
+    }
+    Ok(mode)
+}
+
+/// Progress report of a running installation
+#[derive(Debug, Clone)]
+pub struct InstallProgress {
+    /// Bytes written so far
+    pub bytes_done: u64,
+    /// Total bytes to write
+    pub bytes_total: u64,
+    /// Current step, for display
+    pub message: String,
+}
+
+impl InstallProgress {
+    /// Completed fraction between 0.0 and 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.bytes_total == 0 {
+            0.0
+        } else {
+            (self.bytes_done as f64 / self.bytes_total as f64).min(1.0)
+        }
+    }
+}
+
+/// Error returned when an installation is cancelled. Everything written so far is rolled back.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Installation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// What the installation needs to know about a validated package
+struct ScannedPackage {
+    metadata: Metadata,
+    /// Size of each file under application/
+    sizes: HashMap<PathBuf, u64>,
+    /// Whether install/ ships a desktop entry
+    has_desktop_file: bool,
+}
+
+impl ScannedPackage {
+    /// Every path the package writes, relative to the prefix and absolute
+    fn destinations(&self, prefix: &str) -> Vec<(String, PathBuf)> {
+        let mut destinations = Vec::new();
+        if self.has_desktop_file {
+            let relative = format!("share/applications/{}.desktop", self.metadata.package.app_id);
+            destinations.push((relative.clone(), Path::new(prefix).join(relative)));
+        }
+        for file_entry in &self.metadata.files {
+            let path = Path::new(prefix).join(&file_entry.destination);
+            if !destinations.iter().any(|(_, p)| *p == path) {
+                destinations.push((file_entry.destination.clone(), path));
+            }
+        }
+        destinations
+    }
+}
+
+/// Read the whole package once, checking every entry
+fn scan_package(lis_path: &Path) -> Result<ScannedPackage> {
+    let file = File::open(lis_path).context("Failed to open .lis file")?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+
+    let mut metadata = None;
+    let mut application_files = HashMap::new();
+    let mut install_files = HashSet::new();
+    let mut has_desktop_file = false;
+
+    for entry in archive.entries().context("Failed to read .lis archive")? {
+        let mut entry = entry.context("Corrupted .lis archive")?;
+        let path = entry.path()?.into_owned();
+
+        if !is_safe_relative_path(&path) {
+            anyhow::bail!("Unsafe path in package: {}", path.display());
+        }
+
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Directory => {}
+            other => anyhow::bail!("Unsupported entry type {:?} for {}", other, path.display()),
+        }
+
+        if path == Path::new("metadata.toml") {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            metadata = Some(Metadata::from_toml(&content).context("Invalid metadata.toml")?);
+        } else if let Ok(relative) = path.strip_prefix("application") {
+            if entry.header().entry_type() == EntryType::Regular {
+                application_files.insert(relative.to_path_buf(), entry.size());
+            }
+        } else if let Ok(relative) = path.strip_prefix("install") {
+            if entry.header().entry_type() == EntryType::Regular {
+                has_desktop_file |= relative.extension().and_then(|e| e.to_str()) == Some("desktop");
+                install_files.insert(relative.to_path_buf());
+            }
+        }
+    }
+
+    let metadata = metadata.context("metadata.toml not found in .lis file")?;
+
+    if !is_safe_app_id(&metadata.package.app_id) {
+        anyhow::bail!("Invalid app_id '{}'", metadata.package.app_id);
+    }
+    platform::check_compatible(&metadata.package)?;
+    PackageRelations::from_metadata(&metadata)?;
+
+    // A delta package takes the unchanged files from the installation instead
+    let unchanged = metadata.delta.as_ref().map(|delta| &delta.unchanged);
+    for file_entry in &metadata.files {
+        let kept = unchanged.is_some_and(|unchanged| unchanged.contains_key(&file_entry.destination));
+        if !application_files.contains_key(Path::new(&file_entry.source)) && !kept {
+            anyhow::bail!("File '{}' is listed in metadata but missing from the package", file_entry.source);
+        }
+        if !is_safe_relative_path(Path::new(&file_entry.destination)) {
+            anyhow::bail!("Unsafe destination '{}'", file_entry.destination);
+        }
+        if let Some(permissions) = &file_entry.permissions {
+            parse_permissions(permissions)?;
+        }
+    }
+
+    if let Some(delta) = &metadata.delta {
+        version::Version::parse(&delta.base_version).context("Invalid delta base version")?;
+        for destination in delta.unchanged.keys() {
+            if !metadata.files.iter().any(|f| f.destination == *destination) {
+                anyhow::bail!("Unchanged file '{}' is not listed in metadata", destination);
+            }
+        }
+        for destination in &delta.removed {
+            if !is_safe_relative_path(Path::new(destination)) {
+                anyhow::bail!("Unsafe removed file '{}'", destination);
+            }
+            if metadata.files.iter().any(|f| f.destination == *destination) {
+                anyhow::bail!("Removed file '{}' is also listed in metadata", destination);
+            }
+        }
+    }
+
+    for (kind, script) in metadata.hooks.defined() {
+        if !is_safe_relative_path(Path::new(script)) || !install_files.contains(Path::new(script)) {
+            anyhow::bail!("The {} hook '{}' is missing from the package", kind, script);
+        }
+    }
+
+    Ok(ScannedPackage { metadata, sizes: application_files, has_desktop_file })
+}
+
+/// Extract the hook scripts of a package to a new directory only root (or the
+/// installing user) can write to. Returns `None` when the package has no hooks.
+fn extract_hooks(lis_path: &Path, metadata: &Metadata) -> Result<Option<PathBuf>> {
+    let hooks = metadata.hooks.defined();
+    if hooks.is_empty() {
+        return Ok(None);
+    }
+
+    let hooks_dir = std::env::temp_dir().join(format!("obision-hooks-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&hooks_dir);
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&hooks_dir)
+        .with_context(|| format!("Failed to create {}", hooks_dir.display()))?;
+
+    let file = File::open(lis_path).context("Failed to open .lis file")?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+    for entry in archive.entries().context("Failed to read .lis archive")? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let Ok(relative) = path.strip_prefix("install") else {
+            continue;
+        };
+        // The same script may be used for several hooks
+        let kinds: Vec<HookKind> = hooks.iter()
+            .filter(|(_, script)| Path::new(script) == relative)
+            .map(|(kind, _)| *kind)
+            .collect();
+        let Some((first, others)) = kinds.split_first() else {
+            continue;
+        };
+
+        let dest = hooks_dir.join(first.name());
+        let mut out = File::create(&dest)
+            .with_context(|| format!("Failed to create {}", dest.display()))?;
+        io::copy(&mut entry, &mut out)
+            .with_context(|| format!("Failed to extract the {} hook", first))?;
+        for other in others {
+            fs::copy(&dest, hooks_dir.join(other.name()))
+                .with_context(|| format!("Failed to extract the {} hook", other))?;
+        }
+    }
+
+    Ok(Some(hooks_dir))
+}
+
+/// Run one hook if the package has it, keeping its output in the registry logs
+fn run_package_hook(
+    hooks_dir: &Path,
+    kind: HookKind,
+    env: &HookEnvironment,
+    timeout: Duration,
+    cancel: &AtomicBool,
+) -> Result<()> {
+    let script = hooks_dir.join(kind.name());
+    if !script.exists() {
+        return Ok(());
+    }
+
+    let result = hooks::run_hook(&script, kind, env, timeout, cancel);
+    let output = match &result {
+        Ok(output) => output,
+        Err(e) => &e.output,
+    };
+    let log_path = InstallationManifest::hook_log_path(&env.app_id, kind.name(), env.for_all_users);
+    if let Some(parent) = log_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::write(&log_path, output);
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) if cancel.load(Ordering::Relaxed) => Err(Cancelled.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Validate the structure of a .lis package without trusting any previous check.
+/// Returns the package metadata.
+pub fn validate_package(lis_path: &Path) -> Result<Metadata> {
+    scan_package(lis_path).map(|scanned| scanned.metadata)
+}
+
+/// How installing a package changes the installed version of its application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallKind {
+    New,
+    Upgrade { from: String },
+    Reinstall,
+    Downgrade { from: String },
+}
+
+/// Compare a package with the version of its application installed in one registry
+pub fn install_kind(metadata: &Metadata, for_all_users: bool) -> InstallKind {
+    let Ok(installed) = InstallationManifest::load(&metadata.package.app_id, for_all_users) else {
+        return InstallKind::New;
+    };
+    match version::compare(&metadata.package.version, &installed.version) {
+        std::cmp::Ordering::Greater => InstallKind::Upgrade { from: installed.version },
+        std::cmp::Ordering::Equal => InstallKind::Reinstall,
+        std::cmp::Ordering::Less => InstallKind::Downgrade { from: installed.version },
+    }
+}
+
+/// Check that the version a delta package applies to is installed into
+/// `prefix` in one registry. Returns the manifest of that installation, or
+/// `None` for a full package.
+pub fn check_delta_base(metadata: &Metadata, prefix: &str, for_all_users: bool) -> Result<Option<InstallationManifest>> {
+    let Some(delta) = &metadata.delta else {
+        return Ok(None);
+    };
+    let name = &metadata.package.application_name;
+    let Ok(installed) = InstallationManifest::load(&metadata.package.app_id, for_all_users) else {
+        anyhow::bail!("This update only applies to {} {}, which is not installed", name, delta.base_version);
+    };
+    if !version::compare(&installed.version, &delta.base_version).is_eq() {
+        anyhow::bail!(
+            "This update only applies to {} {}, but version {} is installed",
+            name, delta.base_version, installed.version
+        );
+    }
+    if installed.prefix != prefix {
+        anyhow::bail!("{} is installed in {}, so the update must be installed there too", name, installed.prefix);
+    }
+    for destination in &delta.removed {
+        let path = Path::new(prefix).join(destination).to_string_lossy().to_string();
+        if !installed.installed_files.contains(&path) {
+            anyhow::bail!("This update removes '{}', which {} {} didn't install", destination, name, installed.version);
+        }
+    }
+    Ok(Some(installed))
+}
+
+/// A destination of the package that already belongs to someone else
+#[derive(Debug, Clone)]
+pub struct FileConflict {
+    /// Destination relative to the prefix (e.g., "bin/foo")
+    pub destination: String,
+    /// Absolute path of the destination
+    pub path: PathBuf,
+    /// app_id of the package that installed the file; `None` when Obision doesn't manage it
+    pub owner: Option<String>,
+}
+
+impl fmt::Display for FileConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.owner {
+            Some(owner) => write!(f, "`{}` is owned by {}", self.destination, owner),
+            None => write!(f, "`{}` already exists and is not managed by Obision", self.destination),
+        }
+    }
+}
+
+/// What to do with files that conflict with other packages or the system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictResolution {
+    /// Don't install anything
+    #[default]
+    Abort,
+    /// Install over them, keeping a backup that is restored on uninstall
+    Overwrite,
+    /// Keep them and don't install the package's version
+    Skip,
+}
+
+impl ConflictResolution {
+    /// Name used on the helper's command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictResolution::Abort => "abort",
+            ConflictResolution::Overwrite => "overwrite",
+            ConflictResolution::Skip => "skip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "abort" => Some(ConflictResolution::Abort),
+            "overwrite" => Some(ConflictResolution::Overwrite),
+            "skip" => Some(ConflictResolution::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// Check every destination against the installed manifests and the files already on disk.
+/// Files of a previous installation of the same application are not conflicts.
+fn conflicts_for(scanned: &ScannedPackage, prefix: &str) -> Vec<FileConflict> {
+    let app_id = &scanned.metadata.package.app_id;
+
+    let mut owners: HashMap<String, String> = HashMap::new();
+    let mut own_files: HashSet<String> = HashSet::new();
+    for manifest in InstallationManifest::list_installed(false).into_iter().chain(InstallationManifest::list_installed(true)) {
+        if manifest.app_id == *app_id {
+            own_files.extend(manifest.installed_files);
+        } else {
+            for file in manifest.installed_files {
+                owners.insert(file, manifest.app_id.clone());
+            }
+        }
+    }
+
+    scanned.destinations(prefix)
+        .into_iter()
+        .filter_map(|(destination, path)| {
+            let key = path.to_string_lossy().to_string();
+            let owner = match owners.get(&key) {
+                Some(owner) => Some(owner.clone()),
+                None if own_files.contains(&key) || fs::symlink_metadata(&path).is_err() => return None,
+                None => None,
+            };
+            Some(FileConflict { destination, path, owner })
+        })
+        .collect()
+}
+
+/// Find the files a package would overwrite when installed into `prefix`
+pub fn find_conflicts(lis_path: &Path, prefix: &str) -> Result<Vec<FileConflict>> {
+    let scanned = scan_package(lis_path)?;
+    Ok(conflicts_for(&scanned, prefix))
+}
+
+/// A file written next to its destination, renamed into place once everything succeeded
+struct StagedFile {
+    staged: PathBuf,
+    dest: PathBuf,
+}
+
+/// Files and directories written by a running installation, so it can be undone
+#[derive(Default)]
+struct Transaction {
+    staged: Vec<StagedFile>,
+    created_directories: Vec<PathBuf>,
+    /// Conflicting destinations that are not installed
+    skipped: HashSet<PathBuf>,
+    /// Conflicting destinations moved aside before being replaced
+    backed_up: HashSet<PathBuf>,
+    backups: Vec<BackupFile>,
+    /// Files of the previous installation replaced by the new ones, as
+    /// (destination, moved-aside path), deleted once the installation is registered
+    replaced: Vec<(PathBuf, PathBuf)>,
+    /// Number of staged files moved into place
+    committed: usize,
+}
+
+impl Transaction {
+    /// Create `dir` and its missing parents, remembering the new ones
+    fn create_dirs(&mut self, dir: &Path) -> Result<()> {
+        let mut missing = Vec::new();
+        let mut current = Some(dir);
+        while let Some(d) = current {
+            if d.exists() {
+                break;
+            }
+            missing.push(d.to_path_buf());
+            current = d.parent();
+        }
+
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+
+        self.created_directories.extend(missing.into_iter().rev());
+        Ok(())
+    }
+
+    /// Write a file to a temporary name beside `dest`
+    fn stage_file(
+        &mut self,
+        reader: &mut impl Read,
+        dest: &Path,
+        mode: u32,
+        cancel: &AtomicBool,
+        mut on_bytes: impl FnMut(u64),
+    ) -> Result<()> {
+        if let Some(parent) = dest.parent() {
+            self.create_dirs(parent)?;
+        }
+
+        let file_name = dest.file_name().context("Invalid destination")?.to_string_lossy();
+        let staged = dest.with_file_name(format!(".{}.lis-new", file_name));
+        self.staged.push(StagedFile { staged: staged.clone(), dest: dest.to_path_buf() });
+
+        let mut out = File::create(&staged)
+            .with_context(|| format!("Failed to create {}", staged.display()))?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Cancelled.into());
+            }
+            let read = reader.read(&mut buffer)
+                .context("Failed to read from package")?;
+            if read == 0 {
+                break;
+            }
+            out.write_all(&buffer[..read])
+                .with_context(|| format!("Failed to write {}", dest.display()))?;
+            on_bytes(read as u64);
+        }
+
+        fs::set_permissions(&staged, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set permissions on {}", dest.display()))?;
+        Ok(())
+    }
+
+    /// Move every staged file into place, backing up the conflicting files to
+    /// overwrite and moving aside the ones of the previous installation
+    fn commit(&mut self) -> Result<()> {
+        for file in &self.staged {
+            if fs::symlink_metadata(&file.dest).is_ok() {
+                if self.backed_up.contains(&file.dest) {
+                    let backup = backup_path(&file.dest);
+                    fs::rename(&file.dest, &backup)
+                        .with_context(|| format!("Failed to back up {}", file.dest.display()))?;
+                    self.backups.push(BackupFile {
+                        original: file.dest.to_string_lossy().to_string(),
+                        backup: backup.to_string_lossy().to_string(),
+                    });
+                } else {
+                    let file_name = file.dest.file_name().unwrap_or_default().to_string_lossy();
+                    let aside = file.dest.with_file_name(format!(".{}.lis-old", file_name));
+                    fs::rename(&file.dest, &aside)
+                        .with_context(|| format!("Failed to replace {}", file.dest.display()))?;
+                    self.replaced.push((file.dest.clone(), aside));
+                }
+            }
+            fs::rename(&file.staged, &file.dest)
+                .with_context(|| format!("Failed to install {}", file.dest.display()))?;
+            self.committed += 1;
+        }
+        Ok(())
+    }
+
+    /// Remove everything written so far, even once committed, and put back
+    /// the files it replaced
+    fn rollback(&self) {
+        for (n, file) in self.staged.iter().enumerate() {
+            let written = if n < self.committed { &file.dest } else { &file.staged };
+            let _ = fs::remove_file(written);
+        }
+        for (dest, aside) in &self.replaced {
+            let _ = fs::rename(aside, dest);
+        }
+        for backup in &self.backups {
+            let _ = fs::rename(&backup.backup, &backup.original);
+        }
+        for dir in self.created_directories.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+
+    /// Delete the replaced files of the previous installation, once the new one is registered
+    fn finish(&self) {
+        for (_, aside) in &self.replaced {
+            let _ = fs::remove_file(aside);
+        }
+    }
+}
+
+/// First free `<file>.lis-backup`, `<file>.lis-backup.2`, ... beside `path`
+fn backup_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut backup = path.with_file_name(format!("{}.lis-backup", file_name));
+    let mut n = 2;
+    while fs::symlink_metadata(&backup).is_ok() {
+        backup = path.with_file_name(format!("{}.lis-backup.{}", file_name, n));
+        n += 1;
+    }
+    backup
+}
+
+/// Perform the actual installation of a .lis package into `prefix`.
+///
+/// The package is validated again before anything is written. Files are staged
+/// beside their destination and only moved into place once all of them were
+/// written, so a failure or a cancellation leaves the previous state intact.
+/// The package's pre_install hook runs before the first file is written and
+/// its post_install hook once the installation is registered.
+/// Files owned by other packages or not managed by Obision are handled as
+/// `on_conflict` says. The packages it requires must already be installed.
+pub fn perform_installation(
+    lis_path: &Path,
+    prefix: &str,
+    for_all_users: bool,
+    on_conflict: ConflictResolution,
+    cancel: &AtomicBool,
+    mut progress_callback: impl FnMut(InstallProgress),
+) -> Result<InstallationManifest> {
+    if !Path::new(prefix).is_absolute() {
+        anyhow::bail!("Installation prefix must be an absolute path: {}", prefix);
+    }
+
+    progress_callback(InstallProgress { bytes_done: 0, bytes_total: 0, message: "Verifying package...".to_string() });
+    let scanned = scan_package(lis_path)?;
+    let metadata = &scanned.metadata;
+    let app_id = &metadata.package.app_id;
+
+    progress_callback(InstallProgress { bytes_done: 0, bytes_total: 0, message: "Checking for conflicts...".to_string() });
+    let problems = relations::check_relations(&PackageRelations::from_metadata(metadata)?, &relations::installed_packages());
+    if !problems.is_empty() {
+        let list: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+        anyhow::bail!("{} can't be installed:\n{}", metadata.package.application_name, list.join("\n"));
+    }
+
+    check_delta_base(metadata, prefix, for_all_users)?;
+
+    let conflicts = conflicts_for(&scanned, prefix);
+    let mut transaction = Transaction::default();
+    match on_conflict {
+        ConflictResolution::Abort if !conflicts.is_empty() => {
+            let list: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+            anyhow::bail!("The installation would overwrite existing files:\n{}", list.join("\n"));
+        }
+        ConflictResolution::Abort => {}
+        ConflictResolution::Overwrite => transaction.backed_up = conflicts.into_iter().map(|c| c.path).collect(),
+        ConflictResolution::Skip => transaction.skipped = conflicts.into_iter().map(|c| c.path).collect(),
+    }
+
+    let hooks_dir = extract_hooks(lis_path, metadata)?;
+    let result = (|| {
+        let previous = InstallationManifest::load(app_id, for_all_users).ok();
+        let hook_env = HookEnvironment {
+            prefix: prefix.to_string(),
+            app_id: app_id.clone(),
+            version: metadata.package.version.clone(),
+            old_version: previous.as_ref().map(|p| p.version.clone()),
+            for_all_users,
+        };
+        let hook_timeout = Duration::from_secs(metadata.hooks.timeout);
+
+        if let (Some(hooks_dir), Some(_)) = (&hooks_dir, &metadata.hooks.pre_install) {
+            progress_callback(InstallProgress { bytes_done: 0, bytes_total: 0, message: "Running pre_install hook...".to_string() });
+            run_package_hook(hooks_dir, HookKind::PreInstall, &hook_env, hook_timeout, cancel)?;
+        }
+
+        let result = stage_package(metadata, &scanned.sizes, lis_path, prefix, cancel, &mut transaction, &mut progress_callback)
+            .and_then(|bytes_total| {
+                stage_unchanged(metadata, &scanned.sizes, prefix, bytes_total, cancel, &mut transaction, &mut progress_callback)?;
+                if cancel.load(Ordering::Relaxed) {
+                    return Err(Cancelled.into());
+                }
+                progress_callback(InstallProgress { bytes_done: bytes_total, bytes_total, message: "Registering installation...".to_string() });
+                transaction.commit()?;
+                Ok(bytes_total)
+            });
+
+        let bytes_total = match result {
+            Ok(bytes_total) => bytes_total,
+            Err(e) => {
+                transaction.rollback();
+                return Err(e);
+            }
+        };
+
+        // What the previous installation overwrote and created is restored and
+        // removed on uninstall, also when it used another prefix
+        let mut manifest = InstallationManifest::new(metadata, prefix.to_string());
+        if let Some(previous) = &previous {
+            manifest.created_directories = previous.created_directories.clone();
+            manifest.backups = previous.backups.clone();
+        }
+        manifest.backups.extend(transaction.backups.iter().cloned());
+        for dir in &transaction.created_directories {
+            let dir = dir.to_string_lossy().to_string();
+            if !manifest.created_directories.contains(&dir) {
+                manifest.created_directories.push(dir);
+            }
+        }
+        for file in &transaction.staged {
+            let dest = file.dest.to_string_lossy().to_string();
+            if !manifest.installed_files.contains(&dest) {
+                manifest.installed_files.push(dest);
+            }
+        }
+
+        // Record the prefix so uninstall and upgrades find the installed files.
+        // Nothing of the previous version is deleted before, so a failure can
+        // still put it back.
+        let registered = manifest.save(for_all_users)
+            .and_then(|()| keep_uninstall_hooks(hooks_dir.as_deref(), app_id, for_all_users));
+        if let Err(e) = registered {
+            transaction.rollback();
+            let _ = match &previous {
+                Some(previous) => previous.save(for_all_users),
+                None => manifest.remove(for_all_users),
+            };
+            return Err(e);
+        }
+        transaction.finish();
+
+        // Files of the previous version that the new one no longer ships: the
+        // ones a delta lists, checked against the base installation, or else
+        // the ones missing from the new manifest
+        if let Some(previous) = &previous {
+            let removed: Vec<PathBuf> = match &metadata.delta {
+                Some(delta) => delta.removed.iter().map(|d| Path::new(prefix).join(d)).collect(),
+                None => previous.installed_files.iter()
+                    .filter(|f| !manifest.installed_files.contains(f))
+                    .map(PathBuf::from)
+                    .collect(),
+            };
+            for old_file in removed {
+                let _ = fs::remove_file(old_file);
+            }
+        }
+
+        // The files are in place now, so the post_install hook can't be cancelled
+        if let (Some(hooks_dir), Some(_)) = (&hooks_dir, &metadata.hooks.post_install) {
+            progress_callback(InstallProgress { bytes_done: bytes_total, bytes_total, message: "Running post_install hook...".to_string() });
+            run_package_hook(hooks_dir, HookKind::PostInstall, &hook_env, hook_timeout, &AtomicBool::new(false))
+                .with_context(|| format!("{} was installed, but its post_install hook failed", metadata.package.application_name))?;
+        }
+
+        progress_callback(InstallProgress { bytes_done: bytes_total, bytes_total, message: "Installation complete!".to_string() });
+        Ok(manifest)
+    })();
+
+    if let Some(hooks_dir) = &hooks_dir {
+        let _ = fs::remove_dir_all(hooks_dir);
+    }
+    result
+}
+
+/// Keep the uninstall hooks of the package, replacing those of a previous
+/// version. They are copied beside the kept ones first, so a failure leaves
+/// the previous version's hooks.
+fn keep_uninstall_hooks(hooks_dir: Option<&Path>, app_id: &str, for_all_users: bool) -> Result<()> {
+    let kept_dir = InstallationManifest::hooks_dir(app_id, for_all_users);
+    let new_dir = kept_dir.with_file_name(format!(".{}.lis-new", app_id));
+    let _ = fs::remove_dir_all(&new_dir);
+
+    let copied = hooks_dir.map_or(Ok(()), |hooks_dir| {
+        for kind in HookKind::ALL.iter().filter(|kind| kind.runs_on_uninstall()) {
+            let script = hooks_dir.join(kind.name());
+            if script.exists() {
+                fs::create_dir_all(&new_dir)
+                    .with_context(|| format!("Failed to create {}", new_dir.display()))?;
+                fs::copy(&script, new_dir.join(kind.name()))
+                    .with_context(|| format!("Failed to keep the {} hook", kind))?;
+            }
+        }
+        Ok(())
+    });
+    if let Err(e) = copied {
+        let _ = fs::remove_dir_all(&new_dir);
+        return Err(e);
+    }
+
+    let _ = fs::remove_dir_all(&kept_dir);
+    if new_dir.exists() {
+        fs::rename(&new_dir, &kept_dir)
+            .with_context(|| format!("Failed to keep the uninstall hooks in {}", kept_dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Write every file of the package to its staged location. Returns the number of bytes written.
+fn stage_package(
+    metadata: &Metadata,
+    sizes: &HashMap<PathBuf, u64>,
+    lis_path: &Path,
+    prefix: &str,
+    cancel: &AtomicBool,
+    transaction: &mut Transaction,
+    progress_callback: &mut impl FnMut(InstallProgress),
+) -> Result<u64> {
+    let app_id = &metadata.package.app_id;
+
+    // Files in application/ may be installed to several destinations
+    let mut destinations: HashMap<&str, Vec<&FileEntry>> = HashMap::new();
+    for file_entry in &metadata.files {
+        destinations.entry(file_entry.source.as_str()).or_default().push(file_entry);
+    }
+
+    let bytes_total: u64 = metadata.files.iter()
+        .map(|f| sizes.get(Path::new(&f.source)).copied().unwrap_or(0))
+        .sum();
+    let mut bytes_done = 0u64;
+
+    let file = File::open(lis_path).context("Failed to open .lis file")?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+
+    for entry in archive.entries().context("Failed to read .lis archive")? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+
+        if let Ok(relative) = path.strip_prefix("install") {
+            // Desktop entry is installed under the app_id so it matches the manifest
+            if relative.extension().and_then(|e| e.to_str()) == Some("desktop") {
+                let dest = paths::applications_dir(prefix).join(format!("{}.desktop", app_id));
+                if !transaction.skipped.contains(&dest) {
+                    transaction.stage_file(&mut entry, &dest, 0o644, cancel, |_| {})?;
+                }
+            }
+        } else if let Ok(relative) = path.strip_prefix("application") {
+            let Some(entries) = relative.to_str().and_then(|r| destinations.get(r)) else {
+                continue;
+            };
+
+            let mut first_staged: Option<PathBuf> = None;
+            for file_entry in entries {
+                let dest = Path::new(prefix).join(&file_entry.destination);
+                if transaction.skipped.contains(&dest) {
+                    continue;
+                }
+                let mode = match &file_entry.permissions {
+                    Some(p) => parse_permissions(p)?,
+                    None => 0o644,
+                };
+                let message = format!("Installing {}", file_entry.destination);
+                let on_bytes = |read: u64| {
+                    bytes_done += read;
+                    progress_callback(InstallProgress { bytes_done, bytes_total, message: message.clone() });
+                };
+
+                match &first_staged {
+                    None => transaction.stage_file(&mut entry, &dest, mode, cancel, on_bytes)?,
+                    Some(src) => transaction.stage_file(&mut File::open(src)?, &dest, mode, cancel, on_bytes)?,
+                }
+                if first_staged.is_none() {
+                    first_staged = transaction.staged.last().map(|f| f.staged.clone());
+                }
+            }
+        }
+    }
+
+    Ok(bytes_total)
+}
+
+/// Copy the files a delta package leaves out from the installed base version,
+/// after checking they still have the content the delta was built against
+fn stage_unchanged(
+    metadata: &Metadata,
+    sizes: &HashMap<PathBuf, u64>,
+    prefix: &str,
+    bytes_total: u64,
+    cancel: &AtomicBool,
+    transaction: &mut Transaction,
+    progress_callback: &mut impl FnMut(InstallProgress),
+) -> Result<()> {
+    let Some(delta) = &metadata.delta else {
+        return Ok(());
+    };
+
+    // Sources shipped in the package were already staged for all their destinations
+    for file_entry in metadata.files.iter().filter(|f| !sizes.contains_key(Path::new(&f.source))) {
+        let dest = Path::new(prefix).join(&file_entry.destination);
+        if transaction.skipped.contains(&dest) {
+            continue;
+        }
+        let expected = delta.unchanged.get(&file_entry.destination)
+            .with_context(|| format!("File '{}' is missing from the package", file_entry.source))?;
+        if sha256_file(&dest)? != *expected {
+            anyhow::bail!(
+                "{} was modified after version {} was installed; install the full package instead",
+                dest.display(), delta.base_version
+            );
+        }
+
+        let mode = match &file_entry.permissions {
+            Some(p) => parse_permissions(p)?,
+            None => 0o644,
+        };
+        let message = format!("Keeping {}", file_entry.destination);
+        progress_callback(InstallProgress { bytes_done: bytes_total, bytes_total, message });
+        let mut installed = File::open(&dest)
+            .with_context(|| format!("Failed to open {}", dest.display()))?;
+        transaction.stage_file(&mut installed, &dest, mode, cancel, |_| {})?;
+    }
+    Ok(())
+}
+
+/// Remove an installed application using its manifest
+pub fn uninstall(app_id: &str, for_all_users: bool) -> Result<InstallationManifest> {
+    let manifest = InstallationManifest::load(app_id, for_all_users)?;
+
+    // Check the installation being removed, not one of the same app in the other registry
+    let mut installed = relations::installed_packages();
+    installed.retain(|p| p.app_id != app_id);
+    installed.push(PackageRelations::from_manifest(&manifest));
+    let dependents = relations::dependents(app_id, &installed);
+    if !dependents.is_empty() {
+        anyhow::bail!("{} is required by {}", manifest.app_name, dependents.join(", "));
+    }
+
+    let hooks_dir = InstallationManifest::hooks_dir(app_id, for_all_users);
+    let hook_env = HookEnvironment {
+        prefix: manifest.prefix.clone(),
+        app_id: app_id.to_string(),
+        version: manifest.version.clone(),
+        old_version: None,
+        for_all_users,
+    };
+    let hook_timeout = Duration::from_secs(manifest.hook_timeout);
+    let no_cancel = AtomicBool::new(false);
+
+    // A failing pre_uninstall hook keeps the application installed
+    run_package_hook(&hooks_dir, HookKind::PreUninstall, &hook_env, hook_timeout, &no_cancel)?;
+
+    for file in &manifest.installed_files {
+        if let Err(e) = fs::remove_file(file) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e).with_context(|| format!("Failed to remove {}", file));
+            }
+        }
+    }
+
+    // Put back what this installation overwrote
+    for backup in &manifest.backups {
+        if Path::new(&backup.backup).exists() {
+            fs::rename(&backup.backup, &backup.original)
+                .with_context(|| format!("Failed to restore {}", backup.original))?;
+        }
+    }
+
+    // Only directories left empty are removed
+    for dir in manifest.created_directories.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+
+    manifest.remove(for_all_users)?;
+
+    let post_uninstall = run_package_hook(&hooks_dir, HookKind::PostUninstall, &hook_env, hook_timeout, &no_cancel);
+    let _ = fs::remove_dir_all(&hooks_dir);
+    post_uninstall
+        .with_context(|| format!("{} was removed, but its post_uninstall hook failed", manifest.app_name))?;
+
+    Ok(manifest)
+}
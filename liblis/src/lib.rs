@@ -1,8 +1,25 @@
 pub mod metadata;
 pub mod format;
 pub mod dependencies;
+pub mod manifest;
+pub mod install;
+pub mod hooks;
+pub mod requirements;
+pub mod platform;
+pub mod relations;
+pub mod catalog;
+pub mod hash;
+pub mod diff;
+pub mod delta;
+pub mod updates;
+pub mod version;
+
+#[cfg(test)]
+mod fixtures;
 
 // Re-export main types
 pub use metadata::Metadata;
 pub use format::LisPackage;
 pub use dependencies::{detect_dependencies, find_resources, find_binary};
+pub use manifest::InstallationManifest;
+pub use version::Version;
@@ -0,0 +1,159 @@
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::Metadata;
+
+/// A file that was moved aside because the installation overwrote it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// Path the file had (absolute)
+    pub original: String,
+    /// Where it was moved to (absolute)
+    pub backup: String,
+}
+
+/// Installation manifest for uninstallation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallationManifest {
+    /// Application ID
+    pub app_id: String,
+    /// Application name
+    pub app_name: String,
+    /// Version
+    pub version: String,
+    /// Installation prefix used
+    pub prefix: String,
+    /// Timestamp of installation
+    pub installed_at: String,
+    /// List of all installed files (absolute paths)
+    pub installed_files: Vec<String>,
+    /// List of all created directories (absolute paths)
+    pub created_directories: Vec<String>,
+    /// Maximum run time of the uninstall hooks, in seconds
+    #[serde(default)]
+    pub hook_timeout: u64,
+    /// Files of other packages or the system overwritten by this installation, restored on uninstall
+    #[serde(default)]
+    pub backups: Vec<BackupFile>,
+    /// Package relations declared by the package, kept to check later installations
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    #[serde(default)]
+    pub provides: Vec<String>,
+}
+
+impl InstallationManifest {
+    pub fn new(metadata: &Metadata, prefix: String) -> Self {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Self {
+            app_id: metadata.package.app_id.clone(),
+            app_name: metadata.package.application_name.clone(),
+            version: metadata.package.version.clone(),
+            prefix,
+            installed_at: format!("{}", now),
+            installed_files: Vec::new(),
+            created_directories: Vec::new(),
+            hook_timeout: metadata.hooks.timeout,
+            backups: Vec::new(),
+            requires: metadata.dependencies.requires.clone(),
+            conflicts: metadata.dependencies.conflicts.clone(),
+            provides: metadata.dependencies.provides.clone(),
+        }
+    }
+
+    /// Get the registry directory for installation manifests
+    pub fn registry_dir(for_all_users: bool) -> PathBuf {
+        if for_all_users {
+            // System-wide registry
+            PathBuf::from("/var/lib/obision-installer/manifests")
+        } else {
+            // User registry
+            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+            PathBuf::from(home).join(".local/share/obision-installer/manifests")
+        }
+    }
+
+    /// Get the manifest file path for an app
+    pub fn manifest_path(app_id: &str, for_all_users: bool) -> PathBuf {
+        Self::registry_dir(for_all_users).join(format!("{}.json", app_id))
+    }
+
+    /// Get the directory where the uninstall hooks of an app are kept
+    pub fn hooks_dir(app_id: &str, for_all_users: bool) -> PathBuf {
+        Self::registry_dir(for_all_users).with_file_name("hooks").join(app_id)
+    }
+
+    /// Get the path of the captured output of a hook
+    pub fn hook_log_path(app_id: &str, hook: &str, for_all_users: bool) -> PathBuf {
+        Self::registry_dir(for_all_users)
+            .with_file_name("logs")
+            .join(format!("{}.{}.log", app_id, hook))
+    }
+
+    /// Load the manifest of `app_id` from one registry
+    pub fn load(app_id: &str, for_all_users: bool) -> Result<Self> {
+        let path = Self::manifest_path(app_id, for_all_users);
+        let json = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()))
+    }
+
+    /// Load every manifest of one registry. Unreadable manifests are skipped.
+    pub fn list_installed(for_all_users: bool) -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(Self::registry_dir(for_all_users)) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    return None;
+                }
+                let json = fs::read_to_string(&path).ok()?;
+                serde_json::from_str(&json).ok()
+            })
+            .collect()
+    }
+
+    /// Find the manifest of a previous installation of `app_id`.
+    /// Returns the manifest and whether it was found in the system-wide registry.
+    pub fn find_installed(app_id: &str) -> Option<(Self, bool)> {
+        [false, true].into_iter().find_map(|for_all_users| {
+            Self::load(app_id, for_all_users).ok().map(|m| (m, for_all_users))
+        })
+    }
+
+    /// Save the manifest to disk
+    pub fn save(&self, for_all_users: bool) -> Result<()> {
+        let manifest_path = Self::manifest_path(&self.app_id, for_all_users);
+
+        // Create registry directory if it doesn't exist
+        if let Some(parent) = manifest_path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create manifest directory")?;
+        }
+
+        let json = serde_json::to_string_pretty(self)
+            .context("Failed to serialize manifest")?;
+        fs::write(&manifest_path, json)
+            .context("Failed to write manifest")?;
+
+        Ok(())
+    }
+
+    /// Remove the manifest from the registry
+    pub fn remove(&self, for_all_users: bool) -> Result<()> {
+        fs::remove_file(Self::manifest_path(&self.app_id, for_all_users))
+            .context("Failed to remove manifest")
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 
 /// Metadata del paquete .lis
@@ -7,10 +8,17 @@ pub struct Metadata {
     pub installation: InstallationInfo,
     pub desktop: DesktopInfo,
     pub dependencies: DependenciesInfo,
+    #[serde(default, skip_serializing_if = "RequirementsInfo::is_empty")]
+    pub requirements: RequirementsInfo,
+    #[serde(default, skip_serializing_if = "HooksInfo::is_empty")]
+    pub hooks: HooksInfo,
     #[serde(default)]
     pub files: Vec<FileEntry>,
     #[serde(default)]
     pub installer_screens: Vec<InstallerScreen>,
+    /// Present when the package only ships the files changed since a base version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<DeltaInfo>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +31,12 @@ pub struct PackageInfo {
     pub application_name: String,
     pub package_name: String,
     pub compression_level: u8,
+    /// CPU architecture of the bundled binaries (e.g., "x86_64"); none for architecture-independent packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    /// libc the bundled binaries are linked against ("glibc" or "musl")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub libc: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +58,79 @@ pub struct DesktopInfo {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DependenciesInfo {
     pub bundled: Vec<String>,
+    /// Packages that must be installed first (e.g., "com.example.Runtime >= 1.2")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Packages that can't be installed at the same time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+    /// Other app_ids this package stands in for (e.g., "com.example.Runtime = 1.2")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provides: Vec<String>,
+}
+
+/// System requirements checked before installing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequirementsInfo {
+    /// Minimum GTK version (e.g., "4.10")
+    pub min_gtk_version: Option<String>,
+    /// Minimum libadwaita version (e.g., "1.5")
+    pub min_libadwaita_version: Option<String>,
+    /// Supported CPU architectures (e.g., "x86_64", "aarch64"); empty means any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub architectures: Vec<String>,
+    /// System libraries that are not bundled (e.g., "libssl.so.3")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub libraries: Vec<String>,
+}
+
+impl RequirementsInfo {
+    /// Whether the package declares no requirements
+    pub fn is_empty(&self) -> bool {
+        self.min_gtk_version.is_none()
+            && self.min_libadwaita_version.is_none()
+            && self.architectures.is_empty()
+            && self.libraries.is_empty()
+    }
+}
+
+/// Scripts run around installation and uninstallation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HooksInfo {
+    /// Script paths, relative to the install/ folder in the .lis
+    pub pre_install: Option<String>,
+    pub post_install: Option<String>,
+    pub pre_uninstall: Option<String>,
+    pub post_uninstall: Option<String>,
+    /// Maximum run time of each script, in seconds
+    #[serde(default = "default_hook_timeout")]
+    pub timeout: u64,
+}
+
+fn default_hook_timeout() -> u64 {
+    120
+}
+
+impl Default for HooksInfo {
+    fn default() -> Self {
+        Self {
+            pre_install: None,
+            post_install: None,
+            pre_uninstall: None,
+            post_uninstall: None,
+            timeout: default_hook_timeout(),
+        }
+    }
+}
+
+impl HooksInfo {
+    /// Whether the package has no hook scripts
+    pub fn is_empty(&self) -> bool {
+        self.pre_install.is_none()
+            && self.post_install.is_none()
+            && self.pre_uninstall.is_none()
+            && self.post_uninstall.is_none()
+    }
 }
 
 /// Represents a file to be installed
@@ -57,6 +144,24 @@ pub struct FileEntry {
     pub permissions: Option<String>,
 }
 
+/// What a delta package leaves out, relative to the version it applies to.
+///
+/// `files` still lists every file of the new version, but application/ only
+/// has the sources of changed files. The others are taken from the installed
+/// base version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaInfo {
+    /// Version that must be installed for the delta to apply
+    pub base_version: String,
+    /// Destinations taken from the installation, with the SHA-256 they must have
+    #[serde(default)]
+    pub unchanged: BTreeMap<String, String>,
+    /// Destinations of the base version the new version no longer has, deleted
+    /// once the update is installed
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
 /// Installer screen configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstallerScreen {
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use anyhow::{Context, Result};
+
+use crate::metadata::PackageInfo;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const PT_INTERP: u32 = 3;
+
+/// CPU architecture and libc a binary was built for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryPlatform {
+    /// Architecture, named like `std::env::consts::ARCH` (e.g., "x86_64", "aarch64")
+    pub architecture: String,
+    /// "glibc" or "musl"; `None` for static binaries and libraries
+    pub libc: Option<String>,
+}
+
+impl std::fmt::Display for BinaryPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.libc {
+            Some(libc) => write!(f, "{} ({})", self.architecture, libc),
+            None => write!(f, "{}", self.architecture),
+        }
+    }
+}
+
+/// Reads integers from an ELF file in its byte order
+struct ElfReader {
+    data: Vec<u8>,
+    big_endian: bool,
+}
+
+impl ElfReader {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes: [u8; 8] = self.data.get(offset..offset + 8)?.try_into().ok()?;
+        Some(if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+}
+
+/// Architecture name of an ELF `e_machine` value
+fn architecture_name(machine: u16, is_64: bool) -> String {
+    match machine {
+        0x03 => "x86".to_string(),
+        0x08 if is_64 => "mips64".to_string(),
+        0x08 => "mips".to_string(),
+        0x14 => "powerpc".to_string(),
+        0x15 => "powerpc64".to_string(),
+        0x16 => "s390x".to_string(),
+        0x28 => "arm".to_string(),
+        0x3e => "x86_64".to_string(),
+        0xb7 => "aarch64".to_string(),
+        0xf3 if is_64 => "riscv64".to_string(),
+        0xf3 => "riscv32".to_string(),
+        0x102 => "loongarch64".to_string(),
+        other => format!("elf-machine-{:#x}", other),
+    }
+}
+
+/// libc flavor from the program interpreter (e.g., /lib/ld-musl-x86_64.so.1)
+fn libc_name(interpreter: &str) -> Option<String> {
+    if interpreter.contains("ld-musl") {
+        Some("musl".to_string())
+    } else if interpreter.contains("ld-linux") || interpreter.contains("/ld64.so") || interpreter.contains("/ld.so") {
+        Some("glibc".to_string())
+    } else {
+        None
+    }
+}
+
+/// Detect the platform of an ELF binary. Returns `None` for files that are not ELF.
+pub fn detect<P: AsRef<Path>>(path: P) -> Result<Option<BinaryPlatform>> {
+    let path = path.as_ref();
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    let mut header = vec![0u8; 64];
+    let read = file.read(&mut header)?;
+    header.truncate(read);
+    if header.len() < 20 || header[..4] != ELF_MAGIC {
+        return Ok(None);
+    }
+
+    let is_64 = header[4] == 2;
+    let elf = ElfReader { big_endian: header[5] == 2, data: header };
+    let invalid = || anyhow::anyhow!("Invalid ELF header in {}", path.display());
+
+    let machine = elf.u16(18).ok_or_else(invalid)?;
+    let (phoff, phentsize, phnum) = if is_64 {
+        (elf.u64(32), elf.u16(54), elf.u16(56))
+    } else {
+        (elf.u32(28).map(u64::from), elf.u16(42), elf.u16(44))
+    };
+    let (phoff, phentsize, phnum) = (
+        phoff.ok_or_else(invalid)?,
+        phentsize.ok_or_else(invalid)? as usize,
+        phnum.ok_or_else(invalid)? as usize,
+    );
+
+    if phentsize > 256 || phnum > 4096 {
+        return Err(invalid());
+    }
+
+    // Dynamically linked executables name their loader in PT_INTERP
+    let mut program_headers = vec![0u8; phentsize * phnum];
+    file.seek(SeekFrom::Start(phoff))?;
+    file.read_exact(&mut program_headers)
+        .with_context(|| format!("Truncated ELF file {}", path.display()))?;
+    let headers = ElfReader { data: program_headers, big_endian: elf.big_endian };
+
+    let mut libc = None;
+    for i in 0..phnum {
+        let base = i * phentsize;
+        if headers.u32(base) != Some(PT_INTERP) {
+            continue;
+        }
+        let (offset, size) = if is_64 {
+            (headers.u64(base + 8), headers.u64(base + 32))
+        } else {
+            (headers.u32(base + 4).map(u64::from), headers.u32(base + 16).map(u64::from))
+        };
+        let (offset, size) = (offset.ok_or_else(invalid)?, size.ok_or_else(invalid)?.min(4096));
+
+        let mut interpreter = vec![0u8; size as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut interpreter)?;
+        libc = libc_name(String::from_utf8_lossy(&interpreter).trim_end_matches('\0'));
+        break;
+    }
+
+    Ok(Some(BinaryPlatform {
+        architecture: architecture_name(machine, is_64),
+        libc,
+    }))
+}
+
+/// Platform of this system: the running architecture and the libc of /bin/sh
+pub fn host_platform() -> BinaryPlatform {
+    let libc = detect("/bin/sh").ok().flatten().and_then(|p| p.libc);
+    BinaryPlatform {
+        architecture: std::env::consts::ARCH.to_string(),
+        libc,
+    }
+}
+
+/// Check that a package built for a specific platform can run on this system
+pub fn check_compatible(package: &PackageInfo) -> Result<()> {
+    let host = host_platform();
+
+    if let Some(architecture) = &package.architecture {
+        if *architecture != host.architecture {
+            anyhow::bail!(
+                "{} is built for {} and cannot be installed on this {} system",
+                package.application_name, architecture, host.architecture
+            );
+        }
+    }
+
+    // An unknown host libc is not a reason to refuse
+    if let (Some(libc), Some(host_libc)) = (&package.libc, &host.libc) {
+        if libc != host_libc {
+            anyhow::bail!(
+                "{} is built for {} and cannot be installed on this {} system",
+                package.application_name, libc, host_libc
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,461 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+
+use crate::format::LisPackage;
+use crate::manifest::InstallationManifest;
+use crate::metadata::Metadata;
+use crate::version::{self, Version};
+
+/// Operators of a version constraint, longest first so ">=" is not read as ">"
+const OPERATORS: [(&str, VersionOp); 5] = [
+    (">=", VersionOp::GreaterOrEqual),
+    ("<=", VersionOp::LessOrEqual),
+    ("=", VersionOp::Equal),
+    (">", VersionOp::Greater),
+    ("<", VersionOp::Less),
+];
+
+/// Comparison of a version constraint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl VersionOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            VersionOp::Equal => "=",
+            VersionOp::Greater => ">",
+            VersionOp::GreaterOrEqual => ">=",
+            VersionOp::Less => "<",
+            VersionOp::LessOrEqual => "<=",
+        }
+    }
+
+    /// Whether a version comparing as `ordering` to the wanted one is accepted
+    fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            VersionOp::Equal => ordering == Ordering::Equal,
+            VersionOp::Greater => ordering == Ordering::Greater,
+            VersionOp::GreaterOrEqual => ordering != Ordering::Less,
+            VersionOp::Less => ordering == Ordering::Less,
+            VersionOp::LessOrEqual => ordering != Ordering::Greater,
+        }
+    }
+}
+
+/// A reference to another package by app_id, optionally limited to some versions
+/// (e.g., "com.example.Runtime >= 1.2")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub app_id: String,
+    pub version: Option<(VersionOp, String)>,
+}
+
+impl Relation {
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (app_id, constraint) = match text.find(['<', '>', '=']) {
+            Some(i) => (text[..i].trim(), Some(text[i..].trim())),
+            None => (text, None),
+        };
+        if app_id.is_empty() || app_id.contains(char::is_whitespace) {
+            anyhow::bail!("Invalid package relation '{}'", text);
+        }
+
+        let version = match constraint {
+            None => None,
+            Some(constraint) => {
+                let (op, version) = OPERATORS.iter()
+                    .find_map(|(symbol, op)| constraint.strip_prefix(symbol).map(|rest| (*op, rest.trim())))
+                    .with_context(|| format!("Invalid version constraint in '{}'", text))?;
+                Version::parse(version)
+                    .with_context(|| format!("Invalid version constraint in '{}'", text))?;
+                Some((op, version.to_string()))
+            }
+        };
+
+        Ok(Self { app_id: app_id.to_string(), version })
+    }
+
+    /// Whether a package with this app_id and version fits the relation.
+    /// Without a known version only unversioned relations match.
+    pub fn matches(&self, app_id: &str, version: Option<&str>) -> bool {
+        if self.app_id != app_id {
+            return false;
+        }
+        match (&self.version, version) {
+            (None, _) => true,
+            (Some((op, wanted)), Some(version)) => op.accepts(version::compare(version, wanted)),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some((op, version)) => write!(f, "{} {} {}", self.app_id, op.symbol(), version),
+            None => write!(f, "{}", self.app_id),
+        }
+    }
+}
+
+/// Parse a list of relations, failing on the first invalid one
+fn parse_relations(relations: &[String]) -> Result<Vec<Relation>> {
+    relations.iter().map(|r| Relation::parse(r)).collect()
+}
+
+/// A package taking part in dependency resolution: installed, or about to be
+#[derive(Debug, Clone)]
+pub struct PackageRelations {
+    pub app_id: String,
+    pub version: String,
+    pub requires: Vec<Relation>,
+    pub conflicts: Vec<Relation>,
+    /// Other app_ids the package stands in for, optionally with "= version"
+    pub provides: Vec<Relation>,
+}
+
+impl PackageRelations {
+    /// Relations declared by a package. Invalid entries are an error.
+    pub fn from_metadata(metadata: &Metadata) -> Result<Self> {
+        let dependencies = &metadata.dependencies;
+        let provides = parse_relations(&dependencies.provides)?;
+        if let Some(relation) = provides.iter().find(|r| r.version.as_ref().is_some_and(|(op, _)| *op != VersionOp::Equal)) {
+            anyhow::bail!("Provided package '{}' can only have an exact version (\"= version\")", relation);
+        }
+
+        Ok(Self {
+            app_id: metadata.package.app_id.clone(),
+            version: metadata.package.version.clone(),
+            requires: parse_relations(&dependencies.requires)?,
+            conflicts: parse_relations(&dependencies.conflicts)?,
+            provides,
+        })
+    }
+
+    /// Relations recorded for an installed package. Entries that don't parse are ignored.
+    pub fn from_manifest(manifest: &InstallationManifest) -> Self {
+        let parse = |relations: &[String]| relations.iter().filter_map(|r| Relation::parse(r).ok()).collect();
+        Self {
+            app_id: manifest.app_id.clone(),
+            version: manifest.version.clone(),
+            requires: parse(&manifest.requires),
+            conflicts: parse(&manifest.conflicts),
+            provides: parse(&manifest.provides),
+        }
+    }
+
+    /// Whether this package fits `relation`, by its own app_id or one it provides
+    pub fn satisfies(&self, relation: &Relation) -> bool {
+        relation.matches(&self.app_id, Some(&self.version))
+            || self.provides.iter().any(|provided| {
+                relation.matches(&provided.app_id, provided.version.as_ref().map(|(_, v)| v.as_str()))
+            })
+    }
+}
+
+/// Why a package can't be installed next to the installed ones
+#[derive(Debug, Clone)]
+pub enum RelationProblem {
+    /// A required package is not installed
+    Missing(Relation),
+    /// A required package is installed, but in a version that doesn't fit
+    WrongVersion { relation: Relation, installed: String },
+    /// The package and an installed one declare they can't be installed together
+    Conflict { app_id: String, version: String },
+}
+
+impl RelationProblem {
+    /// Whether installing another package could solve the problem
+    pub fn is_missing_requirement(&self) -> bool {
+        !matches!(self, RelationProblem::Conflict { .. })
+    }
+}
+
+impl fmt::Display for RelationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelationProblem::Missing(relation) => write!(f, "Requires {}, which is not installed", relation),
+            RelationProblem::WrongVersion { relation, installed } => {
+                write!(f, "Requires {}, but version {} is installed", relation, installed)
+            }
+            RelationProblem::Conflict { app_id, version } => {
+                write!(f, "Conflicts with {} {}, which is installed", app_id, version)
+            }
+        }
+    }
+}
+
+/// Relations of every installed package, from both registries.
+/// An application installed for the user hides its system-wide installation.
+pub fn installed_packages() -> Vec<PackageRelations> {
+    let mut packages: Vec<PackageRelations> = Vec::new();
+    for manifest in InstallationManifest::list_installed(false).into_iter().chain(InstallationManifest::list_installed(true)) {
+        if !packages.iter().any(|p| p.app_id == manifest.app_id) {
+            packages.push(PackageRelations::from_manifest(&manifest));
+        }
+    }
+    packages
+}
+
+/// Check a package against the installed ones. An installed version of the
+/// same application is ignored, as the installation replaces it.
+pub fn check_relations(package: &PackageRelations, installed: &[PackageRelations]) -> Vec<RelationProblem> {
+    let others: Vec<&PackageRelations> = installed.iter().filter(|p| p.app_id != package.app_id).collect();
+    let mut problems = Vec::new();
+
+    for relation in &package.requires {
+        if others.iter().any(|p| p.satisfies(relation)) {
+            continue;
+        }
+        problems.push(match others.iter().find(|p| p.app_id == relation.app_id) {
+            Some(p) => RelationProblem::WrongVersion { relation: relation.clone(), installed: p.version.clone() },
+            None => RelationProblem::Missing(relation.clone()),
+        });
+    }
+
+    for other in &others {
+        if package.conflicts.iter().any(|r| other.satisfies(r)) || other.conflicts.iter().any(|r| package.satisfies(r)) {
+            problems.push(RelationProblem::Conflict { app_id: other.app_id.clone(), version: other.version.clone() });
+        }
+    }
+
+    problems
+}
+
+/// Installed packages that would lose a requirement if `app_id` were removed
+pub fn dependents(app_id: &str, installed: &[PackageRelations]) -> Vec<String> {
+    let Some(removed) = installed.iter().find(|p| p.app_id == app_id) else {
+        return Vec::new();
+    };
+    let remaining: Vec<&PackageRelations> = installed.iter().filter(|p| p.app_id != app_id).collect();
+
+    remaining.iter()
+        .filter(|package| {
+            package.requires.iter().any(|relation| {
+                removed.satisfies(relation)
+                    && !remaining.iter().any(|p| p.app_id != package.app_id && p.satisfies(relation))
+            })
+        })
+        .map(|package| package.app_id.clone())
+        .collect()
+}
+
+/// A .lis package found in a local folder
+#[derive(Debug, Clone)]
+pub struct AvailablePackage {
+    pub path: PathBuf,
+    pub metadata: Metadata,
+    pub relations: PackageRelations,
+}
+
+/// Read every .lis package in `dir`. Packages that can't be read are skipped.
+pub fn scan_directory(dir: &Path) -> Result<Vec<AvailablePackage>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?;
+
+    let mut packages = Vec::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("lis") {
+            continue;
+        }
+        let Ok(metadata) = LisPackage::read_metadata(&path) else {
+            continue;
+        };
+        if let Ok(relations) = PackageRelations::from_metadata(&metadata) {
+            packages.push(AvailablePackage { path, metadata, relations });
+        }
+    }
+    Ok(packages)
+}
+
+/// Packages to install before a package, and what can't be solved
+#[derive(Debug, Default)]
+pub struct Resolution<'a> {
+    /// In installation order: every package comes after its own requirements
+    pub packages: Vec<&'a AvailablePackage>,
+    pub problems: Vec<RelationProblem>,
+}
+
+/// Pick packages from `available` for the requirements of `package` that the
+/// installed ones don't meet, following their own requirements too.
+/// The newest fitting version is preferred.
+pub fn resolve<'a>(
+    package: &PackageRelations,
+    installed: &[PackageRelations],
+    available: &'a [AvailablePackage],
+) -> Resolution<'a> {
+    let mut world = installed.to_vec();
+    let mut resolution = Resolution::default();
+    add_requirements(package, &mut world, available, &mut resolution);
+
+    // Requirements are solved; what is left are the conflicts with the final set
+    resolution.problems.extend(
+        check_relations(package, &world).into_iter().filter(|p| !p.is_missing_requirement())
+    );
+    resolution
+}
+
+fn add_requirements<'a>(
+    package: &PackageRelations,
+    world: &mut Vec<PackageRelations>,
+    available: &'a [AvailablePackage],
+    resolution: &mut Resolution<'a>,
+) {
+    for relation in &package.requires {
+        if world.iter().any(|p| p.app_id != package.app_id && p.satisfies(relation)) {
+            continue;
+        }
+
+        let candidate = available.iter()
+            .filter(|a| a.relations.app_id != package.app_id && a.relations.satisfies(relation))
+            .filter(|a| check_relations(&a.relations, world).iter().all(|p| p.is_missing_requirement()))
+            .max_by(|a, b| version::compare(&a.relations.version, &b.relations.version));
+
+        let Some(candidate) = candidate else {
+            resolution.problems.push(match world.iter().find(|p| p.app_id == relation.app_id) {
+                Some(p) => RelationProblem::WrongVersion { relation: relation.clone(), installed: p.version.clone() },
+                None => RelationProblem::Missing(relation.clone()),
+            });
+            continue;
+        };
+
+        // Added before its own requirements so cycles end here
+        world.retain(|p| p.app_id != candidate.relations.app_id);
+        world.push(candidate.relations.clone());
+        add_requirements(&candidate.relations, world, available, resolution);
+        resolution.packages.push(candidate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    /// Package with its relations written as in metadata.toml
+    fn package(app_id: &str, version: &str, requires: &[&str], conflicts: &[&str], provides: &[&str]) -> PackageRelations {
+        let parse = |relations: &[&str]| relations.iter().map(|r| Relation::parse(r).unwrap()).collect();
+        PackageRelations {
+            app_id: app_id.to_string(),
+            version: version.to_string(),
+            requires: parse(requires),
+            conflicts: parse(conflicts),
+            provides: parse(provides),
+        }
+    }
+
+    /// Package found in a folder, with the metadata of its own app_id and version
+    fn available(relations: PackageRelations) -> AvailablePackage {
+        let metadata = fixtures::metadata(&relations.app_id, &relations.version, &[]);
+        AvailablePackage { path: PathBuf::from(format!("/packages/{}.lis", relations.app_id)), metadata, relations }
+    }
+
+    #[test]
+    fn relations_parse_with_optional_constraint() {
+        let relation = Relation::parse(" com.example.Runtime >= 1.2 ").unwrap();
+        assert_eq!(relation.app_id, "com.example.Runtime");
+        assert_eq!(relation.version, Some((VersionOp::GreaterOrEqual, "1.2".to_string())));
+        assert_eq!(relation.to_string(), "com.example.Runtime >= 1.2");
+
+        assert_eq!(Relation::parse("com.example.Runtime").unwrap().version, None);
+        assert_eq!(Relation::parse("com.example.Runtime<2").unwrap().version, Some((VersionOp::Less, "2".to_string())));
+        for invalid in ["", ">= 1.2", "com.example Runtime", "com.example.Runtime => 1.2", "com.example.Runtime >= one"] {
+            assert!(Relation::parse(invalid).is_err(), "'{}' parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn provided_package_satisfies_requirement() {
+        let app = package("com.example.App", "1.0", &["com.example.Runtime >= 1.2"], &[], &[]);
+        let installed = [package("com.example.RuntimeNext", "3.0", &[], &[], &["com.example.Runtime = 1.4"])];
+        assert!(check_relations(&app, &installed).is_empty());
+
+        // A provided app_id without a version only fits unversioned requirements
+        let installed = [package("com.example.RuntimeNext", "3.0", &[], &[], &["com.example.Runtime"])];
+        let problems = check_relations(&app, &installed);
+        assert!(matches!(problems.as_slice(), [RelationProblem::Missing(_)]));
+    }
+
+    #[test]
+    fn unmet_requirement_is_missing_or_wrong_version() {
+        let app = package("com.example.App", "1.0", &["com.example.Runtime >= 1.2", "com.example.Codecs"], &[], &[]);
+        let installed = [package("com.example.Runtime", "1.1.9", &[], &[], &[])];
+
+        let problems = check_relations(&app, &installed);
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(&problems[0], RelationProblem::WrongVersion { installed, .. } if installed == "1.1.9"));
+        assert!(matches!(&problems[1], RelationProblem::Missing(relation) if relation.app_id == "com.example.Codecs"));
+        assert!(problems.iter().all(RelationProblem::is_missing_requirement));
+    }
+
+    #[test]
+    fn conflicts_are_checked_both_ways() {
+        let installed = [
+            package("com.example.Old", "2.0", &[], &[], &[]),
+            package("com.example.Strict", "1.0", &[], &["com.example.App < 2"], &[]),
+        ];
+
+        let app = package("com.example.App", "1.5", &[], &["com.example.Old >= 2"], &[]);
+        let problems = check_relations(&app, &installed);
+        let conflicting: Vec<&str> = problems.iter()
+            .map(|p| match p {
+                RelationProblem::Conflict { app_id, .. } => app_id.as_str(),
+                other => panic!("unexpected {}", other),
+            })
+            .collect();
+        assert_eq!(conflicting, ["com.example.Old", "com.example.Strict"]);
+
+        // The installed version of the same application is replaced, not conflicting
+        let upgrade = package("com.example.Strict", "1.1", &[], &[], &[]);
+        assert!(check_relations(&upgrade, &installed).is_empty());
+    }
+
+    #[test]
+    fn dependents_block_uninstall_unless_another_package_provides() {
+        let installed = vec![
+            package("com.example.Runtime", "1.4", &[], &[], &[]),
+            package("com.example.App", "1.0", &["com.example.Runtime >= 1.2"], &[], &[]),
+            package("com.example.Tool", "1.0", &["com.example.Runtime"], &[], &[]),
+        ];
+        assert_eq!(dependents("com.example.Runtime", &installed), ["com.example.App", "com.example.Tool"]);
+        assert!(dependents("com.example.App", &installed).is_empty());
+
+        let mut installed = installed;
+        installed.push(package("com.example.RuntimeNext", "3.0", &[], &[], &["com.example.Runtime = 1.2"]));
+        assert!(dependents("com.example.Runtime", &installed).is_empty());
+    }
+
+    #[test]
+    fn resolve_picks_newest_fitting_packages_in_order() {
+        let app = package("com.example.App", "1.0", &["com.example.Runtime >= 1.2"], &[], &[]);
+        let available = [
+            available(package("com.example.Runtime", "1.3", &["com.example.Base"], &[], &[])),
+            available(package("com.example.Runtime", "1.5", &["com.example.Base"], &[], &[])),
+            available(package("com.example.Runtime", "2.0", &["com.example.Base"], &["com.example.Legacy"], &[])),
+            available(package("com.example.Base", "1.0", &[], &[], &[])),
+        ];
+        let installed = [package("com.example.Legacy", "1.0", &[], &[], &[])];
+
+        // 2.0 conflicts with an installed package, so 1.5 is the newest fitting
+        let resolution = resolve(&app, &installed, &available);
+        assert!(resolution.problems.is_empty());
+        let picked: Vec<(&str, &str)> = resolution.packages.iter()
+            .map(|p| (p.relations.app_id.as_str(), p.relations.version.as_str()))
+            .collect();
+        assert_eq!(picked, [("com.example.Base", "1.0"), ("com.example.Runtime", "1.5")]);
+
+        let app = package("com.example.App", "1.0", &["com.example.Runtime >= 3"], &[], &[]);
+        let resolution = resolve(&app, &installed, &available);
+        assert!(resolution.packages.is_empty());
+        assert!(matches!(resolution.problems.as_slice(), [RelationProblem::Missing(_)]));
+    }
+}
@@ -0,0 +1,140 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use std::process::Command;
+
+use crate::metadata::RequirementsInfo;
+
+/// Directories searched for libraries when ldconfig is not available
+const LIBRARY_DIRS: &[&str] = &[
+    "/lib",
+    "/lib64",
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/local/lib",
+    "/usr/local/lib64",
+];
+
+/// Versions of the toolkit libraries the installer runs with.
+/// liblis does not link GTK, so the caller provides them.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeVersions {
+    pub gtk: Option<String>,
+    pub libadwaita: Option<String>,
+}
+
+/// Outcome of one requirement check
+#[derive(Debug, Clone)]
+pub struct RequirementCheck {
+    /// What was checked (e.g., "GTK 4.10 or newer")
+    pub title: String,
+    /// What was found on this system
+    pub detail: String,
+    pub passed: bool,
+}
+
+/// Check the requirements declared by a package against this system
+pub fn check_requirements(requirements: &RequirementsInfo, runtime: &RuntimeVersions) -> Vec<RequirementCheck> {
+    let mut checks = Vec::new();
+
+    if !requirements.architectures.is_empty() {
+        let arch = std::env::consts::ARCH;
+        checks.push(RequirementCheck {
+            title: format!("Architecture: {}", requirements.architectures.join(", ")),
+            detail: format!("This system is {}", arch),
+            passed: requirements.architectures.iter().any(|a| a == arch),
+        });
+    }
+
+    let toolkits = [
+        ("GTK", &requirements.min_gtk_version, &runtime.gtk),
+        ("libadwaita", &requirements.min_libadwaita_version, &runtime.libadwaita),
+    ];
+    for (name, required, found) in toolkits {
+        let Some(required) = required else {
+            continue;
+        };
+        let (passed, detail) = match found {
+            Some(found) => (version_at_least(found, required), format!("Version {} is installed", found)),
+            None => (false, format!("{} was not found", name)),
+        };
+        checks.push(RequirementCheck {
+            title: format!("{} {} or newer", name, required),
+            detail,
+            passed,
+        });
+    }
+
+    if !requirements.libraries.is_empty() {
+        let available = system_libraries();
+        for library in &requirements.libraries {
+            let passed = has_library(&available, library);
+            checks.push(RequirementCheck {
+                title: format!("Library {}", library),
+                detail: if passed { "Installed".to_string() } else { "Not installed".to_string() },
+                passed,
+            });
+        }
+    }
+
+    checks
+}
+
+/// Compare dotted numeric versions ("4.10.2" >= "4.10"). Missing components count as 0.
+pub fn version_at_least(version: &str, minimum: &str) -> bool {
+    compare_versions(version, minimum) != Ordering::Less
+}
+
+/// Order dotted numeric versions component by component ("1.10" > "1.9")
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| -> Vec<u64> {
+        v.split('.')
+            .map(|part| part.chars().take_while(|c| c.is_ascii_digit()).collect::<String>().parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parse(a), parse(b));
+
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        if x != y {
+            return x.cmp(&y);
+        }
+    }
+    Ordering::Equal
+}
+
+/// File names of the shared libraries known to the dynamic linker
+fn system_libraries() -> Vec<String> {
+    // Users often don't have /sbin in PATH
+    for ldconfig in ["/sbin/ldconfig", "/usr/sbin/ldconfig", "ldconfig"] {
+        if let Ok(output) = Command::new(ldconfig).arg("-p").output() {
+            if output.status.success() {
+                // Lines look like "\tlibssl.so.3 (libc6,x86-64) => /usr/lib/libssl.so.3"
+                return String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter_map(|line| line.split_whitespace().next())
+                    .filter(|name| name.contains(".so"))
+                    .map(str::to_string)
+                    .collect();
+            }
+        }
+    }
+
+    // No ldconfig: list the standard library directories instead
+    let mut libraries = Vec::new();
+    let multiarch = format!("/usr/lib/{}-linux-gnu", std::env::consts::ARCH);
+    for dir in LIBRARY_DIRS.iter().copied().chain([multiarch.as_str()]) {
+        if let Ok(entries) = std::fs::read_dir(Path::new(dir)) {
+            libraries.extend(entries.flatten().map(|e| e.file_name().to_string_lossy().to_string()));
+        }
+    }
+    libraries
+}
+
+/// A requirement such as "libfoo.so" matches any "libfoo.so.N"
+fn has_library(available: &[String], library: &str) -> bool {
+    available.iter().any(|name| {
+        name == library
+            || name.strip_prefix(library).is_some_and(|rest| rest.starts_with('.'))
+    })
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+
+use crate::catalog::{Catalog, CatalogEntry};
+use crate::manifest::InstallationManifest;
+use crate::platform;
+use crate::version;
+
+/// File listing the repositories to check for updates, one per line
+const REPOSITORIES_FILE: &str = "obision-installer/repositories.conf";
+
+/// A repository catalog and where its packages are
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pub root: PathBuf,
+    pub catalog: Catalog,
+}
+
+impl Repository {
+    /// Open a repository given as a local path or a `file://` URL
+    pub fn open(location: &str) -> Result<Self> {
+        let root = repository_path(location)?;
+        let catalog = Catalog::load(&root)
+            .with_context(|| format!("Failed to open repository {}", location))?;
+        Ok(Self { root, catalog })
+    }
+}
+
+/// Local path of a repository location
+fn repository_path(location: &str) -> Result<PathBuf> {
+    let Some(rest) = location.strip_prefix("file://") else {
+        if location.contains("://") {
+            anyhow::bail!("Unsupported repository URL '{}': only local paths and file:// URLs are supported", location);
+        }
+        return Ok(PathBuf::from(location));
+    };
+
+    // file:///srv/packages, or file://localhost/srv/packages
+    let path = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !path.starts_with('/') {
+        anyhow::bail!("Unsupported repository URL '{}': remote hosts are not supported", location);
+    }
+    Ok(PathBuf::from(percent_decode(path)?))
+}
+
+/// Decode the %XX escapes of a URL path
+fn percent_decode(path: &str) -> Result<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = path.get(i + 1..i + 3).unwrap_or_default();
+            let byte = u8::from_str_radix(hex, 16)
+                .with_context(|| format!("Invalid escape in URL path '{}'", path))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).with_context(|| format!("Invalid URL path '{}'", path))
+}
+
+/// Repositories configured for this user, then system-wide.
+///
+/// They are read from `~/.config/obision-installer/repositories.conf` and
+/// `/etc/obision-installer/repositories.conf`: one path or `file://` URL per
+/// line; empty lines and lines starting with `#` are ignored.
+pub fn configured_repositories() -> Vec<String> {
+    let user_config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    let mut repositories = Vec::new();
+    let files = user_config.into_iter().chain([PathBuf::from("/etc")]).map(|dir| dir.join(REPOSITORIES_FILE));
+    for file in files {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for line in content.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') && !repositories.iter().any(|r| r == line) {
+                repositories.push(line.to_string());
+            }
+        }
+    }
+    repositories
+}
+
+/// A newer version of an installed application found in a repository
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub installed: InstallationManifest,
+    /// Whether the application is installed for all users
+    pub for_all_users: bool,
+    pub available: CatalogEntry,
+    /// Root of the repository that has the new version
+    pub repository: PathBuf,
+}
+
+impl Update {
+    /// Package of the new version, after checking it against the catalog
+    pub fn package_path(&self) -> Result<PathBuf> {
+        self.available.verified_path(&self.repository)
+    }
+}
+
+/// Compare the installed applications with the repositories and return the
+/// newest version of each one that is newer than the installed one and can
+/// run on this system. System-wide installations are included if `include_system`.
+/// A delta package is preferred to the full one of the same version when it
+/// applies to the installed version; other deltas are ignored.
+pub fn find_updates(repositories: &[Repository], include_system: bool) -> Vec<Update> {
+    let registries: &[bool] = if include_system { &[false, true] } else { &[false] };
+    let host = platform::host_platform();
+
+    let mut updates = Vec::new();
+    for &for_all_users in registries {
+        for installed in InstallationManifest::list_installed(for_all_users) {
+            let newest = repositories.iter()
+                .flat_map(|repository| {
+                    repository.catalog.packages.iter()
+                        .filter(|p| p.app_id == installed.app_id)
+                        .filter(|p| p.architecture.as_ref().is_none_or(|a| *a == host.architecture))
+                        // Like platform::check_compatible, an unknown host libc doesn't rule a package out
+                        .filter(|p| p.libc.as_ref().zip(host.libc.as_ref()).is_none_or(|(libc, host_libc)| libc == host_libc))
+                        .filter(|p| p.base_version.as_ref().is_none_or(|base| version::compare(base, &installed.version).is_eq()))
+                        .map(move |p| (repository.root.as_path(), p))
+                })
+                .max_by(|(_, a), (_, b)| {
+                    version::compare(&a.version, &b.version)
+                        .then_with(|| a.base_version.is_some().cmp(&b.base_version.is_some()))
+                });
+
+            if let Some((root, available)) = newest {
+                if version::compare(&available.version, &installed.version).is_gt() {
+                    updates.push(Update {
+                        installed,
+                        for_all_users,
+                        available: available.clone(),
+                        repository: root.to_path_buf(),
+                    });
+                }
+            }
+        }
+    }
+    updates
+}
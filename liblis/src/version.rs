@@ -0,0 +1,277 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use anyhow::Result;
+
+use crate::requirements::compare_versions;
+
+/// A package version: `[epoch:]release[-pre-release][+build]`.
+///
+/// The release is one or more dot separated numbers ("1", "1.2", "1.2.3"),
+/// compared numerically with missing components counting as 0. The
+/// pre-release and build parts follow semver: "1.2.0-beta.2" comes before
+/// "1.2.0", and the build metadata is ignored when comparing. The epoch
+/// (distro style, default 0) overrides everything else, so "1:0.9" is newer
+/// than "2.0"; it is used when a project changes its versioning scheme.
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub epoch: u64,
+    pub release: Vec<u64>,
+    pub pre_release: Vec<PreReleaseId>,
+    pub build: Option<String>,
+}
+
+/// One dot separated identifier of a pre-release ("beta", "2")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreReleaseId {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+impl Ord for PreReleaseId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (PreReleaseId::Numeric(a), PreReleaseId::Numeric(b)) => a.cmp(b),
+            (PreReleaseId::Alphanumeric(a), PreReleaseId::Alphanumeric(b)) => a.cmp(b),
+            // Numeric identifiers have lower precedence
+            (PreReleaseId::Numeric(_), PreReleaseId::Alphanumeric(_)) => Ordering::Less,
+            (PreReleaseId::Alphanumeric(_), PreReleaseId::Numeric(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for PreReleaseId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for PreReleaseId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreReleaseId::Numeric(n) => write!(f, "{}", n),
+            PreReleaseId::Alphanumeric(s) => f.write_str(s),
+        }
+    }
+}
+
+/// Whether `s` is a non-empty run of ASCII digits
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether `s` is a non-empty run of ASCII letters, digits and hyphens
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = |reason: &str| anyhow::anyhow!("Invalid version '{}': {}", text, reason);
+
+        let (rest, build) = match text.split_once('+') {
+            Some((rest, build)) => {
+                if !build.split('.').all(is_identifier) {
+                    return Err(invalid("the build metadata after '+' can only have letters, digits, '-' and '.'"));
+                }
+                (rest, Some(build.to_string()))
+            }
+            None => (text, None),
+        };
+
+        let (epoch, rest) = match rest.split_once(':') {
+            Some((epoch, rest)) => {
+                if !is_number(epoch) {
+                    return Err(invalid("the epoch before ':' must be a number"));
+                }
+                (epoch.parse().map_err(|_| invalid("the epoch is too large"))?, rest)
+            }
+            None => (0, rest),
+        };
+
+        let (release, pre_release) = match rest.split_once('-') {
+            Some((release, pre)) => (release, Some(pre)),
+            None => (rest, None),
+        };
+
+        let release = release.split('.')
+            .map(|part| {
+                if !is_number(part) {
+                    return Err(invalid("it must start with numbers separated by dots (e.g., 1.2.0)"));
+                }
+                part.parse().map_err(|_| invalid("a number is too large"))
+            })
+            .collect::<Result<Vec<u64>>>()?;
+
+        let pre_release = match pre_release {
+            Some(pre) => pre.split('.')
+                .map(|id| {
+                    if !is_identifier(id) {
+                        return Err(invalid("the pre-release after '-' can only have letters, digits, '-' and '.'"));
+                    }
+                    Ok(match id.parse() {
+                        Ok(n) if is_number(id) => PreReleaseId::Numeric(n),
+                        _ => PreReleaseId::Alphanumeric(id.to_string()),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(Self { epoch, release, pre_release, build })
+    }
+
+    /// Whether this is a pre-release (e.g., "2.0.0-rc.1")
+    pub fn is_pre_release(&self) -> bool {
+        !self.pre_release.is_empty()
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch.cmp(&other.epoch)
+            .then_with(|| {
+                let len = self.release.len().max(other.release.len());
+                let component = |v: &Version, i: usize| v.release.get(i).copied().unwrap_or(0);
+                (0..len)
+                    .map(|i| component(self, i).cmp(&component(other, i)))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .then_with(|| match (self.pre_release.is_empty(), other.pre_release.is_empty()) {
+                (true, true) => Ordering::Equal,
+                // A release comes after its pre-releases
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre_release.cmp(&other.pre_release),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.epoch > 0 {
+            write!(f, "{}:", self.epoch)?;
+        }
+        let release: Vec<String> = self.release.iter().map(u64::to_string).collect();
+        f.write_str(&release.join("."))?;
+        if !self.pre_release.is_empty() {
+            let pre: Vec<String> = self.pre_release.iter().map(PreReleaseId::to_string).collect();
+            write!(f, "-{}", pre.join("."))?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two version strings. Versions that don't parse, such as the
+/// free-form ones of packages built before versions were validated, fall
+/// back to comparing their leading numbers.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => compare_versions(a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version {
+        Version::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_every_part() {
+        let v = version("2:1.4.0-beta.2+git.abc123");
+        assert_eq!(v.epoch, 2);
+        assert_eq!(v.release, [1, 4, 0]);
+        assert_eq!(v.pre_release, [PreReleaseId::Alphanumeric("beta".to_string()), PreReleaseId::Numeric(2)]);
+        assert_eq!(v.build.as_deref(), Some("git.abc123"));
+        assert!(v.is_pre_release());
+        assert_eq!(v.to_string(), "2:1.4.0-beta.2+git.abc123");
+
+        let v = version("1.2");
+        assert_eq!((v.epoch, v.release.as_slice(), v.build.as_deref()), (0, [1, 2].as_slice(), None));
+        assert!(!v.is_pre_release());
+
+        for invalid in ["", "1..2", "v1.2", "1.2-", "1.2-beta..1", "a:1.2", "1.2+", "1.2+bad!", "1.x"] {
+            assert!(Version::parse(invalid).is_err(), "'{}' parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn missing_components_count_as_zero() {
+        assert_eq!(version("1.2"), version("1.2.0"));
+        assert_eq!(version("1"), version("1.0.0.0"));
+        assert!(version("1.2.1") > version("1.2"));
+        assert!(version("1.10") > version("1.9"));
+    }
+
+    #[test]
+    fn epoch_overrides_release() {
+        assert!(version("1:0.9") > version("2.0"));
+        assert!(version("2:1.0") > version("1:9.9"));
+        assert_eq!(version("0:1.2"), version("1.2"));
+    }
+
+    #[test]
+    fn pre_releases_come_before_the_release() {
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1-alpha",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn build_metadata_is_ignored() {
+        assert_eq!(version("1.2.0+build.1"), version("1.2.0+build.2"));
+        assert_eq!(version("1.2.0+build.1"), version("1.2.0"));
+        assert_eq!(compare("1.2.0+a", "1.2.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn unparsable_versions_compare_by_leading_numbers() {
+        assert_eq!(compare("1.2.3", "1.10"), Ordering::Less);
+        assert_eq!(compare("2.0 beta", "1.9"), Ordering::Greater);
+        // Free-form versions of old packages
+        assert_eq!(compare("1.2.3b", "1.2.3"), Ordering::Equal);
+        assert_eq!(compare("release 2", "1.0"), Ordering::Less);
+        assert_eq!(compare("1.4rc1", "1.3.9"), Ordering::Greater);
+    }
+}
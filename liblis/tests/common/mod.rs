@@ -0,0 +1,6 @@
+use liblis::{metadata, Metadata};
+
+#[path = "../../src/fixtures.rs"]
+mod fixtures;
+
+pub use fixtures::metadata;
@@ -0,0 +1,98 @@
+use std::fs::{self, File};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use liblis::delta::create_delta;
+use liblis::format::{LisPackage, PackageWriter};
+use liblis::install::{perform_installation, ConflictResolution};
+use liblis::manifest::InstallationManifest;
+
+mod common;
+
+const APP_ID: &str = "com.example.Hello";
+
+fn build(output: &Path, version: &str, files: &[(&str, &str)]) {
+    let mut writer = PackageWriter::with_mtime(File::create(output).unwrap(), 1_700_000_000);
+    for (path, content) in files {
+        let mode = if path.starts_with("bin/") { 0o755 } else { 0o644 };
+        writer.append_bytes(&format!("application/{path}"), content.as_bytes(), mode).unwrap();
+    }
+    let paths: Vec<&str> = files.iter().map(|(path, _)| *path).collect();
+    writer.append_metadata(&common::metadata(APP_ID, version, &paths)).unwrap();
+    writer.finish().unwrap();
+}
+
+fn install(package: &Path, prefix: &Path) -> anyhow::Result<InstallationManifest> {
+    perform_installation(package, prefix.to_str().unwrap(), false, ConflictResolution::Abort, &AtomicBool::new(false), |_| {})
+}
+
+#[test]
+fn delta_upgrades_the_base_installation() {
+    let home = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home.path());
+    let prefix = home.path().join("prefix");
+
+    let base = home.path().join("hello-1.0.lis");
+    let new = home.path().join("hello-1.1.lis");
+    let delta = home.path().join("hello-1.0-1.1.lis");
+    build(&base, "1.0", &[
+        ("bin/hello", "#!/bin/sh\necho 1.0\n"),
+        ("share/hello/data.txt", "same\n"),
+        ("share/hello/old.txt", "old\n"),
+    ]);
+    build(&new, "1.1", &[
+        ("bin/hello", "#!/bin/sh\necho 1.1\n"),
+        ("share/hello/data.txt", "same\n"),
+        ("share/hello/new.txt", "new\n"),
+    ]);
+
+    let summary = create_delta(&base, &new, &delta).unwrap();
+    assert_eq!((summary.shipped, summary.unchanged, summary.removed), (2, 1, 1));
+    let shipped: Vec<String> = LisPackage::entries(&delta).unwrap().into_iter().map(|e| e.path).collect();
+    assert_eq!(shipped, ["application/bin/hello", "application/share/hello/new.txt", "metadata.toml"]);
+
+    // The base version must be installed first
+    let error = install(&delta, &prefix).unwrap_err();
+    assert!(error.to_string().contains("which is not installed"), "{error:#}");
+
+    install(&base, &prefix).unwrap();
+
+    // A file taken from the installation must be the one the base version installed
+    fs::write(prefix.join("share/hello/data.txt"), "edited\n").unwrap();
+    let error = install(&delta, &prefix).unwrap_err();
+    assert!(error.to_string().contains("was modified after version 1.0"), "{error:#}");
+    assert_eq!(fs::read_to_string(prefix.join("bin/hello")).unwrap(), "#!/bin/sh\necho 1.0\n");
+    assert_eq!(InstallationManifest::load(APP_ID, false).unwrap().version, "1.0");
+
+    fs::write(prefix.join("share/hello/data.txt"), "same\n").unwrap();
+
+    // Only files the base installation has can be removed
+    let other_base = home.path().join("hello-1.0-other.lis");
+    let other_delta = home.path().join("hello-1.0-other-1.1.lis");
+    build(&other_base, "1.0", &[
+        ("bin/hello", "#!/bin/sh\necho 1.0\n"),
+        ("share/hello/data.txt", "same\n"),
+        ("share/hello/other.txt", "other\n"),
+    ]);
+    create_delta(&other_base, &new, &other_delta).unwrap();
+    let error = install(&other_delta, &prefix).unwrap_err();
+    assert!(error.to_string().contains("removes 'share/hello/other.txt'"), "{error:#}");
+
+    let manifest = install(&delta, &prefix).unwrap();
+    assert_eq!(manifest.version, "1.1");
+
+    assert_eq!(fs::read_to_string(prefix.join("bin/hello")).unwrap(), "#!/bin/sh\necho 1.1\n");
+    assert_eq!(fs::metadata(prefix.join("bin/hello")).unwrap().permissions().mode() & 0o777, 0o755);
+    assert_eq!(fs::read_to_string(prefix.join("share/hello/data.txt")).unwrap(), "same\n");
+    assert_eq!(fs::read_to_string(prefix.join("share/hello/new.txt")).unwrap(), "new\n");
+    assert!(!prefix.join("share/hello/old.txt").exists());
+
+    let mut installed = manifest.installed_files.clone();
+    installed.sort();
+    let expected: Vec<String> = ["bin/hello", "share/hello/data.txt", "share/hello/new.txt"].iter()
+        .map(|f| prefix.join(f).to_string_lossy().to_string())
+        .collect();
+    assert_eq!(installed, expected);
+    assert_eq!(InstallationManifest::load(APP_ID, false).unwrap().version, "1.1");
+}
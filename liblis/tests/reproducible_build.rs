@@ -0,0 +1,46 @@
+use std::fs::{self, File};
+use std::os::unix::fs::PermissionsExt;
+
+use liblis::format::{LisPackage, PackageWriter};
+
+mod common;
+
+#[test]
+fn entries_are_sorted_and_normalized() {
+    let staging = tempfile::tempdir().unwrap();
+    for (path, mode) in [("application/share/hello/a.txt", 0o600), ("application/bin/hello", 0o700)] {
+        let path = staging.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, path.to_string_lossy().as_bytes()).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    let package = staging.path().join("hello.lis");
+    let mut writer = PackageWriter::with_mtime(File::create(&package).unwrap(), 1_700_000_000);
+    writer.append_dir_all("application", &staging.path().join("application"), |_, _| Ok(())).unwrap();
+    writer.append_metadata(&common::metadata("com.example.Hello", "1.2.0", &["bin/hello", "share/hello/a.txt"])).unwrap();
+    writer.finish().unwrap();
+
+    let entries = LisPackage::entries(&package).unwrap();
+    let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, [
+        "application/bin",
+        "application/bin/hello",
+        "application/share",
+        "application/share/hello",
+        "application/share/hello/a.txt",
+        "metadata.toml",
+    ]);
+
+    let mode = |path: &str| entries.iter().find(|e| e.path == path).unwrap().mode;
+    assert_eq!(mode("application/bin/hello"), 0o755);
+    assert_eq!(mode("application/share/hello/a.txt"), 0o644);
+    assert_eq!(mode("application/share"), 0o755);
+
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(File::open(&package).unwrap()));
+    for entry in archive.entries().unwrap() {
+        let header = entry.unwrap().header().clone();
+        assert_eq!(header.mtime().unwrap(), 1_700_000_000);
+        assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+    }
+}
@@ -0,0 +1,301 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+
+use liblis::diff::{self, EntryChange};
+use liblis::format::{format_size, EntryKind, LisPackage, PackageEntry};
+use liblis::Metadata;
+
+/// `rwxr-xr-x` style permissions, prefixed by the entry type
+fn mode_string(kind: EntryKind, mode: u32) -> String {
+    let prefix = match kind {
+        EntryKind::File => '-',
+        EntryKind::Directory => 'd',
+        EntryKind::Symlink => 'l',
+        EntryKind::Other => '?',
+    };
+    let bits: String = (0..9)
+        .map(|i| {
+            let set = mode & (0o400 >> i) != 0;
+            match (set, i % 3) {
+                (false, _) => '-',
+                (true, 0) => 'r',
+                (true, 1) => 'w',
+                (true, _) => 'x',
+            }
+        })
+        .collect();
+    format!("{}{}", prefix, bits)
+}
+
+/// Split `<package.lis> [--json]` arguments
+fn package_and_json<'a>(command: &str, args: &'a [String]) -> Result<(&'a Path, bool)> {
+    match args {
+        [package] => Ok((Path::new(package), false)),
+        [package, flag] | [flag, package] if flag == "--json" => Ok((Path::new(package), true)),
+        _ => anyhow::bail!("lis {} needs a package and an optional --json", command),
+    }
+}
+
+pub fn run_info(args: &[String]) -> Result<()> {
+    let (package, json) = package_and_json("info", args)?;
+    let metadata = LisPackage::read_metadata(package)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+        return Ok(());
+    }
+
+    let size = std::fs::metadata(package)
+        .with_context(|| format!("Failed to read {}", package.display()))?
+        .len();
+    let installed_size = LisPackage::installed_size(package)?;
+    print_metadata(&metadata, size, installed_size);
+    Ok(())
+}
+
+fn print_metadata(metadata: &Metadata, size: u64, installed_size: u64) {
+    let field = |name: &str, value: &str| {
+        if !value.is_empty() {
+            println!("{:<14}{}", format!("{}:", name), value);
+        }
+    };
+    let package = &metadata.package;
+
+    field("Name", &package.application_name);
+    field("App ID", &package.app_id);
+    field("Version", &package.version);
+    field("Author", &package.author);
+    field("Description", &package.description);
+    field("Size", &format!("{} ({} installed)", format_size(size), format_size(installed_size)));
+    let platform = [&package.architecture, &package.libc].into_iter().flatten().cloned().collect::<Vec<_>>();
+    field("Platform", &platform.join(", "));
+    field("Prefix", &format!("{} (system), {} (user)", metadata.installation.prefix_system, metadata.installation.prefix_user));
+    field("Desktop", &format!("{} (exec: {})", metadata.desktop.name, metadata.desktop.exec));
+
+    if let Some(delta) = &metadata.delta {
+        field("Delta", &format!(
+            "applies to version {} ({} files unchanged, {} removed)",
+            delta.base_version, delta.unchanged.len(), delta.removed.len()
+        ));
+    }
+
+    let dependencies = &metadata.dependencies;
+    field("Requires", &dependencies.requires.join(", "));
+    field("Conflicts", &dependencies.conflicts.join(", "));
+    field("Provides", &dependencies.provides.join(", "));
+
+    let requirements = &metadata.requirements;
+    field("Min GTK", requirements.min_gtk_version.as_deref().unwrap_or_default());
+    field("Min Adwaita", requirements.min_libadwaita_version.as_deref().unwrap_or_default());
+    field("Architectures", &requirements.architectures.join(", "));
+    field("Libraries", &requirements.libraries.join(", "));
+
+    let hooks: Vec<String> = metadata.hooks.defined().iter()
+        .map(|(kind, script)| format!("{} ({})", kind, script))
+        .collect();
+    field("Hooks", &hooks.join(", "));
+
+    let screens: Vec<&str> = metadata.installer_screens.iter()
+        .filter(|s| s.enabled)
+        .map(|s| s.id.as_str())
+        .collect();
+    field("Screens", &screens.join(", "));
+
+    println!("Files ({}):", metadata.files.len());
+    for file in &metadata.files {
+        match &file.permissions {
+            Some(permissions) => println!("    {} <- {} ({})", file.destination, file.source, permissions),
+            None => println!("    {} <- {}", file.destination, file.source),
+        }
+    }
+}
+
+pub fn run_list(args: &[String]) -> Result<()> {
+    let (package, json) = package_and_json("list", args)?;
+    let entries = LisPackage::entries(package)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    for entry in &entries {
+        print_entry(entry);
+    }
+    let total: u64 = entries.iter().map(|e| e.size).sum();
+    println!("{} entries, {}", entries.len(), format_size(total));
+    Ok(())
+}
+
+fn print_entry(entry: &PackageEntry) {
+    let path = match &entry.link_target {
+        Some(target) => format!("{} -> {}", entry.path, target),
+        None => entry.path.clone(),
+    };
+    println!(
+        "{} {:>10}  {:<64}  {}",
+        mode_string(entry.kind, entry.mode),
+        entry.size,
+        entry.sha256.as_deref().unwrap_or("-"),
+        path
+    );
+}
+
+/// Archive path of a file given by name or by one of the shortcuts
+/// `metadata`, `desktop` and `license`
+fn resolve_path(package: &Path, name: &str) -> Result<String> {
+    match name {
+        "metadata" => Ok("metadata.toml".to_string()),
+        "desktop" => LisPackage::entries(package)?
+            .into_iter()
+            .find(|e| e.kind == EntryKind::File && e.path.starts_with("install/") && e.path.ends_with(".desktop"))
+            .map(|e| e.path)
+            .context("The package has no desktop file"),
+        "license" => {
+            // The license screen may name its file; otherwise look for a usual name
+            let metadata = LisPackage::read_metadata(package)?;
+            let entries = LisPackage::entries(package)?;
+            let named = metadata.installer_screens.iter()
+                .find(|s| s.id == "license")
+                .and_then(|s| s.custom_content.as_deref())
+                .and_then(|content| entries.iter().find(|e| e.kind == EntryKind::File && Path::new(&e.path).ends_with(content)));
+            let usual = || entries.iter().find(|e| {
+                let file_name = Path::new(&e.path).file_name().unwrap_or_default().to_string_lossy().to_uppercase();
+                e.kind == EntryKind::File && (file_name.starts_with("LICENSE") || file_name.starts_with("COPYING"))
+            });
+            named.or_else(usual)
+                .map(|e| e.path.clone())
+                .context("The package has no license file")
+        }
+        path => Ok(path.trim_start_matches("./").to_string()),
+    }
+}
+
+pub fn run_cat(args: &[String]) -> Result<()> {
+    let [package, name] = args else {
+        anyhow::bail!("lis cat needs a package and a path (or metadata, desktop, license)");
+    };
+    let package = Path::new(package);
+    let path = resolve_path(package, name)?;
+
+    let mut stdout = io::stdout().lock();
+    LisPackage::copy_file(package, &path, &mut stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+pub fn run_extract(args: &[String]) -> Result<()> {
+    let mut output = PathBuf::from(".");
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = PathBuf::from(iter.next().context("--output needs a directory")?),
+            _ => positional.push(arg),
+        }
+    }
+    let [package, name] = positional[..] else {
+        anyhow::bail!("lis extract needs a package and a path (or metadata, desktop, license)");
+    };
+    let package = Path::new(package);
+    let path = resolve_path(package, name)?;
+
+    for written in LisPackage::extract_path(package, &path, &output)? {
+        println!("{}", written.display());
+    }
+    Ok(())
+}
+
+/// Signed difference of two sizes, like "+1.2 KB"
+fn size_delta(old: u64, new: u64) -> String {
+    if new >= old {
+        format!("+{}", format_size(new - old))
+    } else {
+        format!("-{}", format_size(old - new))
+    }
+}
+
+pub fn run_diff(args: &[String]) -> Result<()> {
+    let (json, paths): (Vec<&String>, Vec<&String>) = args.iter().partition(|a| *a == "--json");
+    let [old, new] = paths[..] else {
+        anyhow::bail!("lis diff needs two packages and an optional --json");
+    };
+    let diff = diff::diff_packages(Path::new(old), Path::new(new))?;
+
+    if !json.is_empty() {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    if !diff.metadata.is_empty() {
+        println!("Metadata:");
+        for change in &diff.metadata {
+            let value = |v: &Option<String>| v.clone().unwrap_or_else(|| "(none)".to_string());
+            println!("  {}: {} -> {}", change.field, value(&change.old), value(&change.new));
+        }
+    }
+
+    if !diff.dependencies.is_empty() {
+        println!("Dependencies:");
+        for change in &diff.dependencies {
+            println!("  {} {} {}", if change.added { '+' } else { '-' }, change.list, change.entry);
+        }
+    }
+
+    if !diff.entries.is_empty() {
+        println!("Files:");
+        for change in &diff.entries {
+            match change {
+                EntryChange::Added { entry } => println!("  A {} ({})", entry.path, format_size(entry.size)),
+                EntryChange::Removed { entry } => println!("  D {} ({})", entry.path, format_size(entry.size)),
+                EntryChange::Modified { old, new } => {
+                    let mut details = Vec::new();
+                    if old.sha256 != new.sha256 || old.link_target != new.link_target {
+                        details.push(format!("content {}", size_delta(old.size, new.size)));
+                    }
+                    if old.kind != new.kind || old.mode != new.mode {
+                        details.push(format!("{} -> {}", mode_string(old.kind, old.mode), mode_string(new.kind, new.mode)));
+                    }
+                    println!("  M {} ({})", new.path, details.join(", "));
+                }
+            }
+        }
+    }
+
+    if !diff.destinations.is_empty() {
+        println!("Installed files:");
+        for change in &diff.destinations {
+            match (&change.old, &change.new) {
+                (None, Some(new)) => println!("  + {} <- {}", change.destination, new.source),
+                (Some(_), None) => println!("  - {}", change.destination),
+                (Some(old), Some(new)) => {
+                    let mut details = Vec::new();
+                    if old.source != new.source {
+                        details.push(format!("source {} -> {}", old.source, new.source));
+                    }
+                    if old.permissions != new.permissions {
+                        let permissions = |p: &Option<String>| p.clone().unwrap_or_else(|| "default".to_string());
+                        details.push(format!("permissions {} -> {}", permissions(&old.permissions), permissions(&new.permissions)));
+                    }
+                    println!("  ~ {} ({})", change.destination, details.join(", "));
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    if diff.is_empty() {
+        println!("The packages have the same contents");
+    }
+    println!(
+        "Size: {} -> {} ({}), installed {} -> {} ({})",
+        format_size(diff.old_size.package),
+        format_size(diff.new_size.package),
+        size_delta(diff.old_size.package, diff.new_size.package),
+        format_size(diff.old_size.installed),
+        format_size(diff.new_size.installed),
+        size_delta(diff.old_size.installed, diff.new_size.installed)
+    );
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::sync::atomic::AtomicBool;
+use anyhow::{Context, Result};
+
+use liblis::catalog::Catalog;
+use liblis::format::format_size;
+use liblis::delta;
+use liblis::install::{self, ConflictResolution};
+use liblis::updates::{self, Repository, Update};
+use liblis::LisPackage;
+
+mod inspect;
+
+const USAGE: &str = "Usage: lis info <package.lis> [--json]
+       lis list <package.lis> [--json]
+       lis cat <package.lis> <path|metadata|desktop|license>
+       lis extract [-o <dir>] <package.lis> <path|metadata|desktop|license>
+       lis diff <old.lis> <new.lis> [--json]
+       lis delta [-o <delta.lis>] <base.lis> <new.lis>
+       lis index <repository>
+       lis search <repository> [query]
+       lis updates [--repo <path|file://url>]... [--system] [--apply] [--notify]";
+
+/// Command line tool for .lis packages and repositories.
+///
+/// Usage:
+///   lis info <package.lis>            Show the package metadata (--json for JSON)
+///   lis list <package.lis>            List the archive entries with sizes, modes and hashes
+///   lis cat <package.lis> <path>      Print one file of the package to stdout
+///   lis extract <package.lis> <path>  Extract one file or directory of the package
+///   lis diff <old.lis> <new.lis>      Show what changed between two packages
+///   lis delta <base.lis> <new.lis>    Build a package with only the files changed since base
+///   lis index <repository>            Write the repository's index.json from its .lis files
+///   lis search <repository> [query]   List the newest version of the matching packages
+///   lis updates                       List (and with --apply, install) newer versions of the
+///                                     installed applications found in the repositories
+fn main() -> ExitCode {
+    // Exit quietly when the output is piped into a command that stops reading (e.g., head)
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("info") => inspect::run_info(&args[1..]),
+        Some("list") => inspect::run_list(&args[1..]),
+        Some("cat") => inspect::run_cat(&args[1..]),
+        Some("extract") => inspect::run_extract(&args[1..]),
+        Some("diff") => inspect::run_diff(&args[1..]),
+        Some("delta") => run_delta(&args[1..]),
+        Some("index") => run_index(&args[1..]),
+        Some("search") => run_search(&args[1..]),
+        Some("updates") => run_updates(&args[1..]),
+        Some("help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!(USAGE)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ERROR: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_delta(args: &[String]) -> Result<()> {
+    let mut output = None;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(iter.next().context("--output needs a file")?)),
+            _ => positional.push(arg),
+        }
+    }
+    let [base, new] = positional[..] else {
+        anyhow::bail!("lis delta needs a base package and a new package");
+    };
+    let (base, new) = (Path::new(base), Path::new(new));
+
+    // myapp-1.1.lis -> myapp-1.1.delta-1.0.lis, beside the new package
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let base_version = LisPackage::read_metadata(base)?.package.version;
+            let stem = new.file_stem().context("Invalid package name")?.to_string_lossy();
+            new.with_file_name(format!("{}.delta-{}.lis", stem, base_version))
+        }
+    };
+
+    let summary = delta::create_delta(base, new, &output)?;
+    println!(
+        "Wrote {} ({}): {} -> {}, {} files shipped, {} unchanged, {} removed",
+        output.display(),
+        format_size(summary.size),
+        summary.base_version,
+        summary.version,
+        summary.shipped,
+        summary.unchanged,
+        summary.removed
+    );
+    Ok(())
+}
+
+fn run_index(args: &[String]) -> Result<()> {
+    let [root] = args else {
+        anyhow::bail!("lis index needs exactly one repository directory");
+    };
+    let root = PathBuf::from(root);
+
+    let catalog = Catalog::generate(&root, |path, e| {
+        eprintln!("Skipping {}: {:#}", path.display(), e);
+    })?;
+    let index = catalog.save(&root)?;
+
+    let total: u64 = catalog.packages.iter().map(|p| p.size).sum();
+    println!(
+        "Indexed {} packages ({}) of {} applications into {}",
+        catalog.packages.len(),
+        format_size(total),
+        catalog.latest().len(),
+        index.display()
+    );
+    Ok(())
+}
+
+fn run_search(args: &[String]) -> Result<()> {
+    let (root, query) = match args {
+        [root] => (root, ""),
+        [root, query] => (root, query.as_str()),
+        _ => anyhow::bail!("lis search needs a repository and an optional query"),
+    };
+    let catalog = Catalog::load(Path::new(root))
+        .context("Run `lis index` on the repository first")?;
+
+    let mut app_ids: Vec<&str> = catalog.search(query).iter().map(|p| p.app_id.as_str()).collect();
+    app_ids.sort_unstable();
+    app_ids.dedup();
+    if app_ids.is_empty() {
+        println!("No packages found");
+    }
+    for package in app_ids.into_iter().filter_map(|app_id| catalog.newest(app_id)) {
+        println!("{} {} ({})", package.app_id, package.version, format_size(package.size));
+        println!("    {}", package.name);
+        if !package.description.is_empty() {
+            println!("    {}", package.description);
+        }
+    }
+    Ok(())
+}
+
+fn run_updates(args: &[String]) -> Result<()> {
+    let mut locations = Vec::new();
+    let mut include_system = false;
+    let mut apply = false;
+    let mut notify = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--repo" => locations.push(iter.next().context("--repo needs a value")?.clone()),
+            "--system" => include_system = true,
+            "--apply" => apply = true,
+            "--notify" => notify = true,
+            _ => anyhow::bail!("Unexpected argument '{}'", arg),
+        }
+    }
+
+    if locations.is_empty() {
+        locations = updates::configured_repositories();
+    }
+    if locations.is_empty() {
+        anyhow::bail!("No repositories configured: add them to ~/.config/obision-installer/repositories.conf or pass --repo");
+    }
+
+    // An unreachable repository (e.g., an unmounted share) doesn't hide the others' updates
+    let mut repositories = Vec::new();
+    for location in &locations {
+        match Repository::open(location) {
+            Ok(repository) => repositories.push(repository),
+            Err(e) => eprintln!("WARNING: {:#}", e),
+        }
+    }
+
+    let available = updates::find_updates(&repositories, include_system);
+    if notify {
+        return notify_updates(&available);
+    }
+
+    if available.is_empty() {
+        println!("All applications are up to date");
+        return Ok(());
+    }
+    for update in &available {
+        println!(
+            "{} {} -> {} ({}, {} from {})",
+            update.installed.app_id,
+            update.installed.version,
+            update.available.version,
+            if update.for_all_users { "all users" } else { "user" },
+            format_size(update.available.size),
+            update.repository.display()
+        );
+    }
+    if !apply {
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for update in &available {
+        println!("Updating {} to {}...", update.installed.app_name, update.available.version);
+        if let Err(e) = apply_update(update) {
+            eprintln!("ERROR: {} was not updated: {:#}", update.installed.app_name, e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} updates failed", failed, available.len());
+    }
+    Ok(())
+}
+
+/// Install the new version over the installed one, in the same prefix
+fn apply_update(update: &Update) -> Result<()> {
+    if update.for_all_users && unsafe { libc::geteuid() } != 0 {
+        anyhow::bail!("it is installed for all users; run lis updates --system --apply as root");
+    }
+    let package = update.package_path()?;
+    let cancel = AtomicBool::new(false);
+    install::perform_installation(
+        &package,
+        &update.installed.prefix,
+        update.for_all_users,
+        ConflictResolution::Abort,
+        &cancel,
+        |_| {},
+    )?;
+    Ok(())
+}
+
+/// Show a desktop notification listing the updates; nothing when there are none
+fn notify_updates(available: &[Update]) -> Result<()> {
+    if available.is_empty() {
+        return Ok(());
+    }
+
+    let summary = match available.len() {
+        1 => "1 application update available".to_string(),
+        n => format!("{} application updates available", n),
+    };
+    let body = available.iter()
+        .map(|u| format!("{} {}", u.installed.app_name, u.available.version))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let status = Command::new("notify-send")
+        .args(["--app-name", "Obision AppInstall", "--icon", "system-software-update", &summary, &body])
+        .status()
+        .context("Failed to run notify-send")?;
+    if !status.success() {
+        anyhow::bail!("notify-send failed");
+    }
+    Ok(())
+}